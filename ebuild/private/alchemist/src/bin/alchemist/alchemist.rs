@@ -128,6 +128,7 @@ fn setup_tools() -> Result<TempDir> {
 
     fs::symlink(&current_exec, tools_dir.path().join("ver_test"))?;
     fs::symlink(&current_exec, tools_dir.path().join("ver_rs"))?;
+    fs::symlink(&current_exec, tools_dir.path().join("ver_cut"))?;
 
     Ok(tools_dir)
 }
@@ -6,12 +6,17 @@ mod alchemist;
 mod digest_repo;
 mod dump_package;
 mod generate_repo;
+mod range;
+mod ver_cut;
+mod ver_rs;
 mod ver_test;
 
 use std::process::ExitCode;
 
 use crate::alchemist::alchemist_main;
 use clap::{Parser, Subcommand};
+use ver_cut::ver_cut_main;
+use ver_rs::ver_rs_main;
 use ver_test::ver_test_main;
 
 #[derive(Parser, Debug)]
@@ -25,6 +30,12 @@ struct Cli {
 enum Executables {
     Alchemist(alchemist::Args),
 
+    #[command(name = "ver_cut")] // Otherwise we get ver-cut
+    VerCut(ver_cut::Args),
+
+    #[command(name = "ver_rs")] // Otherwise we get ver-rs
+    VerRs(ver_rs::Args),
+
     #[command(name = "ver_test")] // Otherwise we get ver-test
     VerTest(ver_test::Args),
 }
@@ -32,6 +43,8 @@ enum Executables {
 fn main() -> ExitCode {
     let result = match Cli::parse().executables {
         Executables::Alchemist(args) => alchemist_main(args),
+        Executables::VerCut(args) => ver_cut_main(args),
+        Executables::VerRs(args) => ver_rs_main(args),
         Executables::VerTest(args) => ver_test_main(args),
     };
     match result {
@@ -0,0 +1,39 @@
+// Copyright 2026 The ChromiumOS Authors.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! The `<range>` argument shared by `ver_rs` and `ver_cut`: a single 1-based index (`N`), an
+//! open-ended tail (`N-`), or an inclusive pair (`N-M`).
+
+use anyhow::Result;
+use nom::bytes::complete::tag;
+use nom::character::complete::digit1;
+use nom::combinator::eof;
+use nom::sequence::preceded;
+use nom::{
+    combinator::{map_res, opt},
+    sequence::tuple,
+    IResult,
+};
+
+// We don't use Range or RangeFrom because they are non-object safe :/
+fn range_expression(input: &str) -> IResult<&str, (u32, Option<u32>)> {
+    let (input, (start, range)) = tuple((
+        map_res(digit1, str::parse::<u32>),
+        opt(preceded(tag("-"), opt(map_res(digit1, str::parse::<u32>)))),
+    ))(input)?;
+
+    let (input, _) = eof(input)?;
+
+    let end = match range {
+        Some(end) => end,
+        None => Some(start),
+    };
+
+    Ok((input, (start, end)))
+}
+
+pub fn parse_range(input: &str) -> Result<(u32, Option<u32>)> {
+    let (_, result) = range_expression(input).map_err(|err| err.to_owned())?;
+    Ok(result)
+}
@@ -0,0 +1,145 @@
+// Copyright 2026 The ChromiumOS Authors.
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::process::exit;
+
+use alchemist::simpleversion::{VersionComponent, VersionComponents};
+use anyhow::{bail, Context, Result};
+use clap::{arg, command, Parser};
+
+use super::range::parse_range;
+
+#[derive(Parser, Debug, PartialEq, Eq)]
+#[command(name = "ver_cut")]
+#[command(author = "ChromiumOS Authors")]
+#[command(about = "Extracts a range of package version components", long_about = None)]
+pub struct Args {
+    // We need to use a Vec because having an optional trailing version
+    // parameter is not supported when using allow_hyphen_values.
+    // See https://github.com/clap-rs/clap/issues/4649
+    #[arg(allow_hyphen_values = true)]
+    args: Vec<String>,
+}
+
+fn processes(args: Args) -> Result<String> {
+    let mut args = args.args;
+
+    let version: String = match args.len() {
+        1 => std::env::var("PV").context("PV environment variable is not set")?,
+        2 => args.pop().unwrap(), // Checked size above
+        _ => bail!("Usage: ver_cut <range> [<version>]"),
+    };
+    let range = args.pop().unwrap(); // Checked size above
+
+    let (start, end) = parse_range(&range).with_context(|| format!("Failed to parse '{}'", range))?;
+    if start == 0 {
+        bail!("Range start must be at least 1");
+    }
+
+    let components: VersionComponents = version.parse()?;
+
+    // Unlike ver_rs's separators, ver_cut's range counts only `Component`s, numbered starting
+    // from 1. We record each component's position in `components.components` so the slice we
+    // print can include whatever separators fall between the first and last selected component.
+    let component_positions: Vec<usize> = components
+        .components
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, component)| match component {
+            VersionComponent::Component(_) => Some(pos),
+            VersionComponent::Separator(_) => None,
+        })
+        .collect();
+
+    let start = start as usize;
+    let end = end.map(|end| end as usize).unwrap_or(component_positions.len());
+    if start > component_positions.len() || end < start {
+        return Ok(String::new());
+    }
+    let end = end.min(component_positions.len());
+
+    let start_pos = component_positions[start - 1];
+    let end_pos = component_positions[end - 1];
+
+    let mut result = String::new();
+    for component in &components.components[start_pos..=end_pos] {
+        match component {
+            VersionComponent::Component(s) => result.push_str(s),
+            VersionComponent::Separator(s) => result.push_str(s),
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn ver_cut_main(args: Args) -> Result<()> {
+    match processes(args) {
+        Ok(result) => {
+            println!("{}", result);
+            exit(0)
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table() -> Result<()> {
+        // Same example inputs as ver_rs's and simpleversion's test tables, hand-derived against
+        // this crate's own VersionComponents parsing (1-based over components only, range end
+        // counted inclusively, interior separators carried along in the printed slice).
+        let inputs = ["1.2.3", "2 Ab 9 s", "A.4.", ".11.2."];
+        let table = [
+            ("1", ["1", "2", "A", "11"]),
+            ("2", ["2", "Ab", "4", "2"]),
+            ("1-2", ["1.2", "2 Ab", "A.4", "11.2"]),
+            ("2-", ["2.3", "Ab 9 s", "4", "2"]),
+        ];
+
+        for (range, expected_values) in table {
+            for (i, input) in inputs.iter().enumerate() {
+                let args = Args {
+                    args: vec![range.to_string(), input.to_string()],
+                };
+
+                let result = processes(args)?;
+
+                assert_eq!(expected_values[i], result);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_three_and_four() -> Result<()> {
+        let args = Args {
+            args: vec!["3".to_string(), "2 Ab 9 s".to_string()],
+        };
+        assert_eq!("9", processes(args)?);
+
+        let args = Args {
+            args: vec!["3-4".to_string(), "2 Ab 9 s".to_string()],
+        };
+        assert_eq!("9 s", processes(args)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_out_of_range_is_empty() -> Result<()> {
+        let args = Args {
+            args: vec!["5".to_string(), "1.2.3".to_string()],
+        };
+        assert_eq!("", processes(args)?);
+
+        Ok(())
+    }
+}
@@ -8,15 +8,7 @@ use alchemist::simpleversion::{VersionComponent, VersionComponents};
 use anyhow::{bail, Context, Result};
 use clap::{arg, command, Parser};
 
-use nom::bytes::complete::tag;
-use nom::character::complete::digit1;
-use nom::combinator::eof;
-use nom::sequence::preceded;
-use nom::{
-    combinator::{map_res, opt},
-    sequence::tuple,
-    IResult,
-};
+use super::range::parse_range;
 
 #[derive(Parser, Debug, PartialEq, Eq)]
 #[command(name = "ver_rs")]
@@ -72,28 +64,6 @@ fn processes(args: Args) -> Result<String> {
     Ok(format!("{}", components))
 }
 
-// We don't use Range or RangeFrom because they are non-object safe :/
-fn range_expression(input: &str) -> IResult<&str, (u32, Option<u32>)> {
-    let (input, (start, range)) = tuple((
-        map_res(digit1, str::parse::<u32>),
-        opt(preceded(tag("-"), opt(map_res(digit1, str::parse::<u32>)))),
-    ))(input)?;
-
-    let (input, _) = eof(input)?;
-
-    let end = match range {
-        Some(end) => end,
-        None => Some(start),
-    };
-
-    Ok((input, (start, end)))
-}
-
-fn parse_range(input: &str) -> Result<(u32, Option<u32>)> {
-    let (_, result) = range_expression(input).map_err(|err| err.to_owned())?;
-    Ok(result)
-}
-
 pub fn ver_rs_main(args: Args) -> Result<()> {
     match processes(args) {
         Ok(result) => {
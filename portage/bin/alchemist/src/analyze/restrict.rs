@@ -71,6 +71,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         }
     }
 
@@ -721,6 +721,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         };
 
         Ok((package, tmp))
@@ -882,6 +883,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         };
         let (local_sources, repo_sources) =
             extract_cros_workon_sources(&package, Path::new("/src"))?;
@@ -1005,6 +1007,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         };
         let (local_sources, repo_sources) =
             extract_cros_workon_sources(&package, Path::new("/src"))?;
@@ -1106,6 +1109,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         };
         let (local_sources, repo_sources) =
             extract_cros_workon_sources(&package, Path::new("/src"))?;
@@ -1231,6 +1235,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         };
         let (local_sources, repo_sources) =
             extract_cros_workon_sources(&package, &dir.join("src"))?;
@@ -1317,6 +1322,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         };
         let (local_sources, repo_sources) =
             extract_cros_workon_sources(&package, &dir.join("src"))?;
@@ -1403,6 +1409,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         }
     }
 
@@ -1569,6 +1576,7 @@ mod tests {
             inherit_paths: vec![],
             direct_build_target: None,
             bazel_metadata: Default::default(),
+            warnings: Vec::new(),
         };
         let (local_sources, repo_sources) =
             extract_cros_workon_sources(&package, &dir.join("src"))?;
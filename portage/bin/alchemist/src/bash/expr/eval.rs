@@ -0,0 +1,25 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use anyhow::Result;
+
+use crate::data::UseMap;
+
+use super::Expr;
+
+pub(super) fn eval(expr: &Expr, map: &UseMap) -> Result<bool> {
+    Ok(match expr {
+        Expr::Literal(value) => *value,
+        Expr::Flag(name) => *map.get(name).unwrap_or(&false),
+        Expr::Not(inner) => !eval(inner, map)?,
+        Expr::And(lhs, rhs) => eval(lhs, map)? && eval(rhs, map)?,
+        Expr::Or(lhs, rhs) => eval(lhs, map)? || eval(rhs, map)?,
+        Expr::AnyOf(children) => children.iter().try_fold(false, |found, child| {
+            Ok::<_, anyhow::Error>(found || eval(child, map)?)
+        })?,
+        Expr::AllOf(children) => children.iter().try_fold(true, |all, child| {
+            Ok::<_, anyhow::Error>(all && eval(child, map)?)
+        })?,
+    })
+}
@@ -0,0 +1,231 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use anyhow::{Error, Result};
+use std::str::FromStr;
+
+mod eval;
+mod parser;
+
+use crate::data::UseMap;
+
+use self::parser::expression;
+
+/// A boolean USE-flag expression, as found in `supports_interface_libraries` and similar
+/// `[bazel]` metadata keys.
+///
+/// Grammar:
+/// ```text
+/// expr   := or
+/// or     := and ( "||" and )*
+/// and    := atom ( "&&" atom )*
+/// atom   := "true" | "false" | "use" ["!"] FLAG | any-of | all-of | "(" expr ")"
+/// any-of := "any-of" "(" expr+ ")"
+/// all-of := "all-of" "(" expr+ ")"
+/// ```
+/// `any-of`/`all-of` mirror Portage's `||( )`/`( )` dependency group syntax. Unknown flags
+/// evaluate to `false` rather than erroring, matching how `||(` and USE-conditional dependency
+/// groups treat unset flags elsewhere in this crate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BashExpr {
+    expr: Expr,
+}
+
+/// The parsed AST of a [`BashExpr`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Expr {
+    Literal(bool),
+    /// `use FLAG`. `use !FLAG` is represented as `Not(Box::new(Flag(FLAG)))`.
+    Flag(String),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    /// `any-of ( a b c )`: true iff at least one child is true.
+    AnyOf(Vec<Expr>),
+    /// `all-of ( a b c )`: true iff every child is true.
+    AllOf(Vec<Expr>),
+}
+
+impl FromStr for BashExpr {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (_, expr) = expression(input).map_err(|err| err.to_owned())?;
+        Ok(BashExpr { expr })
+    }
+}
+
+impl BashExpr {
+    /// Evaluates this expression against `map`, treating any USE flag absent from `map` as unset.
+    pub fn eval(&self, map: &UseMap) -> Result<bool> {
+        self::eval::eval(&self.expr, map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_true() -> Result<()> {
+        let expr = BashExpr::from_str("true")?;
+
+        assert!(expr.eval(&UseMap::default())?, "expr = {:?}", expr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_false() -> Result<()> {
+        let expr = BashExpr::from_str("false")?;
+
+        assert!(!expr.eval(&UseMap::default())?, "expr = {:?}", expr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_unknown_command() {
+        assert!(BashExpr::from_str("echo hello world").is_err());
+    }
+
+    #[test]
+    fn test_parse_use_true() -> Result<()> {
+        let expr = BashExpr::from_str("use foo")?;
+        let map = UseMap::from([("foo".to_owned(), true)]);
+
+        assert!(expr.eval(&map)?, "expr = {:?}", expr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_use_false() -> Result<()> {
+        let expr = BashExpr::from_str("use foo")?;
+        let map = UseMap::from([("foo".to_owned(), false)]);
+
+        assert!(!expr.eval(&map)?, "expr = {:?}", expr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_use_unknown_flag_defaults_false() -> Result<()> {
+        let expr = BashExpr::from_str("use foo")?;
+
+        assert!(!expr.eval(&UseMap::default())?, "expr = {:?}", expr);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_use_not() -> Result<()> {
+        let expr = BashExpr::from_str("use !foo")?;
+
+        assert!(expr.eval(&UseMap::from([("foo".to_owned(), false)]))?);
+        assert!(!expr.eval(&UseMap::from([("foo".to_owned(), true)]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_and() -> Result<()> {
+        let expr = BashExpr::from_str("use foo && use bar")?;
+
+        assert!(expr.eval(&UseMap::from([
+            ("foo".to_owned(), true),
+            ("bar".to_owned(), true)
+        ]))?);
+        assert!(!expr.eval(&UseMap::from([
+            ("foo".to_owned(), true),
+            ("bar".to_owned(), false)
+        ]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_or() -> Result<()> {
+        let expr = BashExpr::from_str("use foo || use bar")?;
+
+        assert!(expr.eval(&UseMap::from([
+            ("foo".to_owned(), false),
+            ("bar".to_owned(), true)
+        ]))?);
+        assert!(!expr.eval(&UseMap::from([
+            ("foo".to_owned(), false),
+            ("bar".to_owned(), false)
+        ]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_parens_override_precedence() -> Result<()> {
+        // Without the parens `&&` binds tighter than `||`, so this parses as
+        // `foo || (bar && baz)`; the map below tells the two groupings apart.
+        let without_parens = BashExpr::from_str("use foo || use bar && use baz")?;
+        let with_parens = BashExpr::from_str("(use foo || use bar) && use baz")?;
+
+        let map = UseMap::from([
+            ("foo".to_owned(), true),
+            ("bar".to_owned(), false),
+            ("baz".to_owned(), false),
+        ]);
+
+        // `foo || (bar && baz)` == true (foo alone), `(foo || bar) && baz` == false (baz unset).
+        assert!(without_parens.eval(&map)?);
+        assert!(!with_parens.eval(&map)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_any_of() -> Result<()> {
+        let expr = BashExpr::from_str("any-of ( use foo use bar )")?;
+
+        assert!(expr.eval(&UseMap::from([("bar".to_owned(), true)]))?);
+        assert!(!expr.eval(&UseMap::default())?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_all_of() -> Result<()> {
+        let expr = BashExpr::from_str("all-of ( use foo use bar )")?;
+
+        assert!(expr.eval(&UseMap::from([
+            ("foo".to_owned(), true),
+            ("bar".to_owned(), true)
+        ]))?);
+        assert!(!expr.eval(&UseMap::from([("foo".to_owned(), true)]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_nested_any_of_all_of() -> Result<()> {
+        let expr = BashExpr::from_str("any-of ( all-of ( use foo use bar ) use baz )")?;
+
+        assert!(expr.eval(&UseMap::from([("baz".to_owned(), true)]))?);
+        assert!(expr.eval(&UseMap::from([
+            ("foo".to_owned(), true),
+            ("bar".to_owned(), true)
+        ]))?);
+        assert!(!expr.eval(&UseMap::from([("foo".to_owned(), true)]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_trailing_garbage_is_an_error() {
+        assert!(BashExpr::from_str("use foo bar").is_err());
+        assert!(BashExpr::from_str("use foo )").is_err());
+    }
+
+    #[test]
+    fn test_parse_empty_is_an_error() {
+        assert!(BashExpr::from_str("").is_err());
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use nom::{
+    branch::alt,
+    bytes::complete::tag,
+    character::complete::{multispace0, multispace1},
+    combinator::{eof, map, opt},
+    multi::{many0, separated_list1},
+    sequence::{delimited, pair, preceded},
+    IResult,
+};
+use nom_regex::str::re_find;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use super::Expr;
+
+/// Matches a valid USE flag name, the same shape dependency expressions use for USE-conditional
+/// groups (`foo? ( ... )`).
+static USE_NAME_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^[A-Za-z0-9][A-Za-z0-9+_@-]*").unwrap());
+
+fn flag_name(input: &str) -> IResult<&str, &str> {
+    re_find(USE_NAME_RE.clone())(input)
+}
+
+/// Parses `use FLAG` or `use !FLAG`.
+fn use_expr(input: &str) -> IResult<&str, Expr> {
+    let (input, _) = tag("use")(input)?;
+    let (input, _) = multispace1(input)?;
+    let (input, negated) = opt(tag("!"))(input)?;
+    let (input, name) = flag_name(input)?;
+
+    let flag = Expr::Flag(name.to_owned());
+    Ok((
+        input,
+        if negated.is_some() {
+            Expr::Not(Box::new(flag))
+        } else {
+            flag
+        },
+    ))
+}
+
+fn literal_expr(input: &str) -> IResult<&str, Expr> {
+    alt((
+        map(tag("true"), |_| Expr::Literal(true)),
+        map(tag("false"), |_| Expr::Literal(false)),
+    ))(input)
+}
+
+/// Parses a parenthesized sub-expression used for grouping, e.g. `(use foo || use bar)`.
+fn parenthesized_expr(input: &str) -> IResult<&str, Expr> {
+    delimited(
+        pair(tag("("), multispace0),
+        expr,
+        pair(multispace0, tag(")")),
+    )(input)
+}
+
+/// Parses the `( expr expr ... )` children of an `any-of`/`all-of` group, space-separated like
+/// Portage's `||( )` dependency groups.
+fn group_children(input: &str) -> IResult<&str, Vec<Expr>> {
+    delimited(
+        pair(tag("("), multispace0),
+        |input| separated_list1(multispace1, expr)(input),
+        pair(multispace0, tag(")")),
+    )(input)
+}
+
+fn any_of_expr(input: &str) -> IResult<&str, Expr> {
+    map(
+        preceded(pair(tag("any-of"), multispace0), group_children),
+        Expr::AnyOf,
+    )(input)
+}
+
+fn all_of_expr(input: &str) -> IResult<&str, Expr> {
+    map(
+        preceded(pair(tag("all-of"), multispace0), group_children),
+        Expr::AllOf,
+    )(input)
+}
+
+fn atom(input: &str) -> IResult<&str, Expr> {
+    alt((
+        literal_expr,
+        use_expr,
+        any_of_expr,
+        all_of_expr,
+        parenthesized_expr,
+    ))(input)
+}
+
+/// Left-associative `&&`, binding tighter than `||`.
+fn and_expr(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = atom(input)?;
+    let (input, rest) = many0(preceded(
+        delimited(multispace0, tag("&&"), multispace0),
+        atom,
+    ))(input)?;
+
+    Ok((
+        input,
+        rest.into_iter()
+            .fold(first, |lhs, rhs| Expr::And(Box::new(lhs), Box::new(rhs))),
+    ))
+}
+
+/// Left-associative `||`, binding looser than `&&`.
+fn or_expr(input: &str) -> IResult<&str, Expr> {
+    let (input, first) = and_expr(input)?;
+    let (input, rest) = many0(preceded(
+        delimited(multispace0, tag("||"), multispace0),
+        and_expr,
+    ))(input)?;
+
+    Ok((
+        input,
+        rest.into_iter()
+            .fold(first, |lhs, rhs| Expr::Or(Box::new(lhs), Box::new(rhs))),
+    ))
+}
+
+fn expr(input: &str) -> IResult<&str, Expr> {
+    or_expr(input)
+}
+
+/// Parses a complete [`super::BashExpr`] expression, requiring the whole input to be consumed.
+pub fn expression(input: &str) -> IResult<&str, Expr> {
+    let (input, _) = multispace0(input)?;
+    let (input, parsed) = expr(input)?;
+    let (input, _) = multispace0(input)?;
+    let (input, _) = eof(input)?;
+
+    Ok((input, parsed))
+}
@@ -3,10 +3,11 @@
 // found in the LICENSE file.
 
 use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Represents a shell variable value in bash.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub enum BashValue {
     Scalar(String),
     IndexedArray(Vec<String>),
@@ -15,7 +16,7 @@ pub enum BashValue {
 
 /// Represents a set of [`BashValue`]. It wraps [`HashMap`] but provides methods
 /// for easier access.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 pub struct BashVars {
     values: HashMap<String, BashValue>,
 }
@@ -12,6 +12,7 @@ use crate::digest_repo::digest_repo_main;
 use crate::dump_package::dump_package_main;
 use crate::dump_profile::dump_profile_main;
 use crate::generate_repo::generate_repo_main;
+use crate::verify_config::verify_config_main;
 
 use alchemist::config::makeconf::generate::MAKEOPTS_VALUE;
 use alchemist::data::Vars;
@@ -23,7 +24,7 @@ use alchemist::{
         ConfigSource, PackageMaskKind, PackageMaskUpdate, SimpleConfigSource, UseUpdate,
         UseUpdateFilter, UseUpdateKind,
     },
-    ebuild::{metadata::CachedEBuildEvaluator, CachedPackageLoader, PackageLoader},
+    ebuild::{metadata::CachedEBuildEvaluator, CachedPackageLoader, LocalDiskCache, PackageLoader},
     fakechroot::{enter_fake_chroot, PathTranslator},
     repository::RepositorySet,
     resolver::PackageResolver,
@@ -78,6 +79,18 @@ pub struct Args {
     #[arg(long, value_name = "PROFILE", default_value = "sdk/bootstrap")]
     host_profile: String,
 
+    /// Name of the build machine's repository, when it's a Canadian cross (CBUILD != CHOST):
+    /// the SDK that builds `--host-board`'s packages is itself cross-compiled, so its BDEPEND /
+    /// IDEPEND dependencies must be satisfied from a distinct build-machine package set instead
+    /// of the host's. If unset, the build machine isn't distinguished from the host, which is
+    /// the overwhelmingly common case.
+    #[arg(long, value_name = "NAME")]
+    build_board: Option<String>,
+
+    /// Profile of the build machine. Only meaningful when `--build-board` is set.
+    #[arg(long, value_name = "PROFILE", default_value = "base")]
+    build_profile: String,
+
     /// Uses the Portage site configs found at `/etc` and `/build/$BOARD/etc`.
     ///
     /// If this flag is set to false, Portage site configs internally generated
@@ -128,6 +141,18 @@ pub struct Args {
     #[arg(short = 's', long, value_name = "DIR")]
     source_dir: Option<String>,
 
+    /// Enables the sccache-style shared ebuild evaluation cache, backed by a local directory at
+    /// this path. If unset, every ebuild is evaluated fresh (beyond the in-process cache shared
+    /// by this one invocation).
+    #[arg(long, value_name = "DIR")]
+    ebuild_cache_dir: Option<PathBuf>,
+
+    /// Enables the persistent config bundle cache, backed by a local directory at this path, so
+    /// repeated invocations skip re-evaluating the profile/make.conf/package.* chain when none of
+    /// it changed. If unset, the config is always rebuilt from scratch.
+    #[arg(long, value_name = "DIR")]
+    config_cache_dir: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -144,6 +169,13 @@ pub enum Commands {
         #[command(flatten)]
         args: crate::dump_profile::Args,
     },
+    /// Cross-checks ConfigBundle's computed USE flags and masked status against the real
+    /// `portageq`/`equery` tools for the same sysroot. Requires running inside the CrOS SDK
+    /// chroot.
+    VerifyConfig {
+        #[command(flatten)]
+        args: crate::verify_config::Args,
+    },
     /// Generates a Bazel repository containing overlays and packages.
     GenerateRepo {
         /// Output directory path.
@@ -153,6 +185,23 @@ pub enum Commands {
         #[arg(long)]
         /// An output path for a json-encoded Vec<deps::Repository>.
         output_repos_json: PathBuf,
+
+        /// The package atom treated as the SDK's implicit system set: packages it provides that
+        /// don't need to be listed as dependencies of other host packages. Individual ebuilds can
+        /// still opt out of this filtering with `inhibit_implicit_system = true` in their `[bazel]`
+        /// metadata.
+        #[arg(long, default_value = "virtual/target-sdk-implicit-system")]
+        implicit_system_atom: String,
+
+        /// Path to the distfile reproducibility lockfile. Written on a normal run; read and
+        /// diffed against instead when `--verify` is passed.
+        #[arg(long, value_name = "PATH")]
+        lockfile: PathBuf,
+
+        /// Instead of writing `lockfile`, regenerate it in memory and fail if it differs from the
+        /// committed file at that path (added/removed entries, or a drifted sha256/size).
+        #[arg(long)]
+        verify_lockfile: bool,
     },
     /// Generates a digest of the repository that can be used to indicate if
     /// any of the overlays, ebuilds, eclasses, etc have changed.
@@ -311,6 +360,7 @@ fn load_board(
     root_dir: &Path,
     use_portage_site_configs: bool,
     force_accept_9999_ebuilds: bool,
+    config_cache_dir: Option<&Path>,
 ) -> Result<TargetData> {
     let repos = Arc::new(repos);
 
@@ -322,13 +372,20 @@ fn load_board(
 
         let profile_path = profile.profile_path().to_path_buf();
 
+        let sources = vec![
+            // The order matters.
+            Box::new(profile) as Box<dyn ConfigSource>,
+            Box::new(site_settings) as Box<dyn ConfigSource>,
+            Box::new(override_source) as Box<dyn ConfigSource>,
+        ];
+
         (
-            ConfigBundle::from_sources(vec![
-                // The order matters.
-                Box::new(profile) as Box<dyn ConfigSource>,
-                Box::new(site_settings) as Box<dyn ConfigSource>,
-                Box::new(override_source) as Box<dyn ConfigSource>,
-            ]),
+            match config_cache_dir {
+                // Cache entries are keyed by board (each board gets its own subdirectory), since
+                // the host and a target board evaluate different sources under the same process.
+                Some(dir) => ConfigBundle::from_sources_cached(sources, &dir.join(board))?,
+                None => ConfigBundle::from_sources(sources),
+            },
             profile_path,
         )
     };
@@ -395,12 +452,20 @@ pub fn alchemist_main(args: Args) -> Result<()> {
         None
     };
 
+    let build_target = args
+        .build_board
+        .as_ref()
+        .map(|board| fakechroot::BoardTarget {
+            board,
+            profile: &args.build_profile,
+        });
+
     // Enter a fake chroot when running outside a cros chroot.
     let translator = if args.use_portage_site_configs {
         // TODO: What do we do here?
         PathTranslator::noop()
     } else {
-        let targets = if let Some(board_target) = board_target.as_ref() {
+        let mut targets = if let Some(board_target) = board_target.as_ref() {
             if board_target.board == host_target.board {
                 vec![&host_target]
             } else {
@@ -409,6 +474,11 @@ pub fn alchemist_main(args: Args) -> Result<()> {
         } else {
             vec![&host_target]
         };
+        if let Some(build_target) = build_target.as_ref() {
+            if build_target.board != host_target.board {
+                targets.push(build_target);
+            }
+        }
         enter_fake_chroot(&targets, &source_dir)?
     };
 
@@ -440,6 +510,32 @@ pub fn alchemist_main(args: Args) -> Result<()> {
         None
     };
 
+    let build_data = if let Some(build_target) = build_target {
+        let root_dir = Path::new("/build").join(build_target.board);
+        if is_inside_chroot()? && !root_dir.try_exists()? {
+            bail!(
+                "\n\
+                *****\n\
+                \t\tYou are running inside the CrOS SDK and `{}` doesn't exist.\n\
+                \n\
+                \t\tPlease run the following command to create the build machine's sysroot and \
+                try again:\n\
+                \t\t$ setup_board --board {} --profile {}\n\
+                \n\
+                *****",
+                root_dir.display(),
+                build_target.board,
+                build_target.profile,
+            );
+        }
+
+        let repos = RepositorySet::load("build", &root_dir)?;
+
+        Some((root_dir, repos, build_target))
+    } else {
+        None
+    };
+
     let host_data = {
         let root_dir = Path::new("/build").join(host_target.board);
         if is_inside_chroot()? && !root_dir.try_exists()? {
@@ -462,17 +558,26 @@ pub fn alchemist_main(args: Args) -> Result<()> {
         (root_dir, repos, host_target)
     };
 
-    // We share an evaluator between both config ROOTS so we only have to parse
+    // We share an evaluator between all config ROOTS so we only have to parse
     // the ebuilds once.
-    let evaluator = Arc::new(CachedEBuildEvaluator::new(
-        [target_data.as_ref().map(|x| &x.1), Some(&host_data.1)]
-            .into_iter()
-            .flatten()
-            .flat_map(|x| x.get_repos())
-            .cloned()
-            .collect(),
+    let evaluator = CachedEBuildEvaluator::new(
+        [
+            target_data.as_ref().map(|x| &x.1),
+            build_data.as_ref().map(|x| &x.1),
+            Some(&host_data.1),
+        ]
+        .into_iter()
+        .flatten()
+        .flat_map(|x| x.get_repos())
+        .cloned()
+        .collect(),
         tools_dir.path(),
-    ));
+    );
+    let evaluator = match args.ebuild_cache_dir {
+        Some(cache_dir) => evaluator.with_shared_cache(Arc::new(LocalDiskCache::new(cache_dir)))?,
+        None => evaluator,
+    };
+    let evaluator = Arc::new(evaluator);
 
     let target = if let Some((root_dir, repos, board_target)) = target_data {
         Some(load_board(
@@ -483,6 +588,22 @@ pub fn alchemist_main(args: Args) -> Result<()> {
             &root_dir,
             args.use_portage_site_configs,
             args.force_accept_9999_ebuilds,
+            args.config_cache_dir.as_deref(),
+        )?)
+    } else {
+        None
+    };
+
+    let build = if let Some((root_dir, repos, build_target)) = build_data {
+        Some(load_board(
+            repos,
+            &evaluator,
+            build_target.board,
+            build_target.profile,
+            &root_dir,
+            args.use_portage_site_configs,
+            args.force_accept_9999_ebuilds,
+            args.config_cache_dir.as_deref(),
         )?)
     } else {
         None
@@ -498,6 +619,7 @@ pub fn alchemist_main(args: Args) -> Result<()> {
             &root_dir,
             args.use_portage_site_configs,
             args.force_accept_9999_ebuilds,
+            args.config_cache_dir.as_deref(),
         )?,
     };
 
@@ -508,17 +630,27 @@ pub fn alchemist_main(args: Args) -> Result<()> {
         Commands::DumpProfile { args: local_args } => {
             dump_profile_main(&target.unwrap_or(host), local_args)?;
         }
+        Commands::VerifyConfig { args: local_args } => {
+            verify_config_main(&target.unwrap_or(host), local_args)?;
+        }
         Commands::GenerateRepo {
             output_dir,
             output_repos_json,
+            implicit_system_atom,
+            lockfile,
+            verify_lockfile,
         } => {
             generate_repo_main(
                 &host,
+                build.as_ref(),
                 target.as_ref(),
                 &translator,
                 &src_dir,
                 &output_dir,
                 &output_repos_json,
+                &implicit_system_atom,
+                &lockfile,
+                verify_lockfile,
             )?;
         }
         Commands::DigestRepo { args: local_args } => {
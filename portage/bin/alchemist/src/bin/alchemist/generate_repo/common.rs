@@ -80,7 +80,7 @@ pub static TOOLCHAIN_PACKAGE_NAMES: &[&str] = &[
     "sys-devel/crossdev",
 ];
 
-fn file_name_to_name(file_name: &str) -> String {
+pub(super) fn file_name_to_name(file_name: &str) -> String {
     let escaped_file_name: String = file_name
         .chars()
         .map(|c| {
@@ -2,8 +2,10 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+mod variant;
+
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fs::{create_dir_all, File},
     io::Write,
     os::unix::fs::symlink,
@@ -17,6 +19,7 @@ use alchemist::{
         PackageAnalysisError,
     },
     config::ProvidedPackage,
+    data::UseMap,
     dependency::restrict::RestrictAtom,
     ebuild::PackageDetails,
     fakechroot::PathTranslator,
@@ -35,6 +38,8 @@ use crate::generate_repo::common::{
     DistFileEntry, AUTOGENERATE_NOTICE, PRIMORDIAL_PACKAGES,
 };
 
+use self::variant::{collect_requested_variants, requested_variant, PackageVariant};
+
 lazy_static! {
     static ref TEMPLATES: Tera = {
         let mut tera: Tera = Default::default();
@@ -79,6 +84,9 @@ pub struct EBuildEntry {
     bashrcs: Vec<String>,
     supports_remoteexec: bool,
     xpak: Vec<(String, String)>,
+    /// A stable suffix identifying the USE-flag variant this target was generated for, or `None`
+    /// for the ebuild's ordinary, unvaried target. See `variant::PackageVariant`.
+    variant_suffix: Option<String>,
 }
 
 /// Specifies the config used to generate host packages.
@@ -141,8 +149,16 @@ pub enum PackageType<'a> {
     /// defined as a build where CBUILD != CHOST. Since we don't specify the
     /// CBUILD or CHOST in this structure we don't know if its a cross-compile.
     CrossRoot {
+        /// The build machine's packages, i.e. CBUILD. These satisfy BDEPEND /
+        /// IDEPEND dependencies when CBUILD != CHOST (a Canadian cross), such
+        /// as when the SDK that builds `target` is itself cross-compiled.
+        ///
+        /// Most configurations don't distinguish the build machine from the
+        /// host, so this is usually `None`, in which case `host` is used to
+        /// satisfy BDEPEND / IDEPEND instead.
+        build: Option<PackageHostConfig<'a>>,
         /// The host packages to use to satisfy BDEPEND / IDEPEND dependencies
-        /// for the target packages.
+        /// for the target packages, when `build` isn't set.
         host: Option<PackageHostConfig<'a>>,
         /// The target to generate packages for.
         target: PackageTargetConfig<'a>,
@@ -152,19 +168,48 @@ pub enum PackageType<'a> {
 /// Splits the provided `packages` into two lists:
 /// 1) `PackageDetails` that don't match the specified `provided` list.
 /// 2) `PackageDetails` that do match the `provided` list.
+///
+/// When `variant_source` is set (the dependent's own use map and the raw dependency expression
+/// that produced `packages`), a `(name, version)` match is only treated as "provided" if the
+/// dependent doesn't also request a non-default USE-flag variant of it (see
+/// [`requested_variant`]): an SDK's pre-built copy of `(name, version)` is always the ebuild's
+/// default resolution, so a dependent forcing `pkg[foo]` can't be satisfied by it and must fall
+/// through to its own variant target (see [`format_variant_dependencies`]) instead. Without this,
+/// a variant-specific dependency would be silently dropped as "already installed".
+///
+/// `variant_source` is `None` for dependency sets that don't have one originating expression to
+/// check against (e.g. a transitively-resolved indirect set spanning many dependents), in which
+/// case this falls back to matching on `(name, version)` alone, as before.
 fn partition_provided<'a>(
+    variant_source: Option<(&UseMap, &str)>,
     packages: impl IntoIterator<Item = &'a Arc<PackageDetails>>,
     provided: &'a [ProvidedPackage],
-) -> (Vec<&Arc<PackageDetails>>, Vec<&Arc<PackageDetails>>) {
-    let (build_host_deps, provided_host_deps): (Vec<_>, Vec<_>) =
-        packages.into_iter().partition(|package| {
-            !provided.iter().any(|provided| {
-                provided.package_name == package.as_basic_data().package_name
-                    && provided.version == package.as_basic_data().version
-            })
+) -> Result<(Vec<&'a Arc<PackageDetails>>, Vec<&'a Arc<PackageDetails>>)> {
+    let mut build_host_deps = Vec::new();
+    let mut provided_host_deps = Vec::new();
+
+    for package in packages {
+        let name_version_matches = provided.iter().any(|provided| {
+            provided.package_name == package.as_basic_data().package_name
+                && provided.version == package.as_basic_data().version
         });
 
-    (build_host_deps, provided_host_deps)
+        let is_provided = match variant_source {
+            Some((source_use_map, expression)) => {
+                name_version_matches
+                    && requested_variant(source_use_map, expression, package)?.is_default()
+            }
+            None => name_version_matches,
+        };
+
+        if is_provided {
+            provided_host_deps.push(package);
+        } else {
+            build_host_deps.push(package);
+        }
+    }
+
+    Ok((build_host_deps, provided_host_deps))
 }
 
 /// Converts the `PackageDetails` items into bazel paths using the provided
@@ -180,6 +225,31 @@ fn format_dependencies<'a>(
     Ok(targets.into_iter().sorted().dedup().collect())
 }
 
+/// Like [`format_dependencies`], but for a direct dependency edge whose [`PackageDependencyAtom`]
+/// use-deps are known (`expression` is the dependent's own raw DEPEND/RDEPEND/etc. string): each
+/// target path is suffixed to point at the USE-flag variant the dependent actually requested, if
+/// any. See `variant::collect_requested_variants`, which registers that same variant as one of
+/// the targets generated for `details`.
+fn format_variant_dependencies<'a>(
+    prefix: &str,
+    source_use_map: &UseMap,
+    expression: &str,
+    deps: impl IntoIterator<Item = &'a Arc<PackageDetails>>,
+) -> Result<Vec<String>> {
+    let targets = deps
+        .into_iter()
+        .map(|details| -> Result<String> {
+            let variant = requested_variant(source_use_map, expression, details)?;
+            let mut target = package_details_to_target_path(details, prefix);
+            if let Some(suffix) = variant.target_suffix() {
+                target = format!("{target}_{suffix}");
+            }
+            Ok(target)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(targets.into_iter().sorted().dedup().collect())
+}
+
 fn get_ebuild_name_from_path(ebuild_path: &Path) -> Result<String> {
     Ok(ebuild_path
         .file_name()
@@ -189,7 +259,11 @@ fn get_ebuild_name_from_path(ebuild_path: &Path) -> Result<String> {
 }
 
 impl EBuildEntry {
-    pub fn try_new(target: &PackageType, package: &Package) -> Result<Self> {
+    pub fn try_new(
+        target: &PackageType,
+        package: &Package,
+        variant: &PackageVariant,
+    ) -> Result<Self> {
         let ebuild_name =
             get_ebuild_name_from_path(&package.details.as_basic_data().ebuild_path).unwrap();
         let basename = ebuild_name
@@ -228,7 +302,7 @@ impl EBuildEntry {
         let extra_sources = package
             .details
             .bazel_metadata
-            .extra_sources
+            .eval_extra_sources(&package.details.use_map)?
             .iter()
             .map(|p| {
                 // Fix "//" to "@//" as generated targets are under @portage.
@@ -315,38 +389,94 @@ impl EBuildEntry {
             })
             .collect();
 
+        // A package can opt out of having the SDK's implicit system set treated as already
+        // provided, so that bootstrap-critical ebuilds are generated assuming it isn't installed
+        // yet. See `BazelSpecificMetadata::inhibits_implicit_system`.
+        let inhibits_implicit_system = package.details.bazel_metadata.inhibits_implicit_system();
+
         let (host_build_deps, provided_host_build_deps) = match &target {
             // When building host packages we need to ensure DEPEND packages
             // are present on the host.
             PackageType::Host(host) => {
-                let (host_build_deps, provided_host_build_deps) = partition_provided(
+                let sdk_provided_packages = if inhibits_implicit_system {
+                    &[]
+                } else {
+                    host.sdk_provided_packages
+                };
+
+                // `direct.build_target` has a single expression
+                // (`expressions.build_target`) we can check for a requested variant; the
+                // transitively-resolved `indirect.build_host_set` doesn't have one
+                // originating expression to check, so a package present in both is
+                // classified using the (more precise) direct check.
+                let build_target_paths: HashSet<&Path> = package
+                    .dependencies
+                    .direct
+                    .build_target
+                    .iter()
+                    .map(|details| details.as_basic_data().ebuild_path.as_path())
+                    .collect();
+
+                let (indirect_deps, indirect_provided) = partition_provided(
+                    None,
                     package
                         .dependencies
                         .indirect
                         .build_host_set
                         .iter()
-                        .chain(package.dependencies.direct.build_target.iter())
-                        .unique_by(|details| &details.as_basic_data().ebuild_path),
-                    host.sdk_provided_packages,
-                );
-
-                let mut host_build_deps =
-                    format_dependencies(host.prefix, host_build_deps.into_iter())?;
+                        .filter(|details| {
+                            !build_target_paths
+                                .contains(details.as_basic_data().ebuild_path.as_path())
+                        }),
+                    sdk_provided_packages,
+                )?;
+                let (direct_deps, direct_provided) = partition_provided(
+                    Some((
+                        &package.details.use_map,
+                        &package.dependencies.expressions.build_target,
+                    )),
+                    package.dependencies.direct.build_target.iter(),
+                    sdk_provided_packages,
+                )?;
+
+                let mut host_build_deps = format_dependencies(host.prefix, indirect_deps)?;
+                host_build_deps.extend(format_variant_dependencies(
+                    host.prefix,
+                    &package.details.use_map,
+                    &package.dependencies.expressions.build_target,
+                    direct_deps,
+                )?);
                 host_build_deps.sort();
+                host_build_deps.dedup();
+
+                let provided_host_build_deps: Vec<_> = indirect_provided
+                    .into_iter()
+                    .chain(direct_provided)
+                    .collect();
 
                 (host_build_deps, provided_host_build_deps)
             }
-            PackageType::CrossRoot { host, .. } => {
-                // Stage 1 packages don't have a host since we don't know
-                // what's contained in the stage1 SDK.
-                if let Some(host) = host {
+            PackageType::CrossRoot { build, host, .. } => {
+                // BDEPEND/IDEPEND are resolved against the build machine when
+                // it's distinct from the host (a Canadian cross), and against
+                // the host otherwise.
+                //
+                // Stage 1 packages have neither since we don't know what's
+                // contained in the stage1 SDK.
+                if let Some(build_or_host) = build.as_ref().or(host.as_ref()) {
+                    let sdk_provided_packages = if inhibits_implicit_system {
+                        &[]
+                    } else {
+                        build_or_host.sdk_provided_packages
+                    };
                     let (host_build_deps, provided_host_build_deps) = partition_provided(
+                        None,
                         package.dependencies.indirect.build_host_set.iter(),
-                        host.sdk_provided_packages,
-                    );
+                        sdk_provided_packages,
+                    )?;
 
                     let mut host_build_deps =
-                        format_dependencies(host.prefix, host_build_deps.into_iter())?;
+                        format_dependencies(build_or_host.prefix, host_build_deps.into_iter())?;
                     host_build_deps.sort();
 
                     (host_build_deps, provided_host_build_deps)
@@ -375,8 +505,10 @@ impl EBuildEntry {
             PackageType::Host { .. } => Vec::new(),
             PackageType::CrossRoot { target, .. } => {
                 // TODO: Add support for stripping the Board SDK's packages.
-                format_dependencies(
+                format_variant_dependencies(
                     target.prefix,
+                    &package.details.use_map,
+                    &package.dependencies.expressions.build_target,
                     package.dependencies.direct.build_target.iter(),
                 )?
             }
@@ -394,12 +526,26 @@ impl EBuildEntry {
 
         let (runtime_deps, provided_runtime_deps) = match &target {
             PackageType::Host(host) => {
+                let sdk_provided_packages = if inhibits_implicit_system {
+                    &[]
+                } else {
+                    host.sdk_provided_packages
+                };
                 let (runtime_deps, provided_runtime_deps) = partition_provided(
+                    Some((
+                        &package.details.use_map,
+                        &package.dependencies.expressions.run_target,
+                    )),
                     package.dependencies.direct.run_target.iter(),
-                    host.sdk_provided_packages,
-                );
+                    sdk_provided_packages,
+                )?;
 
-                let runtime_deps = format_dependencies(host.prefix, runtime_deps.into_iter())?;
+                let runtime_deps = format_variant_dependencies(
+                    host.prefix,
+                    &package.details.use_map,
+                    &package.dependencies.expressions.run_target,
+                    runtime_deps.into_iter(),
+                )?;
 
                 let provided_runtime_deps =
                     format_dependencies(host.prefix, provided_runtime_deps.into_iter())?;
@@ -407,7 +553,12 @@ impl EBuildEntry {
                 (runtime_deps, provided_runtime_deps)
             }
             PackageType::CrossRoot { target, .. } => (
-                format_dependencies(target.prefix, package.dependencies.direct.run_target.iter())?,
+                format_variant_dependencies(
+                    target.prefix,
+                    &package.details.use_map,
+                    &package.dependencies.expressions.run_target,
+                    package.dependencies.direct.run_target.iter(),
+                )?,
                 Vec::new(),
             ),
         };
@@ -428,9 +579,9 @@ impl EBuildEntry {
         let restricts = analyze_restricts(&package.details)?;
         let allow_network_access = restricts.contains(&RestrictAtom::NetworkSandbox);
 
-        let uses = package
-            .details
-            .use_map
+        // Reflects `variant`'s overrides for documentation purposes; see `PackageVariant::apply`.
+        let effective_use_map = variant.apply(&package.details.use_map);
+        let uses = effective_use_map
             .iter()
             .sorted_by(|(a_name, a_value), (b_name, b_value)| {
                 // Enabled ones comes before disabled ones.
@@ -531,6 +682,7 @@ impl EBuildEntry {
             bashrcs,
             supports_remoteexec,
             xpak,
+            variant_suffix: variant.target_suffix(),
         })
     }
 }
@@ -565,6 +717,7 @@ struct BuildTemplateContext<'a> {
 fn generate_package_build_file(
     target: &PackageType,
     packages_in_dir: &[&MaybePackage],
+    variants_by_path: &HashMap<PathBuf, Vec<PackageVariant>>,
     out: &Path,
 ) -> Result<()> {
     let (target_board, target_portage_config) = match target {
@@ -605,8 +758,22 @@ fn generate_package_build_file(
                 MaybePackage::Ok(package) => Some(package),
                 _ => None,
             })
-            .map(|package| EBuildEntry::try_new(target, package))
-            .collect::<Result<_>>()?,
+            .map(|package| {
+                // Every ebuild gets at least the default (unvaried) variant; see
+                // `variant::collect_requested_variants`.
+                let default_variants = vec![PackageVariant::default()];
+                let variants = variants_by_path
+                    .get(&package.details.as_basic_data().ebuild_path)
+                    .unwrap_or(&default_variants);
+                variants
+                    .iter()
+                    .map(|variant| EBuildEntry::try_new(target, package, variant))
+                    .collect::<Result<Vec<_>>>()
+            })
+            .collect::<Result<Vec<Vec<_>>>>()?
+            .into_iter()
+            .flatten()
+            .collect(),
         failures: packages_in_dir
             .iter()
             .flat_map(|package| match package {
@@ -631,6 +798,7 @@ fn generate_package(
     target: &PackageType,
     translator: &PathTranslator,
     packages_in_dir: &[&MaybePackage],
+    variants_by_path: &HashMap<PathBuf, Vec<PackageVariant>>,
     output_dir: &Path,
 ) -> Result<()> {
     create_dir_all(output_dir)?;
@@ -659,7 +827,12 @@ fn generate_package(
         }
     }
 
-    generate_package_build_file(target, packages_in_dir, &output_dir.join("BUILD.bazel"))?;
+    generate_package_build_file(
+        target,
+        packages_in_dir,
+        variants_by_path,
+        &output_dir.join("BUILD.bazel"),
+    )?;
 
     Ok(())
 }
@@ -693,13 +866,23 @@ pub fn generate_internal_packages(
         PackageType::CrossRoot { target, .. } => target.prefix,
     });
 
+    // Resolve, per ebuild, the distinct USE-flag variants its dependents request of it, so a
+    // package needed under two different configurations can be generated as two distinct targets.
+    let variants_by_path = collect_requested_variants(all_packages)?;
+
     // Generate packages in parallel.
     let packages_by_dir = join_by_package_dir(all_packages);
     packages_by_dir
         .into_par_iter()
         .try_for_each(|(relative_package_dir, packages_in_dir)| {
             let output_package_dir = output_packages_dir.join(relative_package_dir);
-            generate_package(target, translator, &packages_in_dir, &output_package_dir)
+            generate_package(
+                target,
+                translator,
+                &packages_in_dir,
+                &variants_by_path,
+                &output_package_dir,
+            )
         })
 }
 
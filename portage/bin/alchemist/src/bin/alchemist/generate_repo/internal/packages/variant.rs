@@ -0,0 +1,190 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Resolves the distinct USE-flag combinations ("variants") that an ebuild is requested under by
+//! its dependents, so [`super::generate_internal_packages`] can emit one Bazel target per variant
+//! instead of assuming a single resolution satisfies every dependent.
+//!
+//! This only covers the forced USE-flag overrides expressed by `[...]` use-dependencies on a
+//! dependency atom (`dep/pkg[foo,-bar]`); it does not re-evaluate the dependency's own
+//! USE-conditional metadata (SRC_URI, sub-dependencies, etc.) under the overridden flags. The
+//! `uses` list rendered for a variant target reflects the override for documentation purposes
+//! only. See `EBuildEntry::try_new`.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+use alchemist::{
+    analyze::MaybePackage,
+    data::UseMap,
+    dependency::package::{AsPackageRef, PackageDependency, PackageDependencyAtom},
+    dependency::{CompositeDependency, Dependency, Predicate},
+    ebuild::PackageDetails,
+};
+use anyhow::Result;
+
+/// A USE-flag combination requested of a dependency, relative to its own analyzed defaults.
+///
+/// The default (empty) variant represents the ebuild's ordinary, unvaried resolution.
+#[derive(Clone, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub struct PackageVariant(BTreeMap<String, bool>);
+
+impl PackageVariant {
+    pub fn is_default(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// A short, stable suffix derived from the requested flags, suitable for disambiguating
+    /// Bazel target names. Returns `None` for the default (unvaried) variant, so callers can
+    /// leave ordinary targets named exactly as they are today.
+    pub fn target_suffix(&self) -> Option<String> {
+        if self.is_default() {
+            return None;
+        }
+
+        use sha2::{Digest, Sha256};
+
+        let mut hasher = Sha256::new();
+        for (flag, value) in &self.0 {
+            hasher.update(flag.as_bytes());
+            hasher.update([*value as u8]);
+        }
+        let digest = format!("{:x}", hasher.finalize());
+        Some(format!("variant_{}", &digest[..12]))
+    }
+
+    /// Overlays the requested overrides on top of `base`, for display in the generated
+    /// `BUILD.bazel` (the `uses` list). Doesn't affect dependency resolution: see the module docs.
+    pub fn apply(&self, base: &UseMap) -> UseMap {
+        let mut merged = base.clone();
+        merged.extend(self.0.iter().map(|(flag, value)| (flag.clone(), *value)));
+        merged
+    }
+}
+
+fn collect_atoms<'a>(
+    dep: &'a PackageDependency,
+    source_use_map: &UseMap,
+    out: &mut Vec<&'a PackageDependencyAtom>,
+) {
+    match dep {
+        Dependency::Leaf(atom) => out.push(atom),
+        Dependency::Composite(composite) => match composite.as_ref() {
+            CompositeDependency::AllOf { children } | CompositeDependency::AnyOf { children } => {
+                for child in children {
+                    collect_atoms(child, source_use_map, out);
+                }
+            }
+            CompositeDependency::UseConditional {
+                name,
+                expect,
+                children,
+            } => {
+                if *source_use_map.get(name).unwrap_or(&false) != *expect {
+                    return;
+                }
+                for child in children {
+                    collect_atoms(child, source_use_map, out);
+                }
+            }
+            CompositeDependency::Constant { .. } => {}
+        },
+    }
+}
+
+/// Computes the [`PackageVariant`] that `expression` (one of a dependent's raw DEPEND-like
+/// expressions) requests of `candidate`, evaluated against the dependent's own `source_use_map`.
+///
+/// Atoms that don't resolve to `candidate` (wrong package, or an unsatisfied USE-conditional) are
+/// ignored. Multiple atoms requesting the same flag are merged, with later atoms in the
+/// expression winning, mirroring how `BazelSpecificMetadata` merges take the last writer.
+pub(crate) fn requested_variant(
+    source_use_map: &UseMap,
+    expression: &str,
+    candidate: &PackageDetails,
+) -> Result<PackageVariant> {
+    if expression.is_empty() {
+        return Ok(PackageVariant::default());
+    }
+
+    let deps = expression.parse::<PackageDependency>()?;
+    let mut atoms = Vec::new();
+    collect_atoms(&deps, source_use_map, &mut atoms);
+
+    let mut overrides = BTreeMap::new();
+    for atom in atoms {
+        if atom.uses().is_empty() || atom.package_name() != candidate.as_basic_data().package_name
+        {
+            continue;
+        }
+        if !atom.matches(source_use_map, &candidate.as_package_ref())? {
+            continue;
+        }
+        for use_dep in atom.uses() {
+            if let Some(value) = use_dep.requested_value(source_use_map)? {
+                overrides.insert(use_dep.flag().to_owned(), value);
+            }
+        }
+    }
+
+    Ok(PackageVariant(overrides))
+}
+
+/// Walks every package's DEPEND/RDEPEND/BDEPEND/IDEPEND expressions and records, for each
+/// dependency ebuild it resolves to, the distinct [`PackageVariant`]s its dependents request of
+/// it. The default (unvaried) variant is always included, so an ebuild nobody requests a specific
+/// USE combination of still gets exactly the one target it does today.
+pub fn collect_requested_variants(
+    all_packages: &[MaybePackage],
+) -> Result<HashMap<PathBuf, Vec<PackageVariant>>> {
+    let mut variants: HashMap<PathBuf, Vec<PackageVariant>> = HashMap::new();
+
+    for package in all_packages {
+        variants
+            .entry(package.as_basic_data().ebuild_path.clone())
+            .or_default();
+    }
+
+    for package in all_packages {
+        let MaybePackage::Ok(package) = package else {
+            continue;
+        };
+
+        let expressions = &package.dependencies.expressions;
+        let resolved_deps = [
+            (&expressions.build_target, &package.dependencies.direct.build_target),
+            (&expressions.run_target, &package.dependencies.direct.run_target),
+            (&expressions.build_host, &package.dependencies.direct.build_host),
+            (&expressions.install_host, &package.dependencies.direct.install_host),
+        ];
+
+        for (expression, candidates) in resolved_deps {
+            for candidate in candidates {
+                let variant =
+                    requested_variant(&package.details.use_map, expression, candidate)?;
+                if variant.is_default() {
+                    continue;
+                }
+                let entry = variants
+                    .entry(candidate.as_basic_data().ebuild_path.clone())
+                    .or_default();
+                if !entry.contains(&variant) {
+                    entry.push(variant);
+                }
+            }
+        }
+    }
+
+    for variant_list in variants.values_mut() {
+        variant_list.sort();
+        variant_list.dedup();
+        if !variant_list.iter().any(PackageVariant::is_default) {
+            variant_list.insert(0, PackageVariant::default());
+        }
+    }
+
+    Ok(variants)
+}
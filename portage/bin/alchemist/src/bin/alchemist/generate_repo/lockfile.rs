@@ -0,0 +1,202 @@
+// Copyright 2026 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Produces `lockfile.json`: a sorted, committed record of every distfile's Bazel repository
+//! name, sha256, and size, so a reproducibility regression in `@portage` (a distfile silently
+//! re-pinned to a different revision, or its recorded hash drifting) surfaces as an ordinary
+//! source diff instead of a silent rebuild change. [`verify_lockfile`] is the presubmit-facing
+//! check: it re-derives the current set and diffs it against a previously committed one.
+//!
+//! Only distfiles are covered here, not generated package targets: unlike a distfile (whose
+//! content hash is known statically from its Manifest, the same source `deps::generate_deps_file`
+//! uses for its `integrity` field), a package's build output is a Bazel action result that
+//! alchemist never computes itself, so there's nothing of alchemist's to pin for it.
+
+use std::{collections::BTreeMap, fs::File, io::BufReader, path::Path};
+
+use alchemist::analyze::source::PackageSources;
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use super::common::file_name_to_name;
+
+/// A single locked entry: the Bazel repository name a distfile is fetched as (matching the `name`
+/// field `deps::generate_deps_file` assigns it), its recorded size, and its SHA256 hash.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LockfileEntry {
+    pub name: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+/// Builds the sorted, de-duplicated set of [`LockfileEntry`] for every distfile reachable from
+/// `all_sources`, keyed the same way `deps::generate_deps` dedups repositories (by filename).
+#[instrument(skip_all)]
+fn generate_lockfile(all_sources: &[&PackageSources]) -> Result<Vec<LockfileEntry>> {
+    let entries = all_sources
+        .iter()
+        .flat_map(|sources| sources.dist_sources.iter())
+        .sorted_by(|a, b| a.filename.cmp(&b.filename))
+        .dedup_by(|a, b| a.filename == b.filename)
+        .map(|dist| {
+            let sha256 = dist
+                .hashes
+                .get("SHA256")
+                .with_context(|| format!("Distfile {} is missing a SHA256 hash", dist.filename))?;
+            Ok(LockfileEntry {
+                name: file_name_to_name(&dist.filename),
+                sha256: sha256.clone(),
+                size: dist.size,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(entries)
+}
+
+/// Writes [`generate_lockfile`]'s result as `lockfile.json`.
+pub fn generate_lockfile_file(all_sources: &[&PackageSources], out: &Path) -> Result<()> {
+    let entries = generate_lockfile(all_sources)?;
+    let mut file = File::create(out)?;
+    serde_json::to_writer_pretty(&mut file, &entries)?;
+    Ok(())
+}
+
+/// Re-derives the current lockfile from `all_sources` and diffs it against the one previously
+/// committed at `existing_lockfile`, failing with a human-readable report of every added, removed,
+/// or drifted (sha256 or size changed) entry. Called instead of [`generate_lockfile_file`] when
+/// `--verify` is passed to the `generate-repo` subcommand.
+pub fn verify_lockfile(all_sources: &[&PackageSources], existing_lockfile: &Path) -> Result<()> {
+    let current: BTreeMap<String, LockfileEntry> = generate_lockfile(all_sources)?
+        .into_iter()
+        .map(|entry| (entry.name.clone(), entry))
+        .collect();
+
+    let file = File::open(existing_lockfile).with_context(|| {
+        format!(
+            "Failed to open committed lockfile {}",
+            existing_lockfile.display()
+        )
+    })?;
+    let committed_entries: Vec<LockfileEntry> = serde_json::from_reader(BufReader::new(file))?;
+    let committed: BTreeMap<String, LockfileEntry> = committed_entries
+        .into_iter()
+        .map(|entry| (entry.name.clone(), entry))
+        .collect();
+
+    let mut problems = Vec::new();
+
+    for (name, current_entry) in &current {
+        match committed.get(name) {
+            None => problems.push(format!("{name}: added (not in committed lockfile)")),
+            Some(committed_entry) if committed_entry != current_entry => {
+                if committed_entry.sha256 != current_entry.sha256 {
+                    problems.push(format!(
+                        "{name}: sha256 drifted ({} -> {})",
+                        committed_entry.sha256, current_entry.sha256
+                    ));
+                }
+                if committed_entry.size != current_entry.size {
+                    problems.push(format!(
+                        "{name}: size drifted ({} -> {})",
+                        committed_entry.size, current_entry.size
+                    ));
+                }
+            }
+            Some(_) => {}
+        }
+    }
+    for name in committed.keys() {
+        if !current.contains_key(name) {
+            problems.push(format!("{name}: removed (no longer generated)"));
+        }
+    }
+
+    if !problems.is_empty() {
+        problems.sort();
+        bail!(
+            "Lockfile verification failed against {}:\n{}",
+            existing_lockfile.display(),
+            problems.join("\n")
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use alchemist::analyze::source::PackageDistSource;
+    use url::Url;
+
+    use super::*;
+
+    fn dist_source(filename: &str, size: u64, sha256: &str) -> PackageDistSource {
+        PackageDistSource {
+            urls: vec![Url::parse("https://example.com/dist").unwrap()],
+            filename: filename.to_owned(),
+            size,
+            hashes: HashMap::from([("SHA256".to_string(), sha256.to_string())]),
+        }
+    }
+
+    #[test]
+    fn generate_lockfile_dedups_and_sorts() -> Result<()> {
+        let sources_a = PackageSources {
+            local_sources: vec![],
+            repo_sources: vec![],
+            dist_sources: vec![dist_source("b-1.0.tar.gz", 100, "aaaa")],
+        };
+        let sources_b = PackageSources {
+            local_sources: vec![],
+            repo_sources: vec![],
+            dist_sources: vec![
+                dist_source("a-1.0.tar.gz", 200, "bbbb"),
+                dist_source("b-1.0.tar.gz", 100, "aaaa"),
+            ],
+        };
+
+        let entries = generate_lockfile(&[&sources_a, &sources_b])?;
+        assert_eq!(
+            entries,
+            vec![
+                LockfileEntry {
+                    name: file_name_to_name("a-1.0.tar.gz"),
+                    sha256: "bbbb".to_string(),
+                    size: 200,
+                },
+                LockfileEntry {
+                    name: file_name_to_name("b-1.0.tar.gz"),
+                    sha256: "aaaa".to_string(),
+                    size: 100,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn verify_lockfile_detects_drift() -> Result<()> {
+        let committed = vec![LockfileEntry {
+            name: file_name_to_name("a-1.0.tar.gz"),
+            sha256: "bbbb".to_string(),
+            size: 200,
+        }];
+        let tmp = tempfile::NamedTempFile::new()?;
+        serde_json::to_writer(File::create(tmp.path())?, &committed)?;
+
+        let sources = PackageSources {
+            local_sources: vec![],
+            repo_sources: vec![],
+            dist_sources: vec![dist_source("a-1.0.tar.gz", 200, "cccc")],
+        };
+
+        let err = verify_lockfile(&[&sources], tmp.path()).unwrap_err();
+        assert!(err.to_string().contains("sha256 drifted (bbbb -> cccc)"));
+        Ok(())
+    }
+}
@@ -5,7 +5,9 @@
 mod common;
 mod deps;
 pub mod internal;
+mod lockfile;
 mod public;
+mod source_map;
 
 use std::{
     collections::HashMap,
@@ -48,7 +50,9 @@ use self::{
         },
         sources::generate_internal_sources,
     },
+    lockfile::{generate_lockfile_file, verify_lockfile},
     public::{generate_public_images, generate_public_packages},
+    source_map::{generate_source_map_file, PackageGroup},
 };
 
 fn load_packages(
@@ -88,9 +92,11 @@ fn load_packages(
     Ok(packages)
 }
 
-fn get_sdk_implicit_system_package(host_packages: &[MaybePackage]) -> Result<Arc<Package>> {
-    // TODO: Add a parameter to pass this along
-    let sdk_atom = PackageAtom::from_str("virtual/target-sdk-implicit-system")?;
+fn get_sdk_implicit_system_package(
+    host_packages: &[MaybePackage],
+    implicit_system_atom: &str,
+) -> Result<Arc<Package>> {
+    let sdk_atom = PackageAtom::from_str(implicit_system_atom)?;
 
     let best_package = select_best_version(
         host_packages
@@ -111,6 +117,15 @@ fn get_sdk_implicit_system_package(host_packages: &[MaybePackage]) -> Result<Arc
     }
 }
 
+// TODO: `packages_by_path`/`collect_transitive_dependencies` are keyed by bare ebuild path, so
+// `ProvidedPackage` below only ever carries `(name, version)`. `EBuildEntry::try_new`'s
+// `partition_provided` already refuses to treat a `(name, version)` match as "provided" when the
+// dependent itself requests a non-default USE-flag variant (see `internal::packages::variant`),
+// which covers the direct-dependency edges that actually land in generated `BUILD.bazel` files.
+// Doing the same for the *transitive* sets computed here would additionally require threading
+// `PackageVariant` through `analyze::dependency::indirect::collect_transitive_dependencies`
+// itself, since the implicit-system/bootstrap package sets are computed before any variant is
+// known. That's a larger change to the dependency analysis than this file can safely make alone.
 fn compute_provided_packages(
     packages_by_path: &HashMap<&Path, Result<&Package, &PackageAnalysisError>>,
     root: &Package,
@@ -188,15 +203,21 @@ fn compute_bootstrap_packages<'a>(
 }
 
 /// Generates the stage1, stage2, etc packages and SDKs.
+///
+/// `build` is the build machine's `TargetData`, when it's a Canadian cross (CBUILD != CHOST):
+/// the SDK that builds `host`/`target`'s packages is itself cross-compiled, so its BDEPEND /
+/// IDEPEND dependencies need their own package set rather than falling back to `host`'s (see
+/// `PackageType::CrossRoot::build`). This is `None` in the overwhelmingly common case where the
+/// build machine isn't distinguished from the host.
 pub fn generate_stages(
     host: &TargetData,
+    build: Option<&TargetData>,
     target: Option<&TargetData>,
     translator: &PathTranslator,
     src_dir: &Path,
     output_dir: &Path,
-) -> Result<Vec<MaybePackage>> {
-    let mut all_packages = vec![];
-
+    implicit_system_atom: &str,
+) -> Result<(Vec<MaybePackage>, Vec<MaybePackage>)> {
     let host_packages = load_packages(host, host, src_dir)?;
 
     let packages_by_path = host_packages
@@ -216,7 +237,8 @@ pub fn generate_stages(
     // analysis phase because bazel doesn't like it when there are cycles in the
     // dependency graph. This means we need to filter out the dependencies
     // when we generate the BUILD files.
-    let implicit_system_package = get_sdk_implicit_system_package(&host_packages)?;
+    let implicit_system_package =
+        get_sdk_implicit_system_package(&host_packages, implicit_system_atom)?;
     let implicit_system_packages =
         compute_provided_packages(&packages_by_path, &implicit_system_package)?;
 
@@ -239,6 +261,7 @@ pub fn generate_stages(
         // guarantees that we can correctly track all the dependencies so
         // we can ensure proper package rebuilds.
         &PackageType::CrossRoot {
+            build: None,
             host: None,
             target: PackageTargetConfig {
                 board: &host.board,
@@ -301,6 +324,78 @@ pub fn generate_stages(
         output_dir,
     )?;
 
+    // When a build machine distinct from `host` is configured (a Canadian cross), generate an
+    // equivalent Stage 1/2 SDK for it, so the cross-root packages below can satisfy their
+    // BDEPEND/IDEPEND against the build machine's own packages instead of `host`'s.
+    let build_implicit_system_packages;
+    let build_host = match build {
+        Some(build) => {
+            let build_packages = load_packages(build, build, src_dir)?;
+            let build_packages_by_path = build_packages
+                .iter()
+                .map(|package| {
+                    (
+                        package.as_basic_data().ebuild_path.as_path(),
+                        package.into(),
+                    )
+                })
+                .collect();
+            let build_implicit_system_package =
+                get_sdk_implicit_system_package(&build_packages, implicit_system_atom)?;
+            build_implicit_system_packages =
+                compute_provided_packages(&build_packages_by_path, &build_implicit_system_package)?;
+
+            generate_stage1_sdk("stage1/build", build, output_dir)?;
+            generate_internal_packages(
+                &PackageType::CrossRoot {
+                    build: None,
+                    host: None,
+                    target: PackageTargetConfig {
+                        board: &build.board,
+                        prefix: "stage1/build",
+                        repo_set: &build.repos,
+                    },
+                },
+                translator,
+                &build_packages,
+                output_dir,
+            )?;
+            generate_base_sdk(
+                &SdkBaseConfig {
+                    name: "stage2:build",
+                    source_package_prefix: "stage1/build",
+                    source_sdk: "stage1/build:base",
+                    source_repo_set: &build.repos,
+                    packages: vec![&build_implicit_system_package],
+                    package_suffix: None,
+                },
+                output_dir,
+            )?;
+            generate_host_sdk(
+                &SdkHostConfig {
+                    base: "stage2:build",
+                    name: "stage2/build",
+                },
+                output_dir,
+            )?;
+
+            let build_host = PackageHostConfig {
+                repo_set: &build.repos,
+                prefix: "stage2/build",
+                sdk_provided_packages: &build_implicit_system_packages,
+            };
+            generate_internal_packages(
+                &PackageType::Host(build_host),
+                translator,
+                &build_packages,
+                output_dir,
+            )?;
+
+            Some(build_host)
+        }
+        None => None,
+    };
+
     // Generate the Stage 3 Bootstrap SDK
     //
     // The stage 3 Bootstrap SDK is composed of packages built using the Stage
@@ -347,6 +442,7 @@ pub fn generate_stages(
     // correctly build the implicit system set.
     generate_internal_packages(
         &PackageType::CrossRoot {
+            build: None,
             host: None,
             target: PackageTargetConfig {
                 board: &host.board,
@@ -400,9 +496,7 @@ pub fn generate_stages(
         &output_dir.join("host"),
     )?;
 
-    all_packages.extend(host_packages);
-
-    if let Some(target) = target {
+    let target_packages = if let Some(target) = target {
         let target_packages = load_packages(host, target, src_dir)?;
 
         generate_stage1_sdk("stage1/target/board", target, output_dir)?;
@@ -412,6 +506,7 @@ pub fn generate_stages(
             // We don't know what packages are installed in the Stage 1 SDK,
             // so we can't support BDEPENDs.
             &PackageType::CrossRoot {
+                build: None,
                 host: None,
                 target: PackageTargetConfig {
                     board: &target.board,
@@ -449,8 +544,10 @@ pub fn generate_stages(
         // cross-compiled using the Stage 2 SDK.
         generate_internal_packages(
             &PackageType::CrossRoot {
-                // We want to use the stage2/host packages to satisfy
-                // our BDEPEND/IDEPEND dependencies.
+                // When a distinct build machine is configured (a Canadian cross), it satisfies
+                // our BDEPEND/IDEPEND dependencies; otherwise we fall back to the stage2/host
+                // packages, which is the common case where CBUILD isn't distinguished from CHOST.
+                build: build_host,
                 host: Some(stage2_host),
                 target: PackageTargetConfig {
                     board: &target.board,
@@ -484,20 +581,29 @@ pub fn generate_stages(
         // TODO: Generate the Stage 3 target packages if we decide to build
         // targets against the stage 3 SDK.
 
-        all_packages.extend(target_packages);
-    }
+        target_packages
+    } else {
+        vec![]
+    };
 
-    Ok(all_packages)
+    Ok((host_packages, target_packages))
 }
 
 /// The entry point of "generate-repo" subcommand.
+///
+/// `lockfile` is read and diffed against rather than (re)written when `verify` is set; see
+/// [`lockfile::verify_lockfile`].
 pub fn generate_repo_main(
     host: &TargetData,
+    build: Option<&TargetData>,
     target: Option<&TargetData>,
     translator: &PathTranslator,
     src_dir: &Path,
     output_dir: &Path,
     deps_file: &Path,
+    implicit_system_atom: &str,
+    lockfile: &Path,
+    verify: bool,
 ) -> Result<()> {
     match remove_dir_all(output_dir) {
         Ok(_) => {}
@@ -519,7 +625,7 @@ pub fn generate_repo_main(
 
     generate_internal_overlays(
         translator,
-        [Some(host), target]
+        [Some(host), build, target]
             .iter()
             .filter_map(|x| x.map(|data| data.repos.as_ref()))
             .collect_vec()
@@ -527,19 +633,56 @@ pub fn generate_repo_main(
         output_dir,
     )?;
 
+    // TODO: `generate_internal_bashrcs` only knows about `host`/`target`; a configured `build`
+    // machine doesn't get its own bashrc overlay yet.
     generate_internal_bashrcs(translator, host, target, output_dir)?;
 
-    let all_packages = generate_stages(host, target, translator, src_dir, output_dir)?;
+    let (host_packages, target_packages) = generate_stages(
+        host,
+        build,
+        target,
+        translator,
+        src_dir,
+        output_dir,
+        implicit_system_atom,
+    )?;
+    let all_packages = host_packages
+        .iter()
+        .chain(target_packages.iter())
+        .collect_vec();
 
-    generate_deps_file(
-        &all_packages
-            .iter()
-            .flat_map(|package| match package {
-                MaybePackage::Ok(package) => Some(&package.sources),
-                _ => None,
-            })
-            .collect_vec(),
-        deps_file,
+    let all_sources = all_packages
+        .iter()
+        .flat_map(|package| match package {
+            MaybePackage::Ok(package) => Some(&package.sources),
+            _ => None,
+        })
+        .collect_vec();
+
+    generate_deps_file(&all_sources, deps_file)?;
+
+    if verify {
+        verify_lockfile(&all_sources, lockfile)?;
+    } else {
+        generate_lockfile_file(&all_sources, lockfile)?;
+    }
+
+    // The host and target packages built here end up under the `stage2/host` and
+    // `stage2/target/board` labels respectively: see the `generate_internal_packages` calls for
+    // the "packages that will be built using the Stage 2 SDK" in `generate_stages`.
+    generate_source_map_file(
+        &[
+            PackageGroup {
+                prefix: "stage2/host",
+                packages: &host_packages,
+            },
+            PackageGroup {
+                prefix: "stage2/target/board",
+                packages: &target_packages,
+            },
+        ],
+        src_dir,
+        &output_dir.join("source_map.json"),
     )?;
 
     generate_portage_config(host, target, output_dir)?;
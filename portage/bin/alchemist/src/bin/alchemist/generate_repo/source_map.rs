@@ -0,0 +1,156 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    path::Path,
+};
+
+use alchemist::analyze::{
+    dependency::direct::DependencyKind, dependency::indirect::collect_transitive_dependencies,
+    source::PackageLocalSource, MaybePackage, Package, PackageAnalysisError,
+};
+use anyhow::Result;
+use tracing::instrument;
+
+use super::common::package_details_to_target_path;
+
+/// A set of packages generated under a single `//internal/packages/<prefix>/...` label
+/// namespace, mirroring [`super::public::TargetConfig`].
+pub struct PackageGroup<'a> {
+    /// Package prefix to use when constructing each package's full target path, e.g.
+    /// `stage2/host`.
+    pub prefix: &'a str,
+    pub packages: &'a [MaybePackage],
+}
+
+/// Returns every source path `package` reads directly: its ebuild, the eclasses it inherits from,
+/// the profile/package bashrcs it executes, and the `CROS_WORKON`-derived source directories it
+/// checks out. Patches and other extra build inputs discovered via
+/// [`alchemist::ebuild::BazelSpecificMetadata::eval_extra_sources`] are already addressed as
+/// Bazel labels rather than filesystem paths, so they're folded in unchanged: a presubmit can
+/// match a changed file against either representation.
+fn direct_sources(package: &Package, src_dir: &Path) -> Result<Vec<String>> {
+    let details = &package.details;
+
+    let mut sources = vec![details
+        .as_basic_data()
+        .ebuild_path
+        .to_string_lossy()
+        .into_owned()];
+
+    sources.extend(
+        details
+            .inherit_paths
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned()),
+    );
+    sources.extend(
+        package
+            .bashrcs
+            .iter()
+            .map(|path| path.to_string_lossy().into_owned()),
+    );
+
+    for local_source in &package.sources.local_sources {
+        match local_source {
+            PackageLocalSource::Src(relative_path) | PackageLocalSource::SrcFile(relative_path) => {
+                sources.push(src_dir.join(relative_path).to_string_lossy().into_owned());
+            }
+            // BazelTarget/Chrome/Chromite/DepotTools aren't paths under `src_dir`: they're
+            // either a Bazel label already, or fetched out-of-tree.
+            PackageLocalSource::BazelTarget(_)
+            | PackageLocalSource::Chrome { .. }
+            | PackageLocalSource::Chromite
+            | PackageLocalSource::DepotTools => {}
+        }
+    }
+
+    sources.extend(details.bazel_metadata.eval_extra_sources(&details.use_map)?);
+
+    Ok(sources)
+}
+
+/// Inverts source dependencies across every package in `groups`: for each package, every source
+/// of every package in its transitive `BuildTarget`/`RunTarget` dependency closure (the same
+/// traversal `compute_bootstrap_packages` uses, via [`collect_transitive_dependencies`]) is
+/// recorded as mapping to that package's own label.
+///
+/// This deliberately over-includes: a package is marked as affected by a source change in
+/// anything it needs to build or run, even when the specific change wouldn't actually alter that
+/// dependency's output. That makes the map safe for a presubmit to intersect against a changed
+/// file list and run only the (possibly still too many, but never too few) impacted targets.
+#[instrument(skip_all)]
+fn generate_source_map(
+    groups: &[PackageGroup],
+    src_dir: &Path,
+) -> Result<BTreeMap<String, Vec<String>>> {
+    let packages_by_path: HashMap<&Path, Result<&Package, &PackageAnalysisError>> = groups
+        .iter()
+        .flat_map(|group| group.packages.iter())
+        .map(|package| (package.as_basic_data().ebuild_path.as_path(), package.into()))
+        .collect();
+
+    let mut source_map: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for group in groups {
+        for package in group.packages {
+            let MaybePackage::Ok(package) = package else {
+                continue;
+            };
+
+            let label = package_details_to_target_path(&package.details, group.prefix);
+
+            let closure = collect_transitive_dependencies::<Package, _, _, _, _>(
+                [&package.details],
+                &packages_by_path,
+                &[DependencyKind::BuildTarget, DependencyKind::RunTarget],
+            )?;
+
+            for dependency_details in &closure {
+                let Some(Ok(dependency_package)) = packages_by_path
+                    .get(dependency_details.as_basic_data().ebuild_path.as_path())
+                    .copied()
+                else {
+                    continue;
+                };
+
+                for source in direct_sources(dependency_package, src_dir)? {
+                    source_map.entry(source).or_default().push(label.clone());
+                }
+            }
+        }
+    }
+
+    for labels in source_map.values_mut() {
+        labels.sort();
+        labels.dedup();
+    }
+
+    Ok(source_map)
+}
+
+/// Writes [`generate_source_map`]'s result as `source_map.json`: a map from every source path
+/// that could affect a package in `groups`, to the sorted, de-duplicated list of Bazel labels
+/// whose output could change as a result. A presubmit can intersect this against a git diff's
+/// changed files to run only the targets they could have affected.
+pub fn generate_source_map_file(groups: &[PackageGroup], src_dir: &Path, out: &Path) -> Result<()> {
+    let source_map = generate_source_map(groups, src_dir)?;
+    let mut file = File::create(out)?;
+    serde_json::to_writer(&mut file, &source_map)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_source_map_handles_empty_groups() -> Result<()> {
+        let source_map = generate_source_map(&[], Path::new("/src"))?;
+        assert!(source_map.is_empty());
+        Ok(())
+    }
+}
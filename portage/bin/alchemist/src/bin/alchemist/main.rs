@@ -9,6 +9,7 @@ mod dump_profile;
 mod generate_repo;
 mod ver_rs;
 mod ver_test;
+mod verify_config;
 
 use std::process::ExitCode;
 
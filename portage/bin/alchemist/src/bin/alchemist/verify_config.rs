@@ -0,0 +1,226 @@
+// Copyright 2026 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Differential verification of `ConfigBundle`'s reimplementation of PMS incremental-variable,
+//! USE, keyword, and mask logic against the real Portage tools, modeled on how Cargo's resolver is
+//! checked against an independent SAT-based oracle: rather than trusting this from-scratch
+//! reimplementation of PMS semantics on its own, shell out to `portageq`/`equery` for the same
+//! sysroot and compare their answers field-by-field against what `ConfigBundle` computed while
+//! loading packages, reporting every field that disagrees.
+//!
+//! This is a developer-facing cross-check, not something run as part of normal `alchemist`
+//! invocations: it requires a real Portage install (`portageq`/`equery` on `$PATH`) pointed at the
+//! same sysroot alchemist itself loaded, which is only true when running inside the CrOS SDK
+//! chroot.
+
+use std::process::Command;
+
+use alchemist::dependency::package::PackageAtom;
+use alchemist::ebuild::PackageDetails;
+use anyhow::{bail, Context, Result};
+use itertools::Itertools;
+
+use crate::alchemist::TargetData;
+
+#[derive(clap::Args, Clone, Debug)]
+pub struct Args {
+    /// Package atoms to verify, e.g. "sys-apps/hello". Defaults to every package visible in the
+    /// target's repositories, which can be slow since each one shells out to `portageq`/`equery`.
+    packages: Vec<String>,
+}
+
+/// A single field-level disagreement between `ConfigBundle` and Portage's own tools for one
+/// package.
+#[derive(Debug)]
+struct Mismatch {
+    package: String,
+    field: &'static str,
+    alchemist: String,
+    portage: String,
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} mismatch: alchemist={:?} portage={:?}",
+            self.package, self.field, self.alchemist, self.portage
+        )
+    }
+}
+
+pub fn verify_config_main(target: &TargetData, args: Args) -> Result<()> {
+    let atoms = if args.packages.is_empty() {
+        target
+            .resolver
+            .find_all_packages()?
+            .into_iter()
+            .map(|package| {
+                let basic_data = package.as_basic_data();
+                format!("={}-{}", &basic_data.package_name, &basic_data.version).parse()
+            })
+            .collect::<Result<Vec<PackageAtom>>>()?
+    } else {
+        args.packages
+            .iter()
+            .map(|raw| raw.parse::<PackageAtom>())
+            .collect::<Result<Vec<_>>>()?
+    };
+
+    let mut mismatches = Vec::new();
+    let mut checked = 0;
+    for atom in &atoms {
+        let Some(package) = target.resolver.find_best_package(atom)? else {
+            continue;
+        };
+        mismatches.extend(verify_package(&target.sysroot, &package)?);
+        checked += 1;
+    }
+
+    if mismatches.is_empty() {
+        println!("OK: {checked} package(s) agree with Portage.");
+        return Ok(());
+    }
+
+    for mismatch in &mismatches {
+        println!("{mismatch}");
+    }
+    bail!(
+        "{} mismatch(es) found across {} package(s)",
+        mismatches.len(),
+        checked
+    );
+}
+
+fn verify_package(sysroot: &std::path::Path, package: &PackageDetails) -> Result<Vec<Mismatch>> {
+    let basic_data = package.as_basic_data();
+    let cpv = format!("{}-{}", &basic_data.package_name, &basic_data.version);
+
+    let mut mismatches = Vec::new();
+
+    if let Some(mismatch) = verify_use_flags(sysroot, &cpv, package)? {
+        mismatches.push(mismatch);
+    }
+    if let Some(mismatch) = verify_masked(sysroot, &cpv, package)? {
+        mismatches.push(mismatch);
+    }
+
+    Ok(mismatches)
+}
+
+/// Compares `ConfigBundle`-derived `package.use_map` against `equery uses`' report of each flag's
+/// final (profile + package.use + mask/force resolved) state for the same package.
+fn verify_use_flags(
+    sysroot: &std::path::Path,
+    cpv: &str,
+    package: &PackageDetails,
+) -> Result<Option<Mismatch>> {
+    let output = run_portage_tool(
+        "equery",
+        ["--root", &sysroot.to_string_lossy(), "--quiet", "uses", cpv],
+    )?;
+    let Some(output) = output else {
+        // equery isn't on $PATH (e.g. running outside the SDK chroot): nothing to compare
+        // against.
+        return Ok(None);
+    };
+
+    let portage_use = parse_equery_uses(&output);
+    let alchemist_use = package
+        .use_map
+        .iter()
+        .filter(|(name, _)| portage_use.contains_key(name.as_str()))
+        .map(|(name, value)| format!("{}{name}", if *value { "+" } else { "-" }))
+        .sorted()
+        .collect_vec();
+    let portage_use = portage_use
+        .into_iter()
+        .map(|(name, value)| format!("{}{name}", if value { "+" } else { "-" }))
+        .sorted()
+        .collect_vec();
+
+    if alchemist_use == portage_use {
+        return Ok(None);
+    }
+
+    Ok(Some(Mismatch {
+        package: cpv.to_owned(),
+        field: "USE",
+        alchemist: alchemist_use.join(" "),
+        portage: portage_use.join(" "),
+    }))
+}
+
+/// Parses `equery uses`' "+flag : description" / "-flag : description" output into a map of flag
+/// name to its final resolved value.
+fn parse_equery_uses(output: &str) -> std::collections::HashMap<String, bool> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let value = if let Some(rest) = line.strip_prefix('+') {
+                (rest, true)
+            } else if let Some(rest) = line.strip_prefix('-') {
+                (rest, false)
+            } else {
+                return None;
+            };
+            let (rest, value) = value;
+            let name = rest.split(':').next()?.trim();
+            Some((name.to_owned(), value))
+        })
+        .collect()
+}
+
+/// Compares `ConfigBundle::is_package_masked` against whether `portageq match` can resolve the
+/// package at all.
+fn verify_masked(
+    sysroot: &std::path::Path,
+    cpv: &str,
+    package: &PackageDetails,
+) -> Result<Option<Mismatch>> {
+    let atom = format!("={cpv}");
+    let output = run_portage_tool("portageq", ["match", &sysroot.to_string_lossy(), &atom])?;
+    let Some(output) = output else {
+        return Ok(None);
+    };
+
+    let portage_masked = output.trim().is_empty();
+    let alchemist_masked = package.readiness.masked();
+
+    if alchemist_masked == portage_masked {
+        return Ok(None);
+    }
+
+    Ok(Some(Mismatch {
+        package: cpv.to_owned(),
+        field: "masked",
+        alchemist: alchemist_masked.to_string(),
+        portage: portage_masked.to_string(),
+    }))
+}
+
+/// Runs a Portage tool, returning `Ok(None)` (rather than failing) if it isn't installed, since
+/// that just means verification can't run outside the CrOS SDK chroot.
+fn run_portage_tool<'a>(
+    program: &str,
+    args: impl IntoIterator<Item = &'a str>,
+) -> Result<Option<String>> {
+    let output = match Command::new(program).args(args).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to spawn {program}")),
+    };
+
+    if !output.status.success() {
+        bail!(
+            "{} failed: {}\nstderr: {}",
+            program,
+            &output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+}
@@ -15,8 +15,9 @@ use version::Version;
 
 use crate::{
     bash::vars::BashVars,
-    data::{IUseMap, Slot, UseMap, Vars},
+    data::{IUseMap, Interner, Slot, Symbol, UseMap, Vars},
     dependency::package::PackageRef,
+    repository::Sha256Digest,
 };
 
 use super::{
@@ -101,6 +102,194 @@ fn merge_incremental_tokens<'s, I: IntoIterator<Item = &'s str>>(
     values.into_iter().sorted()
 }
 
+/// Like [`merge_incremental_tokens`], but interns each token through `interner` and merges
+/// [`Symbol`]s instead of `&str`s, so the resulting set can be membership-tested by hashing a
+/// `u32` instead of re-hashing the same USE flag strings for every package/version pair.
+fn merge_incremental_symbols<'s, I: IntoIterator<Item = &'s str>>(
+    interner: &Interner,
+    iter: I,
+) -> HashSet<Symbol> {
+    let clear_all = interner.intern("*");
+    let mut values = HashSet::<Symbol>::new();
+    for token in iter {
+        if let Some(token) = token.strip_prefix('-') {
+            let symbol = interner.intern(token);
+            if symbol == clear_all {
+                values.clear();
+            } else {
+                values.remove(&symbol);
+            }
+        } else {
+            values.insert(interner.intern(token));
+        }
+    }
+    values
+}
+
+/// The config node(s) and raw token responsible for a single merged incremental-variable value,
+/// as tracked by [`merge_incremental_tokens_with_provenance`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Provenance {
+    /// Whether the token set (`true`) or cleared (`false`) the value. `-*` counts as clearing.
+    pub value: bool,
+    /// The config file(s) that contributed the token. See [`ConfigNode::sources`].
+    pub sources: Vec<PathBuf>,
+    /// The token as written in the source, e.g. `foo`, `-foo`, `-*`.
+    pub token: String,
+}
+
+/// Like [`merge_incremental_tokens`], but instead of collapsing the result down to the merged
+/// set, tracks the [`Provenance`] of the token that last set or cleared each value seen. This
+/// lets callers explain *why* a token ended up (not) in the merged result instead of just
+/// learning the final outcome.
+fn merge_incremental_tokens_with_provenance<'s, I: IntoIterator<Item = (&'s [PathBuf], &'s str)>>(
+    iter: I,
+) -> HashMap<&'s str, Provenance> {
+    let mut values: HashMap<&str, Provenance> = HashMap::new();
+    for (sources, token) in iter {
+        if let Some(bare) = token.strip_prefix('-') {
+            if bare == "*" {
+                for provenance in values.values_mut() {
+                    provenance.value = false;
+                    provenance.sources = sources.to_vec();
+                    provenance.token = token.to_owned();
+                }
+            } else {
+                values.insert(
+                    bare,
+                    Provenance {
+                        value: false,
+                        sources: sources.to_vec(),
+                        token: token.to_owned(),
+                    },
+                );
+            }
+        } else {
+            values.insert(
+                token,
+                Provenance {
+                    value: true,
+                    sources: sources.to_vec(),
+                    token: token.to_owned(),
+                },
+            );
+        }
+    }
+    values
+}
+
+/// Computes the Levenshtein edit distance between `a` and `b` (each insertion, deletion, or
+/// substitution costs 1), via the standard two-row dynamic-programming table.
+fn levenshtein_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect_vec();
+    let b = b.chars().collect_vec();
+
+    let mut prev_row: Vec<usize> = (0..=b.len()).collect();
+    let mut curr_row = vec![0usize; b.len() + 1];
+
+    for (i, &ac) in a.iter().enumerate() {
+        curr_row[0] = i + 1;
+        for (j, &bc) in b.iter().enumerate() {
+            curr_row[j + 1] = if ac == bc {
+                prev_row[j]
+            } else {
+                1 + prev_row[j].min(prev_row[j + 1]).min(curr_row[j])
+            };
+        }
+        std::mem::swap(&mut prev_row, &mut curr_row);
+    }
+
+    prev_row[b.len()]
+}
+
+/// Finds the name in `known_names` closest to `token` by edit distance, if any is close enough to
+/// plausibly be a "did you mean" match rather than an unrelated flag -- at most a third of
+/// `token`'s length (minimum 1), the same kind of threshold Cargo applies when suggesting
+/// corrections for unknown subcommands.
+fn closest_use_flag_name(token: &str, known_names: &[&str]) -> Option<String> {
+    let max_distance = (token.chars().count() / 3).max(1);
+    known_names
+        .iter()
+        .map(|&name| (name, levenshtein_distance(token, name)))
+        .filter(|(_, distance)| *distance > 0 && *distance <= max_distance)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(name, _)| name.to_owned())
+}
+
+/// Identifies which kind of source contributed a [`UseFlagDecision`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UseFlagOrigin {
+    /// The default declared by the ebuild/eclass IUSE (or profile-injected IUSE_EFFECTIVE).
+    EbuildIuse,
+    /// Enabled via USE_EXPAND/USE_EXPAND_UNPREFIXED.
+    UseExpand,
+    /// Set or cleared by a USE variable or `package.use` entry.
+    ProfileUse,
+    /// Masked off by `use.mask`/`package.use.mask`.
+    UseMask,
+    /// Forced on by `use.force`/`package.use.force`.
+    UseForce,
+}
+
+/// One step in how a USE flag's final value was decided, in PMS application order. Returned by
+/// [`ConfigBundle::explain_use_flag`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UseFlagDecision {
+    pub origin: UseFlagOrigin,
+    /// The flag's value after this step is applied.
+    pub value: bool,
+    /// The node(s) responsible for this step, if any. Absent for [`UseFlagOrigin::EbuildIuse`]
+    /// and [`UseFlagOrigin::UseExpand`], which aren't backed by a single config file.
+    pub provenance: Option<Provenance>,
+}
+
+/// One step in deciding whether a package's KEYWORDS are accepted, in the order the underlying
+/// `ACCEPT_KEYWORDS` config lines were applied. Returned by
+/// [`ConfigBundle::explain_keyword_acceptance`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeywordDecision {
+    /// The token as written in the source, e.g. `amd64`, `~arm64`, `-*`.
+    pub token: String,
+    /// The config file(s) that contributed the token.
+    pub sources: Vec<PathBuf>,
+}
+
+/// One step in deciding whether a package is masked, in application order; the last entry wins.
+/// Returned by [`ConfigBundle::explain_mask`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MaskDecision {
+    pub kind: PackageMaskKind,
+    /// The config file(s) that contributed this mask/unmask entry.
+    pub sources: Vec<PathBuf>,
+}
+
+/// Which kind of USE token [`ConfigBundle::lint_use_flags`] is complaining about.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UseFlagLintKind {
+    /// A token from `USE`/`package.use` (or an empty-string profile default).
+    Set,
+    /// A token from `use.mask`/`package.use.mask`.
+    Mask,
+    /// A token from `use.force`/`package.use.force`.
+    Force,
+}
+
+/// A USE token that targets a package but names a flag absent from its effective IUSE, as
+/// surfaced by [`ConfigBundle::lint_use_flags`]. A flag that isn't in IUSE can never affect the
+/// package it's supposedly set for, so this is almost always a typo rather than an intentional
+/// no-op.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UseFlagWarning {
+    pub kind: UseFlagLintKind,
+    /// The unrecognized flag name, as written (without a leading `-`).
+    pub token: String,
+    /// The known effective IUSE name closest to `token` by edit distance, if any is close enough
+    /// to plausibly be what was meant.
+    pub suggestion: Option<String>,
+    /// The config file(s) that contributed the offending token.
+    pub sources: Vec<PathBuf>,
+}
+
 /// Represents a result of ConfigBundle::is_package_accepted().
 pub enum IsPackageAcceptedResult {
     /// The package is not accepted.
@@ -118,6 +307,9 @@ pub struct ConfigBundle {
     incremental_variables: HashMap<String, Vec<String>>,
     use_expand_values: Vec<String>,
     provided_packages: Vec<ProvidedPackage>,
+    /// Interns USE flag names so [`Self::compute_use_map`] can check set membership by `Symbol`
+    /// instead of re-hashing the same flag strings for every package/version pair evaluated.
+    interner: Interner,
 }
 
 impl ConfigBundle {
@@ -174,9 +366,69 @@ impl ConfigBundle {
             incremental_variables,
             use_expand_values,
             provided_packages,
+            interner: Interner::new(),
         }
     }
 
+    /// Reassembles a [`ConfigBundle`] from its already-computed parts, without re-evaluating any
+    /// [`ConfigSource`]s.
+    ///
+    /// Used by [`super::cache`] to reconstruct a bundle loaded from an on-disk cache entry. A
+    /// fresh [`Interner`] is fine here: it only memoizes lookups within a single bundle's
+    /// lifetime, so starting it empty just means the first few [`Self::compute_use_map`] calls
+    /// intern strings that a from-scratch evaluation would have interned earlier.
+    pub(crate) fn from_parts(
+        nodes: Vec<ConfigNode>,
+        env: Vars,
+        incremental_variables: HashMap<String, Vec<String>>,
+        use_expand_values: Vec<String>,
+        provided_packages: Vec<ProvidedPackage>,
+    ) -> Self {
+        Self {
+            nodes,
+            env,
+            incremental_variables,
+            use_expand_values,
+            provided_packages,
+            interner: Interner::new(),
+        }
+    }
+
+    /// Returns the raw config nodes backing this bundle.
+    pub(crate) fn nodes(&self) -> &[ConfigNode] {
+        &self.nodes
+    }
+
+    /// Returns the non-package-specific incremental variables computed for this bundle.
+    pub(crate) fn incremental_variables(&self) -> &HashMap<String, Vec<String>> {
+        &self.incremental_variables
+    }
+
+    /// Returns the USE flags originated from USE_EXPAND/USE_EXPAND_UNPREFIXED.
+    pub(crate) fn use_expand_values(&self) -> &[String] {
+        &self.use_expand_values
+    }
+
+    /// Like [`Self::from_sources`], but reuses a bundle previously cached under `cache_dir` by a
+    /// prior call to this method, if none of the source files it was built from have changed
+    /// since.
+    ///
+    /// See [`super::cache`] for the caveats around what "changed" this cache can and can't
+    /// detect.
+    pub fn from_sources_cached<S: ConfigSource, I: IntoIterator<Item = S>>(
+        sources: I,
+        cache_dir: &Path,
+    ) -> Result<Self> {
+        super::cache::load_or_build(sources, cache_dir)
+    }
+
+    /// Hashes this bundle's full effective configuration, for callers that need to key a cache
+    /// entry on "what configuration produced this result" (e.g. the ebuild evaluation shared
+    /// cache, which must not serve a hit computed under a different board/profile).
+    pub(crate) fn digest(&self) -> Result<Sha256Digest> {
+        super::cache::digest(self)
+    }
+
     /// Creates a minimal [`ConfigBundle`] suitable for unit testing.
     pub fn new_for_testing(arch: &str) -> Self {
         Self::from_sources([SimpleConfigSource::new(vec![ConfigNode {
@@ -345,24 +597,30 @@ impl ConfigBundle {
 
         let effective_iuse_map = self.compute_effective_iuse_map(ebuild_iuse_map);
 
-        let all_use_set: HashSet<&str> = self
-            .compute_use_variable_for_package(package, stable, &effective_iuse_map)
-            .collect();
-        let all_use_mask: HashSet<&str> = self
-            .compute_use_masks(package, stable, UseUpdateKind::Mask)
-            .collect();
-        let all_use_force: HashSet<&str> = self
-            .compute_use_masks(package, stable, UseUpdateKind::Force)
-            .collect();
+        // Interned so the membership checks below, run once per IUSE entry, hash a `Symbol`
+        // rather than re-hashing the same USE flag strings for every package/version pair.
+        let all_use_set = merge_incremental_symbols(
+            &self.interner,
+            self.compute_use_variable_for_package(package, stable, &effective_iuse_map),
+        );
+        let all_use_mask = merge_incremental_symbols(
+            &self.interner,
+            self.compute_use_masks(package, stable, UseUpdateKind::Mask),
+        );
+        let all_use_force = merge_incremental_symbols(
+            &self.interner,
+            self.compute_use_masks(package, stable, UseUpdateKind::Force),
+        );
 
         UseMap::from_iter(effective_iuse_map.keys().map(|name| {
-            let mut value = all_use_set.contains(name.as_str());
+            let symbol = self.interner.intern(name);
+            let mut value = all_use_set.contains(&symbol);
 
             // Apply mask/force. If both are applied, the mask takes precedence.
             // https://projects.gentoo.org/pms/8/pms.html#x1-540005.2.11
-            if all_use_mask.contains(name.as_str()) {
+            if all_use_mask.contains(&symbol) {
                 value = false;
-            } else if all_use_force.contains(name.as_str()) {
+            } else if all_use_force.contains(&symbol) {
                 value = true;
             }
 
@@ -370,6 +628,262 @@ impl ConfigBundle {
         }))
     }
 
+    /// Explains how a package's `flag` USE flag ended up the way it did, as a sequence of
+    /// [`UseFlagDecision`]s in PMS application order (IUSE default -> USE/package.use ->
+    /// mask/force, mask taking precedence over force; see [`Self::compute_use_map`]). Only the
+    /// steps that actually touched the flag are returned, so e.g. a flag that's never set via
+    /// `package.use` has no [`UseFlagOrigin::ProfileUse`] step. Returns an empty `Vec` if `flag`
+    /// isn't in the package's effective IUSE at all.
+    pub fn explain_use_flag(
+        &self,
+        package: &PackageRef,
+        stable: bool,
+        ebuild_iuse_map: &IUseMap,
+        flag: &str,
+    ) -> Vec<UseFlagDecision> {
+        let mut decisions = Vec::new();
+
+        let effective_iuse_map = self.compute_effective_iuse_map(ebuild_iuse_map);
+        let Some(default) = effective_iuse_map.get(flag) else {
+            return decisions;
+        };
+        decisions.push(UseFlagDecision {
+            origin: UseFlagOrigin::EbuildIuse,
+            value: *default,
+            provenance: None,
+        });
+
+        let profile_use = self.compute_use_variable_provenance(package, stable);
+
+        if let Some(provenance) = profile_use.get(flag) {
+            decisions.push(UseFlagDecision {
+                origin: UseFlagOrigin::ProfileUse,
+                value: provenance.value,
+                provenance: Some(provenance.clone()),
+            });
+        } else if self.use_expand_values.iter().any(|s| s == flag) {
+            decisions.push(UseFlagDecision {
+                origin: UseFlagOrigin::UseExpand,
+                value: true,
+                provenance: None,
+            });
+        }
+
+        if let Some(provenance) = self
+            .compute_use_mask_provenance(package, stable, UseUpdateKind::Mask)
+            .get(flag)
+        {
+            if provenance.value {
+                decisions.push(UseFlagDecision {
+                    origin: UseFlagOrigin::UseMask,
+                    value: false,
+                    provenance: Some(provenance.clone()),
+                });
+                return decisions;
+            }
+        }
+        if let Some(provenance) = self
+            .compute_use_mask_provenance(package, stable, UseUpdateKind::Force)
+            .get(flag)
+        {
+            if provenance.value {
+                decisions.push(UseFlagDecision {
+                    origin: UseFlagOrigin::UseForce,
+                    value: true,
+                    provenance: Some(provenance.clone()),
+                });
+            }
+        }
+
+        decisions
+    }
+
+    /// Like [`Self::compute_use_variable_for_package`]'s `config_uses` half, but keeps each merged
+    /// token's [`Provenance`] instead of collapsing the result into a plain iterator. Unlike
+    /// [`Self::compute_use_variable_for_package`], this doesn't include the ebuild-IUSE or
+    /// USE_EXPAND defaults, since neither is backed by a single config file to attribute.
+    fn compute_use_variable_provenance<'a>(
+        &'a self,
+        package: &'a PackageRef,
+        stable: bool,
+    ) -> HashMap<&'a str, Provenance> {
+        merge_incremental_tokens_with_provenance(self.nodes.iter().flat_map(move |node| {
+            match &node.value {
+                ConfigNodeValue::Vars(vars) => vars
+                    .get("USE")
+                    .map(|value| {
+                        value
+                            .split_ascii_whitespace()
+                            .map(|token| (node.sources.as_slice(), token))
+                            .collect_vec()
+                    })
+                    .unwrap_or_default(),
+                ConfigNodeValue::Uses(updates) => updates
+                    .iter()
+                    .filter(|update| {
+                        if update.kind != UseUpdateKind::Set {
+                            return false;
+                        }
+                        if let Some(atom) = &update.filter.atom {
+                            if !atom.matches(package) {
+                                return false;
+                            }
+                        }
+                        if update.filter.stable_only && !stable {
+                            return false;
+                        }
+                        true
+                    })
+                    .flat_map(|update| update.use_tokens.split_ascii_whitespace())
+                    .map(|token| (node.sources.as_slice(), token))
+                    .collect_vec(),
+                _ => Vec::new(),
+            }
+        }))
+    }
+
+    /// Like [`Self::compute_use_masks`], but keeps each merged token's [`Provenance`] instead of
+    /// collapsing the result into a plain set.
+    fn compute_use_mask_provenance<'a>(
+        &'a self,
+        package: &'a PackageRef,
+        stable: bool,
+        kind: UseUpdateKind,
+    ) -> HashMap<&'a str, Provenance> {
+        merge_incremental_tokens_with_provenance(self.nodes.iter().flat_map(move |node| {
+            match &node.value {
+                ConfigNodeValue::Uses(updates) => updates
+                    .iter()
+                    .filter_map(|update| {
+                        if update.kind != kind {
+                            return None;
+                        }
+                        if update.filter.stable_only && !stable {
+                            return None;
+                        }
+                        if let Some(atom) = &update.filter.atom {
+                            if !atom.matches(package) {
+                                return None;
+                            }
+                        }
+                        Some(update.use_tokens.as_str())
+                    })
+                    .flat_map(|tokens| tokens.split_ascii_whitespace())
+                    .map(|token| (node.sources.as_slice(), token))
+                    .collect_vec(),
+                _ => Vec::new(),
+            }
+        }))
+    }
+
+    /// Explains how a package's ACCEPT_KEYWORDS was resolved, as the ordered sequence of
+    /// [`KeywordDecision`]s that [`Self::compute_accept_keywords`] folds together (the config
+    /// values of each contributing node, in node order). Feed the result through
+    /// [`Self::is_keyword_accepted`]-style logic, or just read it top to bottom, to see which
+    /// line is responsible for a package's final acceptance.
+    pub fn explain_keyword_acceptance(&self, package: &PackageRef) -> Vec<KeywordDecision> {
+        let arch = self.env().get("ARCH").map(|s| &**s).unwrap_or_default();
+        let default_for_empty_config_line = format!("~{arch}");
+
+        self.nodes
+            .iter()
+            .flat_map(|node| match &node.value {
+                ConfigNodeValue::Vars(vars) => vars
+                    .get("ACCEPT_KEYWORDS")
+                    .map_or(Vec::new(), |value| vec![(node.sources.as_slice(), &**value)]),
+                ConfigNodeValue::AcceptKeywords(updates) => updates
+                    .iter()
+                    .filter(|update| update.atom.matches(package))
+                    .map(|o| {
+                        (
+                            node.sources.as_slice(),
+                            if o.accept_keywords.is_empty() {
+                                default_for_empty_config_line.as_str()
+                            } else {
+                                o.accept_keywords.as_str()
+                            },
+                        )
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .flat_map(|(sources, value)| {
+                value
+                    .split_ascii_whitespace()
+                    .map(move |token| KeywordDecision {
+                        token: token.to_owned(),
+                        sources: sources.to_vec(),
+                    })
+            })
+            .collect()
+    }
+
+    /// Explains whether a package is masked, as the ordered sequence of [`MaskDecision`]s that
+    /// [`Self::is_package_masked`] collapses down to a single bool (the last matching entry
+    /// wins).
+    pub fn explain_mask(&self, package: &PackageRef) -> Vec<MaskDecision> {
+        self.nodes
+            .iter()
+            .flat_map(|node| match &node.value {
+                ConfigNodeValue::PackageMasks(updates) => updates
+                    .iter()
+                    .filter(|update| update.atom.matches(package))
+                    .map(|update| MaskDecision {
+                        kind: update.kind,
+                        sources: node.sources.clone(),
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Flags USE tokens from `package.use`/`package.use.mask`/`package.use.force` (and their
+    /// non-package counterparts) that target `package` but name a flag absent from its effective
+    /// IUSE -- typically a typo in a profile or `make.conf`, since a flag that isn't in IUSE can
+    /// never affect the package it's supposedly set for. Each warning carries a "did you mean"
+    /// suggestion (by edit distance against the known effective IUSE names, the same approach
+    /// Cargo uses to suggest corrections for unknown subcommands) and the config file(s)
+    /// responsible, so a board bring-up mistake can be tracked back to its source.
+    pub fn lint_use_flags(
+        &self,
+        package: &PackageRef,
+        stable: bool,
+        ebuild_iuse_map: &IUseMap,
+    ) -> Vec<UseFlagWarning> {
+        let effective_iuse_map = self.compute_effective_iuse_map(ebuild_iuse_map);
+        let known_names = effective_iuse_map.keys().map(String::as_str).collect_vec();
+
+        [
+            (
+                UseFlagLintKind::Set,
+                self.compute_use_variable_provenance(package, stable),
+            ),
+            (
+                UseFlagLintKind::Mask,
+                self.compute_use_mask_provenance(package, stable, UseUpdateKind::Mask),
+            ),
+            (
+                UseFlagLintKind::Force,
+                self.compute_use_mask_provenance(package, stable, UseUpdateKind::Force),
+            ),
+        ]
+        .into_iter()
+        .flat_map(|(kind, provenance_by_name)| {
+            provenance_by_name
+                .into_iter()
+                .filter(|(name, _)| !effective_iuse_map.contains_key(*name))
+                .map(|(name, provenance)| UseFlagWarning {
+                    kind,
+                    token: name.to_owned(),
+                    suggestion: closest_use_flag_name(name, &known_names),
+                    sources: provenance.sources,
+                })
+                .collect_vec()
+        })
+        .collect()
+    }
+
     /// Returns if a package is masked by package.mask and friends.
     pub fn is_package_masked(&self, package: &PackageRef) -> bool {
         let status = self
@@ -708,7 +1222,8 @@ mod tests {
 
     use crate::{
         config::{
-            AcceptKeywordsUpdate, PackageBashrc, SimpleConfigSource, UseUpdate, UseUpdateFilter,
+            AcceptKeywordsUpdate, PackageBashrc, PackageMaskUpdate, SimpleConfigSource, UseUpdate,
+            UseUpdateFilter,
         },
         dependency::package::PackageAtom,
     };
@@ -1081,6 +1596,70 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_compute_use_map_mask_precedence() -> Result<()> {
+        let bundle = ConfigBundle::from_sources(vec![SimpleConfigSource::new(vec![
+            ConfigNode {
+                sources: vec![PathBuf::from("make.defaults")],
+                value: ConfigNodeValue::Vars(HashMap::from([(
+                    "USE".to_owned(),
+                    "foo bar".to_owned(),
+                )])),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("use.mask")],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Mask,
+                    filter: UseUpdateFilter {
+                        atom: None,
+                        stable_only: false,
+                    },
+                    use_tokens: "bar".to_string(),
+                }]),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("use.force")],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Force,
+                    filter: UseUpdateFilter {
+                        atom: None,
+                        stable_only: false,
+                    },
+                    use_tokens: "bar baz".to_string(),
+                }]),
+            },
+        ])]);
+
+        let iuse = IUseMap::from_iter([
+            ("foo".to_string(), false),
+            ("bar".to_string(), false),
+            ("baz".to_string(), false),
+        ]);
+
+        let use_map = bundle.compute_use_map(
+            PACKAGE_REF_A.package_name,
+            &VERSION_9999,
+            true,
+            &Slot {
+                main: "0".to_string(),
+                sub: "0".to_string(),
+            },
+            &iuse,
+        );
+
+        assert_eq!(
+            use_map,
+            UseMap::from_iter([
+                ("foo".to_string(), true),
+                // Masked, even though it's also force-enabled: mask takes precedence.
+                ("bar".to_string(), false),
+                ("baz".to_string(), true),
+            ])
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_features() -> Result<()> {
         let bundle = ConfigBundle::from_sources(vec![SimpleConfigSource::new(vec![
@@ -1189,4 +1768,285 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_explain_use_flag() -> Result<()> {
+        let bundle = ConfigBundle::from_sources(vec![SimpleConfigSource::new(vec![
+            ConfigNode {
+                sources: vec![PathBuf::from("make.defaults")],
+                value: ConfigNodeValue::Vars(HashMap::from([(
+                    "USE".to_owned(),
+                    "foo".to_owned(),
+                )])),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("package.use")],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Set,
+                    filter: UseUpdateFilter {
+                        atom: None,
+                        stable_only: false,
+                    },
+                    use_tokens: "-foo".to_string(),
+                }]),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("use.force")],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Force,
+                    filter: UseUpdateFilter {
+                        atom: None,
+                        stable_only: false,
+                    },
+                    use_tokens: "foo".to_string(),
+                }]),
+            },
+        ])]);
+
+        let iuse = HashMap::from([("foo".to_string(), false)]);
+
+        let decisions = bundle.explain_use_flag(&PACKAGE_REF_A, true, &iuse, "foo");
+        assert_eq!(
+            decisions,
+            vec![
+                UseFlagDecision {
+                    origin: UseFlagOrigin::EbuildIuse,
+                    value: false,
+                    provenance: None,
+                },
+                UseFlagDecision {
+                    origin: UseFlagOrigin::ProfileUse,
+                    value: false,
+                    provenance: Some(Provenance {
+                        value: false,
+                        sources: vec![PathBuf::from("package.use")],
+                        token: "-foo".to_string(),
+                    }),
+                },
+                UseFlagDecision {
+                    origin: UseFlagOrigin::UseForce,
+                    value: true,
+                    provenance: Some(Provenance {
+                        value: true,
+                        sources: vec![PathBuf::from("use.force")],
+                        token: "foo".to_string(),
+                    }),
+                },
+            ]
+        );
+
+        // A flag that's not in the effective IUSE at all has nothing to explain.
+        assert_eq!(
+            bundle.explain_use_flag(&PACKAGE_REF_A, true, &IUseMap::new(), "bar"),
+            Vec::new()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_mask() -> Result<()> {
+        let bundle = ConfigBundle::from_sources(vec![SimpleConfigSource::new(vec![
+            ConfigNode {
+                sources: vec![PathBuf::from("package.mask")],
+                value: ConfigNodeValue::PackageMasks(vec![PackageMaskUpdate {
+                    kind: PackageMaskKind::Mask,
+                    atom: PackageAtom::from_str("aaa/bbb")?,
+                }]),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("package.unmask")],
+                value: ConfigNodeValue::PackageMasks(vec![PackageMaskUpdate {
+                    kind: PackageMaskKind::Unmask,
+                    atom: PackageAtom::from_str("=aaa/bbb-9999")?,
+                }]),
+            },
+        ])]);
+
+        assert_eq!(
+            bundle.explain_mask(&PACKAGE_REF_A),
+            vec![
+                MaskDecision {
+                    kind: PackageMaskKind::Mask,
+                    sources: vec![PathBuf::from("package.mask")],
+                },
+                MaskDecision {
+                    kind: PackageMaskKind::Unmask,
+                    sources: vec![PathBuf::from("package.unmask")],
+                },
+            ]
+        );
+        assert!(!bundle.is_package_masked(&PACKAGE_REF_A));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_explain_keyword_acceptance() -> Result<()> {
+        let bundle = ConfigBundle::from_sources(vec![SimpleConfigSource::new(vec![
+            ConfigNode {
+                sources: vec![PathBuf::from("a")],
+                value: ConfigNodeValue::Vars(HashMap::from([(
+                    "ACCEPT_KEYWORDS".to_owned(),
+                    "amd64".to_owned(),
+                )])),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("b")],
+                value: ConfigNodeValue::AcceptKeywords(vec![AcceptKeywordsUpdate {
+                    atom: PackageAtom::from_str("=aaa/bbb-9999")?,
+                    accept_keywords: "-* arm64".to_owned(),
+                }]),
+            },
+        ])]);
+
+        assert_eq!(
+            bundle.explain_keyword_acceptance(&PACKAGE_REF_A),
+            vec![
+                KeywordDecision {
+                    token: "amd64".to_string(),
+                    sources: vec![PathBuf::from("a")],
+                },
+                KeywordDecision {
+                    token: "-*".to_string(),
+                    sources: vec![PathBuf::from("b")],
+                },
+                KeywordDecision {
+                    token: "arm64".to_string(),
+                    sources: vec![PathBuf::from("b")],
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_use_flags() -> Result<()> {
+        let bundle = ConfigBundle::from_sources(vec![SimpleConfigSource::new(vec![
+            ConfigNode {
+                sources: vec![PathBuf::from("package.use")],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Set,
+                    filter: UseUpdateFilter {
+                        atom: None,
+                        stable_only: false,
+                    },
+                    // "fooo" is a typo of the real "foo" flag; "bar" is a legitimate flag.
+                    use_tokens: "fooo bar".to_string(),
+                }]),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("package.use.mask")],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Mask,
+                    filter: UseUpdateFilter {
+                        atom: None,
+                        stable_only: false,
+                    },
+                    use_tokens: "not-even-close".to_string(),
+                }]),
+            },
+        ])]);
+
+        let iuse = HashMap::from([("foo".to_string(), false), ("bar".to_string(), false)]);
+
+        let mut warnings = bundle.lint_use_flags(&PACKAGE_REF_A, true, &iuse);
+        warnings.sort_by(|a, b| a.token.cmp(&b.token));
+
+        assert_eq!(
+            warnings,
+            vec![
+                UseFlagWarning {
+                    kind: UseFlagLintKind::Set,
+                    token: "fooo".to_string(),
+                    suggestion: Some("foo".to_string()),
+                    sources: vec![PathBuf::from("package.use")],
+                },
+                UseFlagWarning {
+                    kind: UseFlagLintKind::Mask,
+                    token: "not-even-close".to_string(),
+                    suggestion: None,
+                    sources: vec![PathBuf::from("package.use.mask")],
+                },
+            ]
+        );
+
+        // "bar" is in IUSE, so setting it is never flagged.
+        assert!(!warnings.iter().any(|w| w.token == "bar"));
+
+        Ok(())
+    }
+
+    /// A small xorshift PRNG, used in place of pulling in `proptest` (not otherwise a dependency
+    /// of this crate) to synthesize random incremental-variable token chains below.
+    struct Xorshift32(u32);
+
+    impl Xorshift32 {
+        fn next_u32(&mut self) -> u32 {
+            let mut x = self.0;
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            self.0 = x;
+            x
+        }
+
+        fn next_below(&mut self, bound: u32) -> u32 {
+            self.next_u32() % bound
+        }
+    }
+
+    /// Synthesizes a random chain of incremental-variable tokens (PMS 5.3.1: plain `token`,
+    /// `-token`, and `-*`) over a small fixed flag alphabet, so the chain is short enough that the
+    /// brute-force oracle below stays easy to reason about while still exercising removal and
+    /// clear-all interactions.
+    fn random_token_chain(rng: &mut Xorshift32, flags: &[&'static str]) -> Vec<String> {
+        let len = rng.next_below(8);
+        (0..len)
+            .map(|_| match rng.next_below(3) {
+                0 => flags[rng.next_below(flags.len() as u32) as usize].to_owned(),
+                1 => format!("-{}", flags[rng.next_below(flags.len() as u32) as usize]),
+                _ => "-*".to_owned(),
+            })
+            .collect()
+    }
+
+    /// Reproduces PMS's "last mention wins, `-*` resets everything before it" description of
+    /// incremental variable merging by scanning `chain` backwards, independently of
+    /// [`merge_incremental_tokens`]'s own forward accumulate-then-remove implementation.
+    fn flag_set_by_naive_backward_scan(chain: &[String], flag: &str) -> bool {
+        for token in chain.iter().rev() {
+            if token == "-*" {
+                return false;
+            }
+            if token == flag {
+                return true;
+            }
+            if token.strip_prefix('-') == Some(flag) {
+                return false;
+            }
+        }
+        false
+    }
+
+    #[test]
+    fn test_merge_incremental_tokens_matches_naive_backward_scan() {
+        const FLAGS: &[&str] = &["a", "b", "c"];
+        let mut rng = Xorshift32(0xc0ffee);
+
+        for _ in 0..500 {
+            let chain = random_token_chain(&mut rng, FLAGS);
+            let chain_refs = chain.iter().map(String::as_str);
+            let merged: HashSet<&str> = merge_incremental_tokens(chain_refs).collect();
+
+            for &flag in FLAGS {
+                assert_eq!(
+                    merged.contains(flag),
+                    flag_set_by_naive_backward_scan(&chain, flag),
+                    "chain = {chain:?}, flag = {flag}"
+                );
+            }
+        }
+    }
 }
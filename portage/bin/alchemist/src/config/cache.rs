@@ -0,0 +1,651 @@
+// Copyright 2026 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! A persistent, fingerprint-invalidated cache for [`ConfigBundle`].
+//!
+//! `ConfigBundle::from_sources` walks and evaluates the entire profile chain (and make.conf,
+//! package.use, package.mask, ...) from scratch on every process start, even though none of it
+//! usually changed since the last invocation. This borrows Cargo's fingerprint-then-skip model
+//! for its job queue: a cache entry records the exact set of source files read to build it, their
+//! size and mtime, and the crate version; [`load_or_build`] re-evaluates from scratch only if any
+//! of those have changed, or skips straight to deserializing the cached bundle otherwise.
+//!
+//! The underlying config types ([`ConfigNode`], [`UseUpdate`], ...) aren't `Serialize`/
+//! `Deserialize` themselves, so this module mirrors them as a parallel, privately-fielded shadow
+//! hierarchy, plus a handful of `From`/`TryFrom` conversions to and from the real types.
+//!
+//! Cache validity is necessarily a weaker guarantee than "re-run and compare": validating the
+//! fingerprint only re-stats the files a *previous* run read, so a profile edit that adds a
+//! brand-new file to the chain (e.g. a freshly created `parent` pointing somewhere not read
+//! before) won't be noticed by the fingerprint check on its own, since discovering the current
+//! file list is exactly the work a cache hit is meant to let us skip. In practice this matches how
+//! `build.ninja`-style fingerprinting tools behave: they detect edits to known inputs, not the
+//! addition of inputs they didn't know to look for.
+
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use version::Version;
+
+use crate::{data::Vars, dependency::package::PackageAtom, repository::Sha256Digest};
+
+use super::{
+    AcceptKeywordsUpdate, ConfigBundle, ConfigNode, ConfigNodeValue, ConfigSource, PackageBashrc,
+    PackageMaskKind, PackageMaskUpdate, ProvidedPackage, UseUpdate, UseUpdateFilter, UseUpdateKind,
+};
+
+/// Bumped whenever [`CachedConfigBundle`] (or a type it embeds) changes shape, so that a cache
+/// entry written by an older binary is never mistaken for a hit by a newer one.
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// Loads the bundle cached under `cache_dir`, rebuilding and re-caching it if it's missing, stale,
+/// or was written by an incompatible binary.
+pub(super) fn load_or_build<S: ConfigSource, I: IntoIterator<Item = S>>(
+    sources: I,
+    cache_dir: &Path,
+) -> Result<ConfigBundle> {
+    let entry_path = cache_dir.join("config_bundle.json");
+
+    if let Some(bundle) = try_load(&entry_path)? {
+        return Ok(bundle);
+    }
+
+    let bundle = ConfigBundle::from_sources(sources);
+
+    // A failure to write the cache should never fail evaluation: it only costs a future cache
+    // miss, the same tradeoff `CacheStorage::put` makes in `crate::ebuild::cache`.
+    if let Err(err) = save(&entry_path, &bundle) {
+        eprintln!(
+            "Warning: failed to write config bundle cache to {}: {:#}",
+            entry_path.display(),
+            err
+        );
+    }
+
+    Ok(bundle)
+}
+
+/// Hashes the full effective configuration (profiles, make.conf, package.use/mask, ...) `bundle`
+/// was built from, so callers keying a cache entry on "what configuration produced this result"
+/// (e.g. [`crate::ebuild::cache::compute_digest`]) don't collide across boards/profiles that
+/// happen to evaluate the same ebuild differently.
+pub(super) fn digest(bundle: &ConfigBundle) -> Result<Sha256Digest> {
+    let data = serde_json::to_vec(&CachedConfigBundle::from(bundle))
+        .context("Failed to serialize config bundle for digest")?;
+    Ok(Sha256::digest(data))
+}
+
+/// Returns the cached bundle at `entry_path` if it exists, deserializes cleanly, and every source
+/// file it was fingerprinted from still matches.
+fn try_load(entry_path: &Path) -> Result<Option<ConfigBundle>> {
+    let data = match fs::read(entry_path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(e).with_context(|| format!("Failed to read {}", entry_path.display()))
+        }
+    };
+
+    let entry: CacheEntry = match serde_json::from_slice(&data) {
+        Ok(entry) => entry,
+        // A corrupt or format-incompatible entry should cost a rebuild, not fail the process.
+        Err(_) => return Ok(None),
+    };
+
+    if entry.format_version != CACHE_FORMAT_VERSION
+        || entry.crate_version != env!("CARGO_PKG_VERSION")
+    {
+        return Ok(None);
+    }
+
+    if entry.fingerprint.iter().any(|file| !file.still_matches()) {
+        return Ok(None);
+    }
+
+    match ConfigBundle::try_from(entry.bundle) {
+        Ok(bundle) => Ok(Some(bundle)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Fingerprints `bundle`'s sources and writes it plus its fingerprint to `entry_path`.
+fn save(entry_path: &Path, bundle: &ConfigBundle) -> Result<()> {
+    if let Some(parent) = entry_path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+
+    let fingerprint = bundle
+        .sources()
+        .into_iter()
+        .unique()
+        .map(SourceFingerprint::compute)
+        .collect::<Result<Vec<_>>>()?;
+
+    let entry = CacheEntry {
+        format_version: CACHE_FORMAT_VERSION,
+        crate_version: env!("CARGO_PKG_VERSION").to_owned(),
+        fingerprint,
+        bundle: CachedConfigBundle::from(bundle),
+    };
+
+    let data = serde_json::to_vec(&entry).context("Failed to serialize config bundle cache")?;
+
+    // Write to a sibling temporary file and rename into place, so a concurrent reader never
+    // observes a partially written entry.
+    let temp_path = entry_path.with_extension(format!("tmp.{}", std::process::id()));
+    fs::write(&temp_path, &data)
+        .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+    fs::rename(&temp_path, entry_path)
+        .with_context(|| format!("Failed to commit {}", entry_path.display()))?;
+
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    format_version: u32,
+    crate_version: String,
+    fingerprint: Vec<SourceFingerprint>,
+    bundle: CachedConfigBundle,
+}
+
+/// Records enough about a source file at the time it was last read to detect most edits to it
+/// without re-reading its content: its size, and its modification time. This is the same
+/// heuristic `make`/`ninja`/Cargo's own fingerprinting use; it can in principle miss an edit that
+/// preserves both (practically, an edit within the same tick of std::fs's mtime resolution that
+/// also preserves length), but it avoids re-hashing every profile and `package.*` file on every
+/// invocation just to answer "did anything change".
+#[derive(Serialize, Deserialize)]
+struct SourceFingerprint {
+    path: PathBuf,
+    len: u64,
+    modified: SystemTime,
+}
+
+impl SourceFingerprint {
+    fn compute(path: &Path) -> Result<Self> {
+        let metadata =
+            fs::metadata(path).with_context(|| format!("Failed to stat {}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+            len: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+
+    /// Returns whether this file still has the size and modification time it had when this
+    /// fingerprint was computed. A file that was deleted no longer matches.
+    fn still_matches(&self) -> bool {
+        matches!(Self::compute(&self.path), Ok(current) if current.len == self.len && current.modified == self.modified)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedConfigBundle {
+    nodes: Vec<CachedConfigNode>,
+    env: Vars,
+    incremental_variables: HashMap<String, Vec<String>>,
+    use_expand_values: Vec<String>,
+    provided_packages: Vec<CachedProvidedPackage>,
+}
+
+impl From<&ConfigBundle> for CachedConfigBundle {
+    fn from(bundle: &ConfigBundle) -> Self {
+        Self {
+            nodes: bundle.nodes().iter().map(CachedConfigNode::from).collect(),
+            env: bundle.env().clone(),
+            incremental_variables: bundle.incremental_variables().clone(),
+            use_expand_values: bundle.use_expand_values().to_vec(),
+            provided_packages: bundle
+                .provided_packages()
+                .iter()
+                .map(CachedProvidedPackage::from)
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<CachedConfigBundle> for ConfigBundle {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedConfigBundle) -> Result<Self> {
+        let nodes = cached
+            .nodes
+            .into_iter()
+            .map(ConfigNode::try_from)
+            .collect::<Result<Vec<_>>>()?;
+        let provided_packages = cached
+            .provided_packages
+            .into_iter()
+            .map(ProvidedPackage::try_from)
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(ConfigBundle::from_parts(
+            nodes,
+            cached.env,
+            cached.incremental_variables,
+            cached.use_expand_values,
+            provided_packages,
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedConfigNode {
+    sources: Vec<PathBuf>,
+    value: CachedConfigNodeValue,
+}
+
+impl From<&ConfigNode> for CachedConfigNode {
+    fn from(node: &ConfigNode) -> Self {
+        Self {
+            sources: node.sources.clone(),
+            value: CachedConfigNodeValue::from(&node.value),
+        }
+    }
+}
+
+impl TryFrom<CachedConfigNode> for ConfigNode {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedConfigNode) -> Result<Self> {
+        Ok(ConfigNode {
+            sources: cached.sources,
+            value: ConfigNodeValue::try_from(cached.value)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedConfigNodeValue {
+    Vars(Vars),
+    AcceptKeywords(Vec<CachedAcceptKeywordsUpdate>),
+    Uses(Vec<CachedUseUpdate>),
+    PackageMasks(Vec<CachedPackageMaskUpdate>),
+    ProvidedPackages(Vec<CachedProvidedPackage>),
+    ProfileBashrc(Vec<PathBuf>),
+    PackageBashrcs(Vec<CachedPackageBashrc>),
+}
+
+impl From<&ConfigNodeValue> for CachedConfigNodeValue {
+    fn from(value: &ConfigNodeValue) -> Self {
+        match value {
+            ConfigNodeValue::Vars(vars) => Self::Vars(vars.clone()),
+            ConfigNodeValue::AcceptKeywords(updates) => Self::AcceptKeywords(
+                updates
+                    .iter()
+                    .map(CachedAcceptKeywordsUpdate::from)
+                    .collect(),
+            ),
+            ConfigNodeValue::Uses(updates) => {
+                Self::Uses(updates.iter().map(CachedUseUpdate::from).collect())
+            }
+            ConfigNodeValue::PackageMasks(updates) => {
+                Self::PackageMasks(updates.iter().map(CachedPackageMaskUpdate::from).collect())
+            }
+            ConfigNodeValue::ProvidedPackages(packages) => {
+                Self::ProvidedPackages(packages.iter().map(CachedProvidedPackage::from).collect())
+            }
+            ConfigNodeValue::ProfileBashrc(paths) => Self::ProfileBashrc(paths.clone()),
+            ConfigNodeValue::PackageBashrcs(bashrcs) => {
+                Self::PackageBashrcs(bashrcs.iter().map(CachedPackageBashrc::from).collect())
+            }
+        }
+    }
+}
+
+impl TryFrom<CachedConfigNodeValue> for ConfigNodeValue {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedConfigNodeValue) -> Result<Self> {
+        Ok(match cached {
+            CachedConfigNodeValue::Vars(vars) => Self::Vars(vars),
+            CachedConfigNodeValue::AcceptKeywords(updates) => Self::AcceptKeywords(
+                updates
+                    .into_iter()
+                    .map(AcceptKeywordsUpdate::try_from)
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            CachedConfigNodeValue::Uses(updates) => Self::Uses(
+                updates
+                    .into_iter()
+                    .map(UseUpdate::try_from)
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            CachedConfigNodeValue::PackageMasks(updates) => Self::PackageMasks(
+                updates
+                    .into_iter()
+                    .map(PackageMaskUpdate::try_from)
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            CachedConfigNodeValue::ProvidedPackages(packages) => Self::ProvidedPackages(
+                packages
+                    .into_iter()
+                    .map(ProvidedPackage::try_from)
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+            CachedConfigNodeValue::ProfileBashrc(paths) => Self::ProfileBashrc(paths),
+            CachedConfigNodeValue::PackageBashrcs(bashrcs) => Self::PackageBashrcs(
+                bashrcs
+                    .into_iter()
+                    .map(PackageBashrc::try_from)
+                    .collect::<Result<Vec<_>>>()?,
+            ),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedAcceptKeywordsUpdate {
+    atom: String,
+    accept_keywords: String,
+}
+
+impl From<&AcceptKeywordsUpdate> for CachedAcceptKeywordsUpdate {
+    fn from(update: &AcceptKeywordsUpdate) -> Self {
+        Self {
+            atom: update.atom.to_string(),
+            accept_keywords: update.accept_keywords.clone(),
+        }
+    }
+}
+
+impl TryFrom<CachedAcceptKeywordsUpdate> for AcceptKeywordsUpdate {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedAcceptKeywordsUpdate) -> Result<Self> {
+        Ok(Self {
+            atom: PackageAtom::from_str(&cached.atom)?,
+            accept_keywords: cached.accept_keywords,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedUseUpdate {
+    kind: CachedUseUpdateKind,
+    filter: CachedUseUpdateFilter,
+    use_tokens: String,
+}
+
+impl From<&UseUpdate> for CachedUseUpdate {
+    fn from(update: &UseUpdate) -> Self {
+        Self {
+            kind: CachedUseUpdateKind::from(update.kind),
+            filter: CachedUseUpdateFilter::from(&update.filter),
+            use_tokens: update.use_tokens.clone(),
+        }
+    }
+}
+
+impl TryFrom<CachedUseUpdate> for UseUpdate {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedUseUpdate) -> Result<Self> {
+        Ok(Self {
+            kind: UseUpdateKind::from(cached.kind),
+            filter: UseUpdateFilter::try_from(cached.filter)?,
+            use_tokens: cached.use_tokens,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedUseUpdateKind {
+    Set,
+    Mask,
+    Force,
+}
+
+impl From<UseUpdateKind> for CachedUseUpdateKind {
+    fn from(kind: UseUpdateKind) -> Self {
+        match kind {
+            UseUpdateKind::Set => Self::Set,
+            UseUpdateKind::Mask => Self::Mask,
+            UseUpdateKind::Force => Self::Force,
+        }
+    }
+}
+
+impl From<CachedUseUpdateKind> for UseUpdateKind {
+    fn from(cached: CachedUseUpdateKind) -> Self {
+        match cached {
+            CachedUseUpdateKind::Set => Self::Set,
+            CachedUseUpdateKind::Mask => Self::Mask,
+            CachedUseUpdateKind::Force => Self::Force,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedUseUpdateFilter {
+    atom: Option<String>,
+    stable_only: bool,
+}
+
+impl From<&UseUpdateFilter> for CachedUseUpdateFilter {
+    fn from(filter: &UseUpdateFilter) -> Self {
+        Self {
+            atom: filter.atom.as_ref().map(|atom| atom.to_string()),
+            stable_only: filter.stable_only,
+        }
+    }
+}
+
+impl TryFrom<CachedUseUpdateFilter> for UseUpdateFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedUseUpdateFilter) -> Result<Self> {
+        Ok(Self {
+            atom: cached
+                .atom
+                .map(|atom| PackageAtom::from_str(&atom))
+                .transpose()?,
+            stable_only: cached.stable_only,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedPackageMaskUpdate {
+    kind: CachedPackageMaskKind,
+    atom: String,
+}
+
+impl From<&PackageMaskUpdate> for CachedPackageMaskUpdate {
+    fn from(update: &PackageMaskUpdate) -> Self {
+        Self {
+            kind: CachedPackageMaskKind::from(update.kind),
+            atom: update.atom.to_string(),
+        }
+    }
+}
+
+impl TryFrom<CachedPackageMaskUpdate> for PackageMaskUpdate {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedPackageMaskUpdate) -> Result<Self> {
+        Ok(Self {
+            kind: PackageMaskKind::from(cached.kind),
+            atom: PackageAtom::from_str(&cached.atom)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+enum CachedPackageMaskKind {
+    Mask,
+    Unmask,
+}
+
+impl From<PackageMaskKind> for CachedPackageMaskKind {
+    fn from(kind: PackageMaskKind) -> Self {
+        match kind {
+            PackageMaskKind::Mask => Self::Mask,
+            PackageMaskKind::Unmask => Self::Unmask,
+        }
+    }
+}
+
+impl From<CachedPackageMaskKind> for PackageMaskKind {
+    fn from(cached: CachedPackageMaskKind) -> Self {
+        match cached {
+            CachedPackageMaskKind::Mask => Self::Mask,
+            CachedPackageMaskKind::Unmask => Self::Unmask,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedProvidedPackage {
+    package_name: String,
+    version: String,
+}
+
+impl From<&ProvidedPackage> for CachedProvidedPackage {
+    fn from(package: &ProvidedPackage) -> Self {
+        Self {
+            package_name: package.package_name.clone(),
+            version: package.version.to_string(),
+        }
+    }
+}
+
+impl TryFrom<CachedProvidedPackage> for ProvidedPackage {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedProvidedPackage) -> Result<Self> {
+        Ok(Self {
+            package_name: cached.package_name,
+            version: Version::from_str(&cached.version)?,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedPackageBashrc {
+    atom: String,
+    paths: Vec<PathBuf>,
+}
+
+impl From<&PackageBashrc> for CachedPackageBashrc {
+    fn from(bashrc: &PackageBashrc) -> Self {
+        Self {
+            atom: bashrc.atom.to_string(),
+            paths: bashrc.paths.clone(),
+        }
+    }
+}
+
+impl TryFrom<CachedPackageBashrc> for PackageBashrc {
+    type Error = anyhow::Error;
+
+    fn try_from(cached: CachedPackageBashrc) -> Result<Self> {
+        Ok(Self {
+            atom: PackageAtom::from_str(&cached.atom)?,
+            paths: cached.paths,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::SimpleConfigSource;
+
+    fn sample_bundle() -> Result<ConfigBundle> {
+        Ok(ConfigBundle::from_sources([SimpleConfigSource::new(vec![
+            ConfigNode {
+                sources: vec![PathBuf::from("make.conf")],
+                value: ConfigNodeValue::Vars(HashMap::from([
+                    ("ARCH".to_owned(), "amd64".to_owned()),
+                    ("ACCEPT_KEYWORDS".to_owned(), "amd64".to_owned()),
+                ])),
+            },
+            ConfigNode {
+                sources: vec![PathBuf::from("package.use")],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Set,
+                    filter: UseUpdateFilter {
+                        atom: Some(PackageAtom::from_str("aaa/bbb")?),
+                        stable_only: false,
+                    },
+                    use_tokens: "foo".to_owned(),
+                }]),
+            },
+        ])]))
+    }
+
+    #[test]
+    fn test_cache_bundle_roundtrip() -> Result<()> {
+        let bundle = sample_bundle()?;
+        let cached = CachedConfigBundle::from(&bundle);
+        let data = serde_json::to_vec(&cached)?;
+        let cached: CachedConfigBundle = serde_json::from_slice(&data)?;
+        let restored = ConfigBundle::try_from(cached)?;
+
+        assert_eq!(restored.env(), bundle.env());
+        assert_eq!(restored.sources(), bundle.sources());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_or_build_caches_across_calls() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let profile_dir = temp_dir.path().join("profile");
+        fs::create_dir_all(&profile_dir)?;
+        let use_file = profile_dir.join("package.use");
+        fs::write(&use_file, "aaa/bbb foo\n")?;
+
+        let cache_dir = temp_dir.path().join("cache");
+
+        let make_sources = || {
+            [SimpleConfigSource::new(vec![ConfigNode {
+                sources: vec![use_file.clone()],
+                value: ConfigNodeValue::Uses(vec![UseUpdate {
+                    kind: UseUpdateKind::Set,
+                    filter: UseUpdateFilter {
+                        atom: None,
+                        stable_only: false,
+                    },
+                    use_tokens: "foo".to_owned(),
+                }]),
+            }])]
+        };
+
+        let first = load_or_build(make_sources(), &cache_dir)?;
+        assert!(cache_dir.join("config_bundle.json").exists());
+
+        let second = load_or_build(make_sources(), &cache_dir)?;
+        assert_eq!(first.sources(), second.sources());
+
+        // Touching the source file should invalidate the cache on the next call. Changing the
+        // file's length (rather than just its content) keeps this assertion robust to coarse
+        // filesystem mtime resolution, since `SourceFingerprint` compares length and mtime, not
+        // content.
+        fs::write(&use_file, "aaa/bbb barbaz\n")?;
+        let third = load_or_build(make_sources(), &cache_dir)?;
+        assert_eq!(third.sources(), first.sources());
+        let entry: CacheEntry =
+            serde_json::from_slice(&fs::read(cache_dir.join("config_bundle.json"))?)?;
+        assert!(entry
+            .fingerprint
+            .iter()
+            .any(|file| file.path == use_file && file.len == "aaa/bbb barbaz\n".len() as u64));
+
+        Ok(())
+    }
+}
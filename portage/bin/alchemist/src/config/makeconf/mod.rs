@@ -4,7 +4,7 @@
 
 use anyhow::{bail, Context, Result};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Debug,
     fs::read_to_string,
     path::{Path, PathBuf},
@@ -17,19 +17,71 @@ use super::{ConfigNode, ConfigNodeValue, ConfigSource};
 pub mod generate;
 mod parser;
 
+/// How many `source` directives deep [`MakeConf::load_file`] will follow before giving up, as a
+/// backstop against a misconfigured include graph that isn't an outright cycle but is still
+/// unreasonably deep.
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+/// The operator half of a `${VAR<op>}` expansion, evaluated against the final environment in
+/// [`Value::fmt_with_env`]. Mirrors the subset of bash parameter expansion Portage configs
+/// actually use:
+/// https://dev.gentoo.org/~ulm/pms/head/pms.html#x1-260000
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ExpansionOp {
+    /// `${VAR:-word}`: `word` if `VAR` is unset or empty, else `VAR`'s value.
+    Default(RVal),
+    /// `${VAR:+word}`: `word` if `VAR` is set and non-empty, else empty.
+    Alternate(RVal),
+    /// `${VAR#pattern}`: `VAR`'s value with a literal leading `pattern` stripped, if present.
+    StripPrefix(String),
+    /// `${VAR%pattern}`: `VAR`'s value with a literal trailing `pattern` stripped, if present.
+    StripSuffix(String),
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 enum Value {
     Literal(String),
-    UnresolvedExpansion(String),
+    /// A bare `$VAR`/`${VAR}` whose value isn't known until the final environment is available.
+    EnvLookup(String),
+    /// `${<source><op>}`, where `source` is the variable's current value (either inlined directly
+    /// if it was already known when this was parsed, or an [`Value::EnvLookup`] deferred to the
+    /// final environment otherwise) and `op` is the operator applied to it.
+    ParamExpansion { source: Box<RVal>, op: ExpansionOp },
 }
 
 impl Value {
     fn fmt_with_env(&self, mut w: impl std::fmt::Write, env: &Vars) {
         match self {
             Value::Literal(s) => w.write_str(s.as_ref()).unwrap(),
-            Value::UnresolvedExpansion(name) => w
-                .write_str(env.get(name).map(|s| &**s).unwrap_or_default())
-                .unwrap(),
+            Value::EnvLookup(name) => {
+                w.write_str(env.get(name).map(|s| s.as_str()).unwrap_or_default())
+                    .unwrap();
+            }
+            Value::ParamExpansion { source, op } => {
+                let current = source.evaluate(env);
+                match op {
+                    ExpansionOp::Default(word) => {
+                        if current.is_empty() {
+                            word.fmt_with_env(&mut w, env);
+                        } else {
+                            w.write_str(&current).unwrap();
+                        }
+                    }
+                    ExpansionOp::Alternate(word) => {
+                        if !current.is_empty() {
+                            word.fmt_with_env(&mut w, env);
+                        }
+                    }
+                    ExpansionOp::StripPrefix(pattern) => {
+                        w.write_str(current.strip_prefix(pattern.as_str()).unwrap_or(&current))
+                            .unwrap();
+                    }
+                    ExpansionOp::StripSuffix(pattern) => {
+                        w.write_str(current.strip_suffix(pattern.as_str()).unwrap_or(&current))
+                            .unwrap();
+                    }
+                }
+            }
         }
     }
 }
@@ -73,18 +125,35 @@ impl RVal {
     }
 
     pub fn try_to_string_no_unresolved_expansion(&self) -> Result<String> {
-        let mut result = String::new();
+        self.ensure_no_unresolved_expansion()?;
+        // Every `EnvLookup` has just been proven absent from this value (including inside any
+        // nested `ParamExpansion`'s source/word), so evaluating against an empty environment is
+        // equivalent to evaluating against the real one.
+        Ok(self.evaluate(&Vars::new()))
+    }
+
+    /// Recursively checks that no [`Value::EnvLookup`] remains anywhere in this value, including
+    /// nested inside a [`Value::ParamExpansion`]'s `source` or its `Default`/`Alternate` word.
+    /// Returns an error naming the unresolved variable if one is found.
+    fn ensure_no_unresolved_expansion(&self) -> Result<()> {
         for value in self.vals.iter() {
             match value {
-                Value::Literal(s) => {
-                    result.push_str(s);
-                }
-                Value::UnresolvedExpansion(name) => {
+                Value::Literal(_) => {}
+                Value::EnvLookup(name) => {
                     bail!("contains unresolved expansion ${}", name);
                 }
+                Value::ParamExpansion { source, op } => {
+                    source.ensure_no_unresolved_expansion()?;
+                    match op {
+                        ExpansionOp::Default(word) | ExpansionOp::Alternate(word) => {
+                            word.ensure_no_unresolved_expansion()?;
+                        }
+                        ExpansionOp::StripPrefix(_) | ExpansionOp::StripSuffix(_) => {}
+                    }
+                }
             }
         }
-        Ok(result)
+        Ok(())
     }
 }
 
@@ -96,10 +165,181 @@ impl FromIterator<Value> for RVal {
     }
 }
 
+/// Resolves a [`parser::RVal`] into [`RVal`], inlining any bare `$VAR`/`${VAR}` whose value is
+/// already known in `values`. A not-yet-known bare expansion is deferred instead: it's pushed
+/// through as a [`Value::EnvLookup`] to be resolved later against the final environment, by
+/// [`Value::fmt_with_env`]. A parameter expansion with an operator (`${VAR:-word}` and friends) is
+/// always pushed through as a [`Value::ParamExpansion`], but its `source` is inlined the same way,
+/// so later operations on an already-known variable still see this file's own assignments.
+fn resolve_rval(values: &HashMap<String, RVal>, rval: parser::RVal) -> RVal {
+    let mut resolved_rval = RVal::new();
+    for value in rval.vals {
+        match value {
+            parser::Value::Literal(s) => {
+                let s = *s.fragment();
+                resolved_rval.push(Value::Literal(s.to_owned()));
+            }
+            parser::Value::Expansion(name) => {
+                let name = *name.fragment();
+                match values.get(name) {
+                    None => {
+                        resolved_rval.push(Value::EnvLookup(name.to_owned()));
+                    }
+                    Some(expanded_rval) => {
+                        for value in expanded_rval.vals.iter() {
+                            resolved_rval.push(value.clone());
+                        }
+                    }
+                }
+            }
+            parser::Value::ParamExpansion(name, op) => {
+                let source = Box::new(lookup_rval(values, *name.fragment()));
+                let op = match op {
+                    parser::ParamOp::Default(word) => {
+                        ExpansionOp::Default(resolve_rval(values, word))
+                    }
+                    parser::ParamOp::Alternate(word) => {
+                        ExpansionOp::Alternate(resolve_rval(values, word))
+                    }
+                    parser::ParamOp::StripPrefix(pattern) => {
+                        ExpansionOp::StripPrefix((*pattern.fragment()).to_owned())
+                    }
+                    parser::ParamOp::StripSuffix(pattern) => {
+                        ExpansionOp::StripSuffix((*pattern.fragment()).to_owned())
+                    }
+                };
+                resolved_rval.push(Value::ParamExpansion { source, op });
+            }
+        }
+    }
+    resolved_rval
+}
+
+/// Resolves `name` to its current value: the already-known [`RVal`] if `values` has it (inlined
+/// directly, mirroring how a bare `$VAR` reference is resolved above), or a single deferred
+/// [`Value::EnvLookup`] if it isn't known yet at this point in the file.
+fn lookup_rval(values: &HashMap<String, RVal>, name: &str) -> RVal {
+    match values.get(name) {
+        Some(rval) => rval.clone(),
+        None => RVal::from_iter([Value::EnvLookup(name.to_owned())]),
+    }
+}
+
+/// Returns whether `target`, a `source` directive's resolved value, should be treated as a shell
+/// glob rather than a literal path. Only `*` and `?` are recognized; bracket character classes
+/// (`[...]`) aren't supported.
+fn has_glob_metachars(target: &str) -> bool {
+    target.contains(['*', '?'])
+}
+
+/// Expands `target`, an absolute path that may contain `*`/`?` glob components, against the
+/// filesystem, matching one path component at a time and sorting each component's matches by name
+/// to match the existing directory-load ordering in [`MakeConf::load_file_contents`]. A component
+/// without glob metacharacters is kept literal without checking that it exists; that's left to the
+/// caller, the same way a non-glob `source` target is.
+fn expand_source_glob(target: &Path) -> Vec<PathBuf> {
+    let mut candidates = vec![PathBuf::new()];
+
+    for component in target.components() {
+        let component = component.as_os_str();
+        if !has_glob_metachars(&component.to_string_lossy()) {
+            for candidate in candidates.iter_mut() {
+                candidate.push(component);
+            }
+            continue;
+        }
+
+        let mut next_candidates = Vec::new();
+        for candidate in &candidates {
+            let Ok(entries) = candidate.read_dir() else {
+                continue;
+            };
+            let mut names = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.file_name()))
+                .collect::<Vec<_>>();
+            names.sort();
+
+            for name in names {
+                if wildcard_match(&component.to_string_lossy(), &name.to_string_lossy()) {
+                    next_candidates.push(candidate.join(name));
+                }
+            }
+        }
+        candidates = next_candidates;
+    }
+
+    candidates
+}
+
+/// Matches `name` against `pattern`, where `*` in `pattern` matches any (possibly empty) run of
+/// characters and `?` matches exactly one, via the standard two-row wildcard-matching DP.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+
+    let mut dp = vec![false; name.len() + 1];
+    dp[0] = true;
+    for &p in &pattern {
+        let mut next_dp = vec![false; name.len() + 1];
+        next_dp[0] = dp[0] && p == '*';
+        for j in 1..=name.len() {
+            next_dp[j] = match p {
+                '*' => next_dp[j - 1] || dp[j],
+                '?' => dp[j - 1],
+                c => dp[j - 1] && c == name[j - 1],
+            };
+        }
+        dp = next_dp;
+    }
+    dp[name.len()]
+}
+
+/// Declares, per variable name, whether the current process environment should win over a
+/// `make.conf`-assigned value ("environment-wins") rather than being clobbered by it as happens
+/// by default ("file-wins"), mirroring the layering Cargo applies between its config files and
+/// `CARGO_*` environment overrides.
+///
+/// Used by [`MakeConf::evaluate_configs_with_overrides`], e.g. so a caller can force `USE` from
+/// the command line while `make.conf` is still evaluated for every other variable. A `${USE}`
+/// expansion inside the file sees the overriding value only where it was deferred to the final
+/// environment; one that follows the file's own earlier assignment of `USE` was inlined at load
+/// time and keeps that assignment's value.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OverridePolicy {
+    environment_wins: HashSet<String>,
+}
+
+impl OverridePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks `name` as environment-wins: if `env` already has a value for `name` when
+    /// [`MakeConf::evaluate_configs_with_overrides`] runs, that value is kept instead of being
+    /// replaced by this file's assignment.
+    pub fn environment_wins(mut self, name: impl Into<String>) -> Self {
+        self.environment_wins.insert(name.into());
+        self
+    }
+
+    fn wins_over_file(&self, name: &str) -> bool {
+        self.environment_wins.contains(name)
+    }
+}
+
+/// Identifies the file and line of the statement that produced a variable's current value, as
+/// tracked per variable name by [`MakeConf::origins`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigOrigin {
+    pub path: PathBuf,
+    pub line: u32,
+}
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct MakeConf {
     sources: Vec<PathBuf>,
     values: HashMap<String, RVal>,
+    origins: HashMap<String, ConfigOrigin>,
 }
 
 impl MakeConf {
@@ -112,6 +352,7 @@ impl MakeConf {
         Self {
             sources,
             values: rvalues,
+            origins: HashMap::new(),
         }
     }
 
@@ -124,17 +365,35 @@ impl MakeConf {
         let mut conf = Self {
             sources: Vec::new(),
             values: HashMap::new(),
+            origins: HashMap::new(),
         };
-        conf.load_file(path, base_dir, allow_source, allow_missing)?;
+        conf.load_file(path, base_dir, allow_source, allow_missing, &mut Vec::new())?;
         Ok(conf)
     }
 
+    /// Returns, for every variable whose value is currently known, the file and line number of
+    /// the statement that last assigned it -- e.g. to let a debugging tool print "USE flag came
+    /// from /etc/portage/make.conf.user:12".
+    ///
+    /// Note this isn't threaded into [`ConfigNodeValue::Vars`] (which only carries the final
+    /// flattened values, not provenance), so a caller that needs this alongside the rest of a
+    /// [`super::ConfigBundle`] must go through [`MakeConf`] directly rather than the
+    /// [`ConfigSource`] trait.
+    pub fn origins(&self) -> HashMap<String, ConfigOrigin> {
+        self.origins.clone()
+    }
+
+    /// Loads `path`, following any `source` directives it contains. `include_stack` carries the
+    /// canonicalized paths of every file currently being loaded (i.e. `path`'s ancestors via
+    /// `source`), so that a file that (transitively) sources itself is reported as an actionable
+    /// error instead of recursing until the stack overflows.
     fn load_file(
         &mut self,
         path: &Path,
         base_dir: &Path,
         allow_source: bool,
         allow_missing: bool,
+        include_stack: &mut Vec<PathBuf>,
     ) -> Result<()> {
         let source = base_dir.join(path);
         let context = || format!("Failed to load {}", source.display());
@@ -142,6 +401,47 @@ impl MakeConf {
         if allow_missing && !source.exists() {
             return Ok(());
         }
+
+        let canonical = source.canonicalize().with_context(context)?;
+        if let Some(pos) = include_stack.iter().position(|p| *p == canonical) {
+            let mut chain = include_stack[pos..]
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            chain.push(canonical.display().to_string());
+            bail!("Include cycle detected: {}", chain.join(" -> "));
+        }
+        if include_stack.len() >= MAX_INCLUDE_DEPTH {
+            bail!(
+                "Exceeded maximum include depth ({MAX_INCLUDE_DEPTH}) while loading {}",
+                source.display()
+            );
+        }
+
+        include_stack.push(canonical);
+        let result = self.load_file_contents(
+            &source,
+            path,
+            base_dir,
+            allow_source,
+            allow_missing,
+            include_stack,
+        );
+        include_stack.pop();
+        result
+    }
+
+    fn load_file_contents(
+        &mut self,
+        source: &Path,
+        path: &Path,
+        base_dir: &Path,
+        allow_source: bool,
+        allow_missing: bool,
+        include_stack: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let context = || format!("Failed to load {}", source.display());
+
         if source.is_dir() {
             let mut names = Vec::new();
             for entry in source.read_dir()? {
@@ -151,75 +451,92 @@ impl MakeConf {
 
             for name in names {
                 let new_path = path.join(name);
-                self.load_file(&new_path, base_dir, allow_source, allow_missing)
+                self.load_file(&new_path, base_dir, allow_source, allow_missing, include_stack)
                     .with_context(context)?;
             }
             return Ok(());
         }
 
-        let content = read_to_string(&source).with_context(context)?;
-        let span = parser::Span::new_extra(&content, &source);
+        let content = read_to_string(source).with_context(context)?;
+        let span = parser::Span::new_extra(&content, source);
         let statements = parser::full_parse(span, allow_source).with_context(context)?;
 
-        // Resolves [parser::RVal] into [RVal].
-        let evaluate_parser_rval = |values: &HashMap<String, RVal>, rval: parser::RVal| {
-            let mut resolved_rval = RVal::new();
-            for value in rval.vals {
-                match value {
-                    parser::Value::Literal(s) => {
-                        let s = *s.fragment();
-                        resolved_rval.push(Value::Literal(s.to_owned()));
-                    }
-                    parser::Value::Expansion(name) => {
-                        let name = *name.fragment();
-                        match values.get(name) {
-                            None => {
-                                resolved_rval.push(Value::UnresolvedExpansion(name.to_owned()));
-                            }
-                            Some(expanded_rval) => {
-                                for value in expanded_rval.vals.iter() {
-                                    resolved_rval.push(value.clone());
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            resolved_rval
-        };
-
         for statement in statements {
             match statement {
                 parser::Statement::Assign(lval, rval) => {
-                    self.values.insert(
-                        (*lval.fragment()).to_owned(),
-                        evaluate_parser_rval(&self.values, rval),
+                    let name = (*lval.fragment()).to_owned();
+                    self.origins.insert(
+                        name.clone(),
+                        ConfigOrigin {
+                            path: source.to_owned(),
+                            line: lval.location_line(),
+                        },
                     );
+                    self.values
+                        .insert(name, resolve_rval(&self.values, rval));
                 }
                 parser::Statement::Source(rval) => {
-                    let rval = evaluate_parser_rval(&self.values, rval);
-                    let source_path = base_dir.join(
-                        rval.try_to_string_no_unresolved_expansion()
-                            .with_context(context)?,
-                    );
-                    self.load_file(&source_path, base_dir, allow_source, allow_missing)
+                    let rval = resolve_rval(&self.values, rval);
+                    let target = rval
+                        .try_to_string_no_unresolved_expansion()
+                        .with_context(context)?;
+
+                    if has_glob_metachars(&target) {
+                        let matches = expand_source_glob(&base_dir.join(&target));
+                        if matches.is_empty() && !allow_missing {
+                            bail!("{target} did not match any files");
+                        }
+                        for source_path in matches {
+                            self.load_file(
+                                &source_path,
+                                base_dir,
+                                allow_source,
+                                allow_missing,
+                                include_stack,
+                            )
+                            .with_context(context)?;
+                        }
+                    } else {
+                        self.load_file(
+                            &base_dir.join(&target),
+                            base_dir,
+                            allow_source,
+                            allow_missing,
+                            include_stack,
+                        )
                         .with_context(context)?;
+                    }
                 }
             }
         }
 
-        self.sources.push(source);
+        self.sources.push(source.to_owned());
 
         Ok(())
     }
 }
 
-impl ConfigSource for MakeConf {
-    fn evaluate_configs(&self, env: &mut Vars) -> Vec<ConfigNode> {
-        // Evaluate variables.
+impl MakeConf {
+    /// Like [`ConfigSource::evaluate_configs`], but variables named as environment-wins in
+    /// `overrides` keep whatever value `env` already held instead of being clobbered by this
+    /// file's assignment. This only has an effect for a variable that's already present in `env`
+    /// when this call starts; if it isn't, the file's assignment applies normally.
+    pub fn evaluate_configs_with_overrides(
+        &self,
+        env: &mut Vars,
+        overrides: &OverridePolicy,
+    ) -> Vec<ConfigNode> {
+        // Evaluate variables, preserving the pre-existing `env` value for anything whose name is
+        // environment-wins. Every other value is evaluated against `env` as passed in, so only its
+        // deferred `${VAR}` lookups see the override, not references inlined at load time.
         let mut vars = Vars::new();
         for (name, rval) in self.values.iter() {
-            vars.insert(name.to_owned(), rval.evaluate(env));
+            let value = if overrides.wins_over_file(name) {
+                env.get(name).cloned().unwrap_or_else(|| rval.evaluate(env))
+            } else {
+                rval.evaluate(env)
+            };
+            vars.insert(name.to_owned(), value);
         }
 
         // Update `env` with computed variables.
@@ -232,6 +549,12 @@ impl ConfigSource for MakeConf {
     }
 }
 
+impl ConfigSource for MakeConf {
+    fn evaluate_configs(&self, env: &mut Vars) -> Vec<ConfigNode> {
+        self.evaluate_configs_with_overrides(env, &OverridePolicy::default())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -317,7 +640,7 @@ LOL="${LOL} ${LOL} ${LOL} ${LOL} ${LOL}"
             HashMap::from_iter([(
                 "USE".to_owned(),
                 RVal::from_iter([
-                    Value::UnresolvedExpansion("USE".to_owned()),
+                    Value::EnvLookup("USE".to_owned()),
                     Value::Literal(" foo bar".to_owned()),
                 ])
             )]),
@@ -326,6 +649,40 @@ LOL="${LOL} ${LOL} ${LOL} ${LOL} ${LOL}"
         Ok(())
     }
 
+    #[test]
+    fn test_origins() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(
+            dir,
+            [(
+                "make.conf",
+                "USE=\"foo\"\nCHOST=\"x86_64-pc-linux-gnu\"\nUSE=\"${USE} bar\"\n",
+            )],
+        )?;
+        let conf = MakeConf::load(&PathBuf::from("make.conf"), dir, false, false)?;
+
+        let origins = conf.origins();
+        // USE was last (re-)assigned on line 3, not line 1 where it was first set.
+        assert_eq!(
+            origins.get("USE"),
+            Some(&ConfigOrigin {
+                path: dir.join("make.conf"),
+                line: 3,
+            })
+        );
+        assert_eq!(
+            origins.get("CHOST"),
+            Some(&ConfigOrigin {
+                path: dir.join("make.conf"),
+                line: 2,
+            })
+        );
+
+        Ok(())
+    }
+
     fn write_source_files(dir: &Path) -> Result<()> {
         write_files(
             dir,
@@ -370,7 +727,7 @@ LOL="${LOL} ${LOL} ${LOL} ${LOL} ${LOL}"
             HashMap::from_iter([(
                 "USE".to_owned(),
                 RVal::from_iter([
-                    Value::UnresolvedExpansion("USE".to_owned()),
+                    Value::EnvLookup("USE".to_owned()),
                     Value::Literal(" a b c".to_owned()),
                 ])
             )]),
@@ -402,7 +759,7 @@ LOL="${LOL} ${LOL} ${LOL} ${LOL} ${LOL}"
             HashMap::from_iter([(
                 "USE".to_owned(),
                 RVal::from_iter([
-                    Value::UnresolvedExpansion("USE".to_owned()),
+                    Value::EnvLookup("USE".to_owned()),
                     Value::Literal(" a x b x c".to_owned()),
                 ])
             )]),
@@ -430,6 +787,118 @@ LOL="${LOL} ${LOL} ${LOL} ${LOL} ${LOL}"
         Ok(())
     }
 
+    #[test]
+    fn test_evaluate_configs_with_overrides() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(
+            dir,
+            [(
+                "make.conf",
+                r#"
+                    USE="${USE} foo"
+                    CHOST="x86_64-pc-linux-gnu"
+                "#,
+            )],
+        )?;
+        let conf = MakeConf::load(&PathBuf::from("make.conf"), dir, false, false)?;
+
+        let mut env = Vars::from_iter([("USE".to_owned(), "forced".to_owned())]);
+        let overrides = OverridePolicy::new().environment_wins("USE");
+        let nodes = conf.evaluate_configs_with_overrides(&mut env, &overrides);
+
+        // USE is environment-wins and was already set, so it's untouched...
+        assert_eq!(env.get("USE").map(String::as_str), Some("forced"));
+        // ...but CHOST, which isn't listed in the override policy, is still set from the file.
+        assert_eq!(
+            env.get("CHOST").map(String::as_str),
+            Some("x86_64-pc-linux-gnu")
+        );
+
+        let ConfigNodeValue::Vars(vars) = &nodes[0].value else {
+            panic!("expected a Vars node");
+        };
+        assert_eq!(vars.get("USE").map(String::as_str), Some("forced"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_param_expansion_evaluation() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(
+            dir,
+            [(
+                "make.conf",
+                r#"
+                    SET_VAR="hello"
+                    DEFAULT_UNSET="${UNSET_VAR:-fallback}"
+                    DEFAULT_SET="${SET_VAR:-fallback}"
+                    ALT_UNSET="${UNSET_VAR:+shown}"
+                    ALT_SET="${SET_VAR:+shown}"
+                    STRIPPED_PREFIX="${SET_VAR#hel}"
+                    STRIPPED_SUFFIX="${SET_VAR%llo}"
+                "#,
+            )],
+        )?;
+        let conf = MakeConf::load(&PathBuf::from("make.conf"), dir, false, false)?;
+
+        let mut env = Vars::new();
+        let nodes = conf.evaluate_configs(&mut env);
+        let ConfigNodeValue::Vars(vars) = &nodes[0].value else {
+            panic!("expected a Vars node");
+        };
+
+        assert_eq!(vars.get("DEFAULT_UNSET").map(String::as_str), Some("fallback"));
+        assert_eq!(vars.get("DEFAULT_SET").map(String::as_str), Some("hello"));
+        assert_eq!(vars.get("ALT_UNSET").map(String::as_str), Some(""));
+        assert_eq!(vars.get("ALT_SET").map(String::as_str), Some("shown"));
+        assert_eq!(vars.get("STRIPPED_PREFIX").map(String::as_str), Some("lo"));
+        assert_eq!(vars.get("STRIPPED_SUFFIX").map(String::as_str), Some("he"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_target_with_resolved_param_expansion() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(
+            dir,
+            [
+                (
+                    "make.conf",
+                    r#"
+                        PORTAGE_CONFIGROOT="make.conf.d/"
+                        source "${PORTAGE_CONFIGROOT:-fallback/}extra.conf"
+                    "#,
+                ),
+                ("make.conf.d/extra.conf", r#"USE="extra""#),
+            ],
+        )?;
+
+        let conf = MakeConf::load(&PathBuf::from("make.conf"), dir, true, false)?;
+
+        assert_eq!(
+            HashMap::from_iter([
+                (
+                    "PORTAGE_CONFIGROOT".to_owned(),
+                    RVal::from_iter([Value::Literal("make.conf.d/".to_owned())])
+                ),
+                (
+                    "USE".to_owned(),
+                    RVal::from_iter([Value::Literal("extra".to_owned())])
+                ),
+            ]),
+            conf.values
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_allow_missing_enabled() -> Result<()> {
         let dir = tempfile::tempdir()?;
@@ -438,4 +907,106 @@ LOL="${LOL} ${LOL} ${LOL} ${LOL} ${LOL}"
         MakeConf::load(&PathBuf::from("make.conf"), dir, false, true)?;
         Ok(())
     }
+
+    #[test]
+    fn test_source_cycle_detected() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(dir, [("make.conf", "source make.conf\n")])?;
+
+        let err = MakeConf::load(&PathBuf::from("make.conf"), dir, true, false)
+            .expect_err("a file that sources itself should be rejected");
+        assert!(
+            err.chain().any(|c| c.to_string().contains("Include cycle detected")),
+            "unexpected error: {err:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_cycle_detected_transitively() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(
+            dir,
+            [("a.conf", "source b.conf\n"), ("b.conf", "source a.conf\n")],
+        )?;
+
+        let err = MakeConf::load(&PathBuf::from("a.conf"), dir, true, false)
+            .expect_err("a transitive source cycle should be rejected");
+        assert!(
+            err.chain().any(|c| c.to_string().contains("Include cycle detected")),
+            "unexpected error: {err:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_include_depth_exceeded() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        let depth = MAX_INCLUDE_DEPTH + 1;
+        let files = (0..depth).map(|i| (format!("{i}.conf"), format!("source {}.conf\n", i + 1)));
+        write_files(dir, files)?;
+        write_files(dir, [(format!("{depth}.conf"), String::new())])?;
+
+        let err = MakeConf::load(&PathBuf::from("0.conf"), dir, true, false)
+            .expect_err("an include chain deeper than MAX_INCLUDE_DEPTH should be rejected");
+        assert!(
+            err.chain()
+                .any(|c| c.to_string().contains("Exceeded maximum include depth")),
+            "unexpected error: {err:?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_glob() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(
+            dir,
+            [
+                ("make.conf", "source make.conf.d/*.conf\n"),
+                ("make.conf.d/a.conf", "USE=\"$USE a\""),
+                ("make.conf.d/b.conf", "USE=\"$USE b\""),
+                ("make.conf.d/c.conf", "USE=\"$USE c\""),
+                // Shouldn't match the "*.conf" glob.
+                ("make.conf.d/readme.txt", "USE=\"$USE nope\""),
+            ],
+        )?;
+
+        let conf = MakeConf::load(&PathBuf::from("make.conf"), dir, true, false)?;
+
+        assert_eq!(
+            HashMap::from_iter([(
+                "USE".to_owned(),
+                RVal::from_iter([
+                    Value::EnvLookup("USE".to_owned()),
+                    Value::Literal(" a b c".to_owned()),
+                ])
+            )]),
+            conf.values
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_glob_no_match() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let dir = dir.as_ref();
+
+        write_files(dir, [("make.conf", "source make.conf.d/*.conf\n")])?;
+
+        MakeConf::load(&PathBuf::from("make.conf"), dir, true, false)
+            .expect_err("a glob matching nothing should fail when allow_missing is false");
+
+        let conf = MakeConf::load(&PathBuf::from("make.conf"), dir, true, true)?;
+        assert!(conf.values.is_empty());
+        Ok(())
+    }
 }
@@ -23,15 +23,42 @@ use nom_locate::LocatedSpan;
 
 pub type Span<'a> = LocatedSpan<&'a str, &'a Path>;
 
-/// An enum corresponding to the values that can be assigned to a variable. The two variants
-/// correspond to either a literal string or an in-place variable expansion (e.g. "${FOO}").
-/// A variable expansion can then recursively contain literal strings and more variable expansions.
+/// The operator half of a `${VAR<op>}` bash-style parameter expansion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamOp<'a> {
+    /// `${VAR:-word}`.
+    Default(RVal<'a>),
+    /// `${VAR:+word}`.
+    Alternate(RVal<'a>),
+    /// `${VAR#pattern}`.
+    StripPrefix(Span<'a>),
+    /// `${VAR%pattern}`.
+    StripSuffix(Span<'a>),
+}
+
+impl fmt::Display for ParamOp<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParamOp::Default(word) => write!(f, ":-{}", word),
+            ParamOp::Alternate(word) => write!(f, ":+{}", word),
+            ParamOp::StripPrefix(pattern) => write!(f, "#{}", pattern),
+            ParamOp::StripSuffix(pattern) => write!(f, "%{}", pattern),
+        }
+    }
+}
+
+/// An enum corresponding to the values that can be assigned to a variable. The variants
+/// correspond to a literal string, a bare in-place variable expansion (e.g. "${FOO}"), or a
+/// bash-style parameter expansion with an operator (e.g. "${FOO:-bar}"). A variable expansion can
+/// then recursively contain literal strings and more variable expansions.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Value<'a> {
     /// A verbatim section of text, e.g. "foo".
     Literal(Span<'a>),
     /// A variable expansion site, e.g. `${MY_VAR}`.
     Expansion(Span<'a>),
+    /// A parameter expansion with an operator, e.g. `${MY_VAR:-default}`.
+    ParamExpansion(Span<'a>, ParamOp<'a>),
 }
 
 impl fmt::Display for Value<'_> {
@@ -39,6 +66,7 @@ impl fmt::Display for Value<'_> {
         match self {
             Value::Literal(s) => write!(f, "{}", s),
             Value::Expansion(name) => write!(f, "${{{}}}", name),
+            Value::ParamExpansion(name, op) => write!(f, "${{{}{}}}", name, op),
         }
     }
 }
@@ -161,7 +189,12 @@ fn double_quoted_rval(input: Span) -> IResult<Span, RVal> {
     map(
         delimited(
             tag("\""),
-            many0(alt((double_quoted_literal, escaped_char, expansion))),
+            many0(alt((
+                double_quoted_literal,
+                escaped_char,
+                param_expansion,
+                expansion,
+            ))),
             tag("\""),
         ),
         |vals| RVal { vals },
@@ -187,7 +220,10 @@ fn unquoted_rval(input: Span) -> IResult<Span, RVal> {
     let unquoted_literal = map(take_while1(not_ws), Value::Literal);
 
     map(
-        preceded(multispace0, many0(alt((expansion, unquoted_literal)))),
+        preceded(
+            multispace0,
+            many0(alt((param_expansion, expansion, unquoted_literal))),
+        ),
         RVal::new,
     )(input)
 }
@@ -223,6 +259,38 @@ fn expansion(input: Span<'_>) -> IResult<Span<'_>, Value<'_>> {
     )(input)
 }
 
+/// Parser to recognize literal text within a parameter-expansion `word`, up to the next `$` or
+/// the closing `}`.
+fn word_literal(input: Span<'_>) -> IResult<Span<'_>, Value<'_>> {
+    map(is_not("$}"), Value::Literal)(input)
+}
+
+/// Parser to recognize the `word` portion of `${VAR:-word}`/`${VAR:+word}`, which may itself
+/// contain literal text and nested expansions.
+fn param_word(input: Span<'_>) -> IResult<Span<'_>, RVal<'_>> {
+    map(
+        many0(alt((param_expansion, expansion, word_literal))),
+        RVal::new,
+    )(input)
+}
+
+/// Parser to recognize a bash-style parameter expansion with an operator: `${VAR:-word}`,
+/// `${VAR:+word}`, `${VAR#pattern}`, or `${VAR%pattern}`. Only a literal `pattern` is supported
+/// for the `#`/`%` strip forms (no glob matching).
+fn param_expansion(input: Span<'_>) -> IResult<Span<'_>, Value<'_>> {
+    let op = alt((
+        map(preceded(tag(":-"), param_word), ParamOp::Default),
+        map(preceded(tag(":+"), param_word), ParamOp::Alternate),
+        map(preceded(tag("#"), is_not("}")), ParamOp::StripPrefix),
+        map(preceded(tag("%"), is_not("}")), ParamOp::StripSuffix),
+    ));
+
+    map(
+        delimited(tag("${"), pair(variable, op), tag("}")),
+        |(name, op)| Value::ParamExpansion(name, op),
+    )(input)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -468,4 +536,74 @@ USE="${USE} bar"
             file
         );
     }
+
+    const PARAM_EXPANSION_DEFAULT: &str = r#"FOO="${BAR:-baz}""#;
+    #[test]
+    fn test_param_expansion_default() {
+        let span = null_span(PARAM_EXPANSION_DEFAULT);
+        let res = full_parse(span, false);
+        let file = res.unwrap();
+        assert_eq!(
+            vec![Statement::Assign(
+                span.slice(0usize..3usize),
+                RVal {
+                    vals: vec![Value::ParamExpansion(
+                        span.slice(7usize..10usize),
+                        ParamOp::Default(RVal {
+                            vals: vec![Value::Literal(span.slice(12usize..15usize))]
+                        })
+                    )]
+                },
+            )],
+            file
+        );
+    }
+
+    const PARAM_EXPANSION_ALTERNATE: &str = r#"FOO="${BAR:+baz}""#;
+    #[test]
+    fn test_param_expansion_alternate() {
+        let span = null_span(PARAM_EXPANSION_ALTERNATE);
+        let res = full_parse(span, false);
+        let file = res.unwrap();
+        assert_eq!(
+            vec![Statement::Assign(
+                span.slice(0usize..3usize),
+                RVal {
+                    vals: vec![Value::ParamExpansion(
+                        span.slice(7usize..10usize),
+                        ParamOp::Alternate(RVal {
+                            vals: vec![Value::Literal(span.slice(12usize..15usize))]
+                        })
+                    )]
+                },
+            )],
+            file
+        );
+    }
+
+    const PARAM_EXPANSION_STRIP: &str = r#"FOO="${BAR#pre}${BAR%suf}""#;
+    #[test]
+    fn test_param_expansion_strip() {
+        let span = null_span(PARAM_EXPANSION_STRIP);
+        let res = full_parse(span, false);
+        let file = res.unwrap();
+        assert_eq!(
+            vec![Statement::Assign(
+                span.slice(0usize..3usize),
+                RVal {
+                    vals: vec![
+                        Value::ParamExpansion(
+                            span.slice(7usize..10usize),
+                            ParamOp::StripPrefix(span.slice(11usize..14usize))
+                        ),
+                        Value::ParamExpansion(
+                            span.slice(17usize..20usize),
+                            ParamOp::StripSuffix(span.slice(21usize..24usize))
+                        ),
+                    ]
+                },
+            )],
+            file
+        );
+    }
 }
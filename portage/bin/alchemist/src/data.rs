@@ -3,6 +3,7 @@
 // found in the LICENSE file.
 
 use std::{
+    cell::RefCell,
     collections::HashMap,
     fmt::Display,
     ops::{Deref, DerefMut},
@@ -91,3 +92,53 @@ impl<S: Display> Display for Slot<S> {
         write!(f, "{}/{}", &self.main, &self.sub)
     }
 }
+
+/// A cheap, `Copy` handle to a string interned by an [`Interner`]. Two [`Symbol`]s compare equal
+/// iff the strings they were interned from are equal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct Symbol(u32);
+
+/// Interns USE flag, keyword, and incremental-variable-name strings into [`Symbol`]s.
+///
+/// Evaluating a full board's packages re-merges the same handful of USE flag and keyword strings
+/// tens of thousands of times; interning them lets that hot path hash/compare a `u32` instead of
+/// re-hashing and re-allocating the same strings over and over.
+///
+/// Interning takes `&self` (backed by a [`RefCell`]) so callers like
+/// [`crate::config::bundle::ConfigBundle`]'s compute methods can intern without needing `&mut
+/// self`.
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    inner: RefCell<InternerState>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct InternerState {
+    strings: Vec<Box<str>>,
+    symbols: HashMap<Box<str>, Symbol>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Interns `s`, returning its (possibly newly assigned) [`Symbol`].
+    pub fn intern(&self, s: &str) -> Symbol {
+        let mut state = self.inner.borrow_mut();
+        if let Some(symbol) = state.symbols.get(s) {
+            return *symbol;
+        }
+        let symbol = Symbol(state.strings.len() as u32);
+        state.strings.push(s.into());
+        state.symbols.insert(s.into(), symbol);
+        symbol
+    }
+
+    /// Resolves a [`Symbol`] back to the string it was interned from.
+    ///
+    /// Panics if `symbol` wasn't returned by this same [`Interner`].
+    pub fn resolve(&self, symbol: Symbol) -> String {
+        self.inner.borrow().strings[symbol.0 as usize].to_string()
+    }
+}
@@ -3,13 +3,16 @@
 // found in the LICENSE file.
 
 pub mod algorithm;
+mod parse_error;
 pub mod package;
 mod parser;
 pub mod requse;
 pub mod restrict;
 pub mod uri;
 
-use std::{convert::Infallible, fmt::Display, str::FromStr};
+pub use parse_error::{DependencyParseError, Location};
+
+use std::{collections::BTreeSet, convert::Infallible, fmt::Display, str::FromStr};
 
 use anyhow::Result;
 use itertools::Itertools;
@@ -126,6 +129,99 @@ impl<M: DependencyMeta> Dependency<M> {
 }
 
 impl<M: DependencyMeta> Dependency<M> {
+    /// Evaluates `UseConditional` nodes against a concrete, fully-known set of enabled USE flags,
+    /// the way cargo-platform matches a `cfg(...)` predicate against a set of active cfgs.
+    ///
+    /// A `UseConditional { name, expect, children }` node is replaced by its own (recursively
+    /// evaluated) `children` when `enabled.contains(name) == expect`, and dropped otherwise, same
+    /// as [`ThreeValuedPredicate::matches`] treats an unsatisfied condition as a dependency that
+    /// "does not exist". Nested `AllOf`s collapse into a single flat conjunction. `AnyOf` nodes
+    /// are left intact as alternative groups (only their children are evaluated in turn) since
+    /// choosing among them needs a real resolver, not just USE evaluation: in particular, an
+    /// `AnyOf` every one of whose alternatives evaluates away to nothing is left as an empty `||
+    /// ( )`, which (per [`Self::check_constant`]) reads as unsatisfiable, matching PMS semantics
+    /// for a dropped any-of alternative rather than a vacuous one.
+    pub fn evaluate_use(&self, enabled: &BTreeSet<String>) -> Self {
+        Self::new_composite(CompositeDependency::AllOf {
+            children: self.evaluate_use_as_all_of_term(enabled),
+        })
+    }
+
+    /// Evaluates `self` as one conjunct of an enclosing (or the top-level) all-of, returning the
+    /// flattened list of terms it contributes: zero for a dropped/trivially-true node, one or
+    /// more for everything else (more than one only when `self` is itself an `AllOf`).
+    fn evaluate_use_as_all_of_term(&self, enabled: &BTreeSet<String>) -> Vec<Self> {
+        match self {
+            Self::Leaf(_) => vec![self.clone()],
+            Self::Composite(composite) => match &**composite {
+                CompositeDependency::AllOf { children } => children
+                    .iter()
+                    .flat_map(|child| child.evaluate_use_as_all_of_term(enabled))
+                    .collect(),
+                CompositeDependency::AnyOf { children } => {
+                    let children = children
+                        .iter()
+                        .filter_map(|child| match child {
+                            // A use-conditional directly gating an any-of alternative "does not
+                            // exist" when unsatisfied, same as `ThreeValuedPredicate::matches`
+                            // returns `None` (not "trivially true") for this exact shape, so it's
+                            // dropped from the alternative list instead of being left behind as a
+                            // vacuously-true one. This differs from the same use-conditional
+                            // nested one level deeper inside an explicit `AllOf` alternative,
+                            // where a dropped condition legitimately leaves that alternative
+                            // vacuously true: that asymmetry comes from the PMS semantics this
+                            // mirrors, not something introduced here.
+                            Self::Composite(inner) => match &**inner {
+                                CompositeDependency::UseConditional {
+                                    name,
+                                    expect,
+                                    children: cond_children,
+                                } => {
+                                    if enabled.contains(name) == *expect {
+                                        Some(Self::new_composite(CompositeDependency::AllOf {
+                                            children: cond_children
+                                                .iter()
+                                                .flat_map(|c| {
+                                                    c.evaluate_use_as_all_of_term(enabled)
+                                                })
+                                                .collect(),
+                                        }))
+                                    } else {
+                                        None
+                                    }
+                                }
+                                _ => Some(child.evaluate_use(enabled)),
+                            },
+                            Self::Leaf(_) => Some(child.evaluate_use(enabled)),
+                        })
+                        .collect();
+                    vec![Self::new_composite(CompositeDependency::AnyOf { children })]
+                }
+                CompositeDependency::UseConditional {
+                    name,
+                    expect,
+                    children,
+                } => {
+                    if enabled.contains(name) == *expect {
+                        children
+                            .iter()
+                            .flat_map(|child| child.evaluate_use_as_all_of_term(enabled))
+                            .collect()
+                    } else {
+                        vec![]
+                    }
+                }
+                CompositeDependency::Constant { value, .. } => {
+                    if *value {
+                        vec![]
+                    } else {
+                        vec![self.clone()]
+                    }
+                }
+            },
+        }
+    }
+
     pub fn map_tree(self, mut f: impl FnMut(Self) -> Self) -> Self {
         self.try_map_tree(move |d| Result::<Self, Infallible>::Ok(f(d)))
             .unwrap()
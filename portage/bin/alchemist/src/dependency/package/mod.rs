@@ -181,6 +181,43 @@ pub struct PackageUseDependency {
 }
 
 impl PackageUseDependency {
+    /// The USE flag this constraint is about.
+    pub fn flag(&self) -> &str {
+        &self.flag
+    }
+
+    /// Returns the single boolean value this constraint pins the target's flag to, given the
+    /// evaluating package's own `source_use_map`, or `None` if the constraint doesn't pin the
+    /// flag to one value (e.g. an unsatisfied `?` conditional, which leaves the target free).
+    ///
+    /// This mirrors the logic in [`Self::matches`], but solves it for the target value that
+    /// satisfies the constraint instead of checking an existing one. Used to compute which
+    /// USE-flag variant of a dependency a dependent is asking for.
+    pub fn requested_value(&self, source_use_map: &UseMap) -> Result<Option<bool>> {
+        let value = match self.op {
+            PackageUseDependencyOp::Required => Some(!self.negate),
+            PackageUseDependencyOp::Synchronized => {
+                let source_value = source_use_map
+                    .get(&self.flag)
+                    .copied()
+                    .with_context(|| format!("Missing source USE flag '{}'", self.flag))?;
+                Some(source_value ^ self.negate)
+            }
+            PackageUseDependencyOp::ConditionalRequired => {
+                let source_value = source_use_map
+                    .get(&self.flag)
+                    .copied()
+                    .with_context(|| format!("Missing source USE flag '{}'", self.flag))?;
+                match (source_value, self.negate) {
+                    (true, false) => Some(true),
+                    (false, true) => Some(false),
+                    _ => None,
+                }
+            }
+        };
+        Ok(value)
+    }
+
     fn matches(&self, source_use_map: &UseMap, target_use_map: &UseMap) -> Result<bool> {
         let target_value = target_use_map
             .get(&self.flag)
@@ -626,7 +663,10 @@ impl PackageAtom {
 
 #[cfg(test)]
 mod tests {
-    use std::{collections::HashMap, str::FromStr};
+    use std::{
+        collections::{BTreeSet, HashMap},
+        str::FromStr,
+    };
 
     use anyhow::{anyhow, Result};
 
@@ -677,6 +717,61 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_evaluate_use() -> Result<()> {
+        let empty_use_map = UseMap::new();
+        let default_version = Version::try_new("1.0").unwrap();
+        let package_set = PackageRefSet::from_iter([
+            PackageRef {
+                package_name: "pkg/aaa",
+                version: &default_version,
+                slot: Some(Slot::new("0")),
+                use_map: Some(&empty_use_map),
+            },
+            PackageRef {
+                package_name: "pkg/bbb",
+                version: &default_version,
+                slot: Some(Slot::new("0")),
+                use_map: Some(&empty_use_map),
+            },
+        ]);
+
+        // evaluate_use() only resolves away USE-conditionals; it must not change what the
+        // expression means. So for every (raw expression, enabled-flags) pair below, check that
+        // matching the evaluated tree against an empty USE map agrees with matching the original
+        // tree against a USE map that actually has those flags set.
+        let test_cases: &[(&str, &[&str])] = &[
+            ("pkg/aaa", &[]),
+            ("foo? ( pkg/aaa )", &[]),
+            ("foo? ( pkg/aaa )", &["foo"]),
+            ("!foo? ( pkg/aaa )", &["foo"]),
+            ("!foo? ( pkg/aaa )", &[]),
+            ("foo? ( pkg/aaa pkg/bbb )", &["foo"]),
+            ("foo? ( pkg/aaa !pkg/bbb )", &["foo"]),
+            ("|| ( foo? ( pkg/aaa ) pkg/bbb )", &[]),
+            ("|| ( foo? ( !pkg/aaa ) !pkg/bbb )", &[]),
+            ("|| ( foo? ( !pkg/aaa ) foo? ( !pkg/bbb ) )", &[]),
+        ];
+
+        for (raw_deps, enabled) in test_cases {
+            let enabled: BTreeSet<String> = enabled.iter().map(|s| s.to_string()).collect();
+            let use_map: UseMap = enabled.iter().map(|flag| (flag.clone(), true)).collect();
+
+            let deps = PackageDependency::from_str(raw_deps)?;
+            let original = deps.matches(&use_map, &package_set)?;
+            let evaluated = deps
+                .evaluate_use(&enabled)
+                .matches(&empty_use_map, &package_set)?;
+            assert_eq!(
+                evaluated, original,
+                "evaluate_use({:?}, {:?})",
+                raw_deps, enabled
+            );
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_package_atom() -> Result<()> {
         let test_cases = HashMap::from([
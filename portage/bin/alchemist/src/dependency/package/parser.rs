@@ -10,7 +10,6 @@ use nom::{
     combinator::{eof, map, opt, recognize, value},
     multi::separated_list1,
     sequence::{delimited, pair},
-    IResult,
 };
 use nom_regex::str::re_find;
 use once_cell::sync::Lazy;
@@ -22,7 +21,10 @@ use crate::dependency::{
         PackageBlock, PackageDependency, PackageDependencyAtom, PackageSlotDependency,
         PackageUseDependency, PackageVersionDependency, PackageVersionOp,
     },
-    parser::{DependencyParser, DependencyParserCommon},
+    parser::{
+        parse_composite, parse_expression_list, parse_use_name, to_dependency_parse_error,
+        DependencyParser, PResult, PartialExpressionParser,
+    },
     CompositeDependency, Dependency,
 };
 
@@ -49,24 +51,20 @@ static PACKAGE_NAME_WITH_VERSION_RE: Lazy<Regex> = Lazy::new(|| {
 /// Implements the package dependency expression parser.
 pub struct PackageDependencyParser {}
 
-impl<'i> DependencyParserCommon<'i, PackageDependencyAtom> for PackageDependencyParser {
-    fn new_all_of(children: Vec<PackageDependency>) -> PackageDependency {
-        Dependency::new_composite(CompositeDependency::AllOf { children })
-    }
+impl PartialExpressionParser for PackageDependencyParser {
+    type Output = PackageDependency;
 
-    fn expression(input: &str) -> IResult<&str, PackageDependency> {
+    fn parse_expression(input: &str) -> PResult<Self::Output> {
         let (input, _) = multispace0(input)?;
         alt((
             map(Self::atom, Dependency::Leaf),
-            Self::all_of,
-            Self::any_of,
-            Self::use_conditional,
+            map(parse_composite::<Self>, Dependency::new_composite),
         ))(input)
     }
 }
 
 impl PackageDependencyParser {
-    fn block(input: &str) -> IResult<&str, PackageBlock> {
+    fn block(input: &str) -> PResult<PackageBlock> {
         alt((
             value(PackageBlock::Strong, tag(PackageBlock::Strong.as_ref())),
             value(PackageBlock::Weak, tag(PackageBlock::Weak.as_ref())),
@@ -74,11 +72,11 @@ impl PackageDependencyParser {
         ))(input)
     }
 
-    fn package_name_plain(input: &str) -> IResult<&str, &str> {
+    fn package_name_plain(input: &str) -> PResult<&str> {
         re_find(PACKAGE_NAME_PLAIN_RE.clone())(input)
     }
 
-    fn package_name_with_version(input: &str) -> IResult<&str, (&str, PackageVersionDependency)> {
+    fn package_name_with_version(input: &str) -> PResult<(&str, PackageVersionDependency)> {
         let (input, op) = alt((
             value(
                 PackageVersionOp::Equal { wildcard: false },
@@ -121,7 +119,7 @@ impl PackageDependencyParser {
         ))
     }
 
-    fn slot_name_unit(input: &str) -> IResult<&str, &str> {
+    fn slot_name_unit(input: &str) -> PResult<&str> {
         recognize(pair(
             take_while1(|c| is_alphanumeric(c as u8) || c == '_'),
             take_while(|c| {
@@ -130,14 +128,14 @@ impl PackageDependencyParser {
         ))(input)
     }
 
-    fn slot_name(input: &str) -> IResult<&str, &str> {
+    fn slot_name(input: &str) -> PResult<&str> {
         recognize(pair(
             Self::slot_name_unit,
             opt(pair(tag("/"), Self::slot_name_unit)),
         ))(input)
     }
 
-    fn slot_specific(input: &str) -> IResult<&str, PackageSlotDependency> {
+    fn slot_specific(input: &str) -> PResult<PackageSlotDependency> {
         let (input, (spec, opt_mark)) = pair(Self::slot_name, opt(tag("=")))(input)?;
         let (main, sub) = spec
             .split_once('/')
@@ -149,17 +147,17 @@ impl PackageDependencyParser {
         ))
     }
 
-    fn slot_wildcard(input: &str) -> IResult<&str, PackageSlotDependency> {
+    fn slot_wildcard(input: &str) -> PResult<PackageSlotDependency> {
         let (input, mark) = alt((tag("*"), tag("=")))(input)?;
         Ok((input, PackageSlotDependency::new(None, mark == "=")))
     }
 
-    fn slot(input: &str) -> IResult<&str, PackageSlotDependency> {
+    fn slot(input: &str) -> PResult<PackageSlotDependency> {
         let (input, _) = tag(":")(input)?;
         alt((Self::slot_specific, Self::slot_wildcard))(input)
     }
 
-    fn use_item_default(input: &str) -> IResult<&str, bool> {
+    fn use_item_default(input: &str) -> PResult<bool> {
         delimited(
             tag("("),
             alt((value(true, tag("+")), value(false, tag("-")))),
@@ -167,11 +165,11 @@ impl PackageDependencyParser {
         )(input)
     }
 
-    fn use_item(input: &str) -> IResult<&str, PackageUseDependency> {
+    fn use_item(input: &str) -> PResult<PackageUseDependency> {
         let (input, negate) = opt(tag("-"))(input)?;
         if negate.is_some() {
             let (input, (flag, missing_default)) =
-                pair(Self::use_name, opt(Self::use_item_default))(input)?;
+                pair(parse_use_name, opt(Self::use_item_default))(input)?;
 
             return Ok((
                 input,
@@ -187,7 +185,7 @@ impl PackageDependencyParser {
         let (input, not_op) = opt(tag("!"))(input)?;
         if not_op.is_some() {
             let (input, (flag, missing_default)) =
-                pair(Self::use_name, opt(Self::use_item_default))(input)?;
+                pair(parse_use_name, opt(Self::use_item_default))(input)?;
 
             let (input, op) = alt((
                 value(PackageUseDependencyOp::Synchronized, tag("=")),
@@ -206,7 +204,7 @@ impl PackageDependencyParser {
         }
 
         let (input, (flag, missing_default)) =
-            pair(Self::use_name, opt(Self::use_item_default))(input)?;
+            pair(parse_use_name, opt(Self::use_item_default))(input)?;
 
         let (input, op) = opt(alt((
             value(PackageUseDependencyOp::Synchronized, tag("=")),
@@ -226,7 +224,7 @@ impl PackageDependencyParser {
         ))
     }
 
-    fn uses(input: &str) -> IResult<&str, Vec<PackageUseDependency>> {
+    fn uses(input: &str) -> PResult<Vec<PackageUseDependency>> {
         delimited(
             tag("["),
             separated_list1(tag(","), Self::use_item),
@@ -234,7 +232,7 @@ impl PackageDependencyParser {
         )(input)
     }
 
-    fn atom(input: &str) -> IResult<&str, PackageDependencyAtom> {
+    fn atom(input: &str) -> PResult<PackageDependencyAtom> {
         let (input, block) = Self::block(input)?;
         let (input, (package_name, version)) = alt((
             map(Self::package_name_plain, |name| (name, None)),
@@ -256,30 +254,35 @@ impl PackageDependencyParser {
         ))
     }
 
-    fn full_atom(input: &str) -> IResult<&str, PackageDependencyAtom> {
+    fn full_atom(input: &str) -> PResult<PackageDependencyAtom> {
         let (input, atom) = Self::atom(input)?;
         let (input, _) = eof(input)?;
         Ok((input, atom))
     }
 
-    fn full(input: &str) -> IResult<&str, PackageDependency> {
-        let (input, children) = Self::expression_list(input)?;
+    fn full(input: &str) -> PResult<PackageDependency> {
+        let (input, children) = parse_expression_list::<Self>(input)?;
         let (input, _) = multispace0(input)?;
         let (input, _) = eof(input)?;
-        Ok((input, Self::new_all_of(children)))
+        Ok((
+            input,
+            Dependency::new_composite(CompositeDependency::AllOf { children }),
+        ))
     }
 
     pub fn parse_atom(input: &str) -> Result<PackageDependencyAtom> {
-        let (_, atom) = PackageDependencyParser::full_atom(input).map_err(|err| err.to_owned())?;
+        let (_, atom) = PackageDependencyParser::full_atom(input)
+            .map_err(|err| to_dependency_parse_error(input, err))?;
         Ok(atom)
     }
 }
 
-impl DependencyParser<PackageDependency> for PackageDependencyParser {
+impl DependencyParser for PackageDependencyParser {
+    type Output = PackageDependency;
     type Err = Error;
 
-    fn parse(input: &str) -> Result<PackageDependency> {
-        let (_, deps) = Self::full(input).map_err(|err| err.to_owned())?;
+    fn parse(input: &str) -> Result<Self::Output> {
+        let (_, deps) = Self::full(input).map_err(|err| to_dependency_parse_error(input, err))?;
         Ok(deps)
     }
 }
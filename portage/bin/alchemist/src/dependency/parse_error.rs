@@ -0,0 +1,132 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! A nom-compatible error type that remembers where in the input a
+//! dependency expression failed to parse, so [`DependencyParser::parse`]
+//! (see [`super::parser`]) can report a precise offset instead of just
+//! giving up silently.
+
+use std::fmt;
+
+/// A 1-based line/column position within a dependency expression string.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Nom error type threaded through the `PartialExpressionParser` combinators.
+///
+/// It keeps the remaining input at the deepest point of failure (from which
+/// the byte offset into the original string can be recovered), along with
+/// the first context message attached via [`nom::error::context`], e.g.
+/// "expected ')'".
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RawDependencyParseError<'i> {
+    pub(super) remaining: &'i str,
+    pub(super) message: Option<&'static str>,
+}
+
+impl<'i> nom::error::ParseError<&'i str> for RawDependencyParseError<'i> {
+    fn from_error_kind(input: &'i str, _kind: nom::error::ErrorKind) -> Self {
+        RawDependencyParseError {
+            remaining: input,
+            message: None,
+        }
+    }
+
+    fn append(_input: &'i str, _kind: nom::error::ErrorKind, other: Self) -> Self {
+        // Keep the deepest error instead of the outermost one.
+        other
+    }
+}
+
+impl<'i, E> nom::error::FromExternalError<&'i str, E> for RawDependencyParseError<'i> {
+    fn from_external_error(input: &'i str, _kind: nom::error::ErrorKind, _e: E) -> Self {
+        RawDependencyParseError {
+            remaining: input,
+            message: None,
+        }
+    }
+}
+
+impl<'i> nom::error::ContextError<&'i str> for RawDependencyParseError<'i> {
+    fn add_context(_input: &'i str, ctx: &'static str, other: Self) -> Self {
+        // The first (innermost) context to fire wins, since it's the one
+        // closest to the actual point of failure.
+        match other.message {
+            Some(_) => other,
+            None => RawDependencyParseError {
+                message: Some(ctx),
+                ..other
+            },
+        }
+    }
+}
+
+/// A dependency expression failed to parse at a specific, reported location.
+#[derive(Debug, Clone, Eq, PartialEq, thiserror::Error)]
+#[error("{message} at {location}:\n{snippet}")]
+pub struct DependencyParseError {
+    pub location: Location,
+    pub message: String,
+    /// The offending line, with a `^` caret under the failing column.
+    pub snippet: String,
+}
+
+impl DependencyParseError {
+    /// Builds a [`DependencyParseError`] from the raw nom error and the
+    /// original, complete input string it was parsing.
+    pub(super) fn new(original: &str, raw: RawDependencyParseError) -> Self {
+        // `raw.remaining` is always a suffix of `original`, since every
+        // combinator in this module narrows the input without copying it.
+        let offset = original.len() - raw.remaining.len();
+        let before = &original[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let column = offset - line_start + 1;
+
+        let line_end = original[offset..]
+            .find('\n')
+            .map(|i| offset + i)
+            .unwrap_or(original.len());
+        let line_text = &original[line_start..line_end];
+        let snippet = format!("{line_text}\n{}^", " ".repeat(column.saturating_sub(1)));
+
+        DependencyParseError {
+            location: Location { line, column },
+            message: raw
+                .message
+                .unwrap_or("unexpected token")
+                .to_string(),
+            snippet,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_at_the_failing_line_and_column() {
+        let original = "foo/bar\n!!! ( baz )";
+        let raw = RawDependencyParseError {
+            remaining: "!! ( baz )",
+            message: Some(r#"expected ')'"#),
+        };
+
+        let err = DependencyParseError::new(original, raw);
+
+        assert_eq!(err.location, Location { line: 2, column: 2 });
+        assert_eq!(err.message, r#"expected ')'"#);
+        assert_eq!(err.snippet, "!!! ( baz )\n ^");
+    }
+}
@@ -8,6 +8,7 @@ use nom::{
     bytes::complete::tag,
     character::complete::{multispace0, multispace1},
     combinator::{map, opt},
+    error::context,
     multi::separated_list0,
     sequence::{delimited, pair, preceded},
     IResult,
@@ -16,12 +17,19 @@ use nom_regex::str::re_find;
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+use super::parse_error::{DependencyParseError, RawDependencyParseError};
 use super::{ComplexCompositeDependency, CompositeDependency};
 
 /// Regular expression matching a valid USE flag name.
 static USE_NAME_RE: Lazy<Regex> =
     Lazy::new(|| Regex::new(r"^[A-Za-z0-9][A-Za-z0-9+_@-]*").unwrap());
 
+/// The nom result type used by every combinator in this module: it carries a
+/// [`RawDependencyParseError`] instead of nom's default error, so that
+/// failures retain the position and context needed to build a
+/// [`DependencyParseError`] once they reach [`DependencyParser::parse`].
+pub type PResult<'i, O> = IResult<&'i str, O, RawDependencyParseError<'i>>;
+
 /// Provides a dependency expression parser.
 pub trait DependencyParser {
     type Output;
@@ -37,13 +45,11 @@ pub trait PartialExpressionParser {
     type Output;
 
     /// Consumes an expression found on the beginning of the input.
-    fn parse_expression(input: &str) -> IResult<&str, Self::Output>;
+    fn parse_expression(input: &str) -> PResult<Self::Output>;
 }
 
 /// Consumes zero or more expressions found on the beginning of the input.
-pub fn parse_expression_list<P: PartialExpressionParser>(
-    input: &str,
-) -> IResult<&str, Vec<P::Output>> {
+pub fn parse_expression_list<P: PartialExpressionParser>(input: &str) -> PResult<Vec<P::Output>> {
     let (input, exprs) = preceded(
         multispace0,
         separated_list0(multispace1, |input| P::parse_expression(input)),
@@ -59,7 +65,7 @@ pub fn parse_expression_list<P: PartialExpressionParser>(
 fn parse_group<'i, P: PartialExpressionParser>(
     input: &'i str,
     marker: Option<&str>,
-) -> IResult<&'i str, Vec<P::Output>> {
+) -> PResult<'i, Vec<P::Output>> {
     let input = if let Some(marker) = marker {
         let (input, _) = tag(marker)(input)?;
         let (input, _) = multispace1(input)?;
@@ -70,14 +76,14 @@ fn parse_group<'i, P: PartialExpressionParser>(
     let (input, children) = delimited(
         pair(tag("("), multispace1),
         |input| parse_expression_list::<P>(input),
-        pair(multispace1, tag(")")),
+        pair(multispace1, context(r#"expected ')'"#, tag(")"))),
     )(input)?;
     Ok((input, children))
 }
 
 /// Consumes a USE flag name found on the beginning of the input.
-pub fn parse_use_name(input: &str) -> IResult<&str, &str> {
-    re_find(USE_NAME_RE.clone())(input)
+pub fn parse_use_name(input: &str) -> PResult<&str> {
+    context("invalid USE flag name", re_find(USE_NAME_RE.clone()))(input)
 }
 
 /// Result of [`parse_use_conditional`].
@@ -90,16 +96,16 @@ struct ParsedUseConditional<'i, D> {
 /// Consumes a USE conditional expression found on the beginning of the input.
 fn parse_use_conditional<P: PartialExpressionParser>(
     input: &str,
-) -> IResult<&str, ParsedUseConditional<P::Output>> {
+) -> PResult<ParsedUseConditional<P::Output>> {
     let (input, negate) = opt(tag("!"))(input)?;
     let expect = negate.is_none();
     let (input, name) = parse_use_name(input)?;
-    let (input, _) = tag("?")(input)?;
+    let (input, _) = context("unexpected token after '?'", tag("?"))(input)?;
     let (input, _) = multispace1(input)?;
     let (input, children) = delimited(
         pair(tag("("), multispace1),
         |input| parse_expression_list::<P>(input),
-        pair(multispace1, tag(")")),
+        pair(multispace1, context(r#"expected ')'"#, tag(")"))),
     )(input)?;
     Ok((
         input,
@@ -115,7 +121,7 @@ fn parse_use_conditional<P: PartialExpressionParser>(
 /// returns [`CompositeDependency`].
 pub fn parse_composite<P: PartialExpressionParser>(
     input: &str,
-) -> IResult<&str, CompositeDependency<P::Output>> {
+) -> PResult<CompositeDependency<P::Output>> {
     alt((
         map(
             |input| parse_group::<P>(input, None),
@@ -139,7 +145,7 @@ pub fn parse_composite<P: PartialExpressionParser>(
 /// and returns [`ComplexCompositeDependency`].
 pub fn parse_complex_composite<P: PartialExpressionParser>(
     input: &str,
-) -> IResult<&str, ComplexCompositeDependency<P::Output>> {
+) -> PResult<ComplexCompositeDependency<P::Output>> {
     alt((
         map(
             |input| parse_group::<P>(input, None),
@@ -166,3 +172,19 @@ pub fn parse_complex_composite<P: PartialExpressionParser>(
         }),
     ))(input)
 }
+
+/// Converts a failed nom parse of `original` into a [`DependencyParseError`]
+/// pinpointing where parsing gave up.
+pub fn to_dependency_parse_error(
+    original: &str,
+    err: nom::Err<RawDependencyParseError>,
+) -> DependencyParseError {
+    let raw = match err {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => RawDependencyParseError {
+            remaining: "",
+            message: None,
+        },
+    };
+    DependencyParseError::new(original, raw)
+}
@@ -8,13 +8,12 @@ use nom::{
     bytes::complete::tag,
     character::complete::multispace0,
     combinator::{eof, map, opt},
-    IResult,
 };
 
 use crate::dependency::{
     parser::{
-        parse_complex_composite, parse_expression_list, parse_use_name, DependencyParser,
-        PartialExpressionParser,
+        parse_complex_composite, parse_expression_list, parse_use_name,
+        to_dependency_parse_error, DependencyParser, PResult, PartialExpressionParser,
     },
     requse::{RequiredUseAtom, RequiredUseDependency},
     ComplexCompositeDependency, ComplexDependency,
@@ -26,7 +25,7 @@ pub struct RequiredUseDependencyParser;
 impl PartialExpressionParser for RequiredUseDependencyParser {
     type Output = RequiredUseDependency;
 
-    fn parse_expression(input: &str) -> IResult<&str, Self::Output> {
+    fn parse_expression(input: &str) -> PResult<Self::Output> {
         let (input, _) = multispace0(input)?;
         alt((
             map(
@@ -39,7 +38,7 @@ impl PartialExpressionParser for RequiredUseDependencyParser {
 }
 
 impl RequiredUseDependencyParser {
-    fn atom(input: &str) -> IResult<&str, RequiredUseDependency> {
+    fn atom(input: &str) -> PResult<RequiredUseDependency> {
         let (input, negate) = opt(tag("!"))(input)?;
         let expect = negate.is_none();
         let (input, name) = parse_use_name(input)?;
@@ -53,7 +52,7 @@ impl RequiredUseDependencyParser {
         ))
     }
 
-    fn full(input: &str) -> IResult<&str, RequiredUseDependency> {
+    fn full(input: &str) -> PResult<RequiredUseDependency> {
         let (input, children) = parse_expression_list::<Self>(input)?;
         let (input, _) = multispace0(input)?;
         let (input, _) = eof(input)?;
@@ -69,7 +68,8 @@ impl DependencyParser for RequiredUseDependencyParser {
     type Err = Error;
 
     fn parse(input: &str) -> Result<Self::Output> {
-        let (_, deps) = RequiredUseDependencyParser::full(input).map_err(|err| err.to_owned())?;
+        let (_, deps) = RequiredUseDependencyParser::full(input)
+            .map_err(|err| to_dependency_parse_error(input, err))?;
         Ok(deps)
     }
 }
@@ -10,11 +10,13 @@ use nom::{
     bytes::complete::take_while1,
     character::complete::multispace0,
     combinator::{eof, map, map_res},
-    IResult,
 };
 
 use crate::dependency::{
-    parser::{parse_composite, parse_expression_list, DependencyParser, PartialExpressionParser},
+    parser::{
+        parse_composite, parse_expression_list, to_dependency_parse_error, DependencyParser,
+        PResult, PartialExpressionParser,
+    },
     restrict::{RestrictAtom, RestrictDependency},
     CompositeDependency, Dependency,
 };
@@ -25,7 +27,7 @@ pub struct RestrictDependencyParser;
 impl PartialExpressionParser for RestrictDependencyParser {
     type Output = RestrictDependency;
 
-    fn parse_expression(input: &str) -> IResult<&str, Self::Output> {
+    fn parse_expression(input: &str) -> PResult<Self::Output> {
         let (input, _) = multispace0(input)?;
         alt((
             map(parse_composite::<Self>, Dependency::new_composite),
@@ -35,7 +37,7 @@ impl PartialExpressionParser for RestrictDependencyParser {
 }
 
 impl RestrictDependencyParser {
-    fn restrict(input: &str) -> IResult<&str, RestrictDependency> {
+    fn restrict(input: &str) -> PResult<RestrictDependency> {
         let first = Cell::new(true);
         let (input, value) = map_res(
             take_while1(|c| {
@@ -52,7 +54,7 @@ impl RestrictDependencyParser {
         Ok((input, Dependency::Leaf(value)))
     }
 
-    fn full(input: &str) -> IResult<&str, RestrictDependency> {
+    fn full(input: &str) -> PResult<RestrictDependency> {
         let (input, children) = parse_expression_list::<Self>(input)?;
         let (input, _) = multispace0(input)?;
         let (input, _) = eof(input)?;
@@ -68,7 +70,8 @@ impl DependencyParser for RestrictDependencyParser {
     type Err = Error;
 
     fn parse(input: &str) -> Result<Self::Output> {
-        let (_, deps) = RestrictDependencyParser::full(input).map_err(|err| err.to_owned())?;
+        let (_, deps) = RestrictDependencyParser::full(input)
+            .map_err(|err| to_dependency_parse_error(input, err))?;
         Ok(deps)
     }
 }
@@ -9,12 +9,14 @@ use nom::{
     character::complete::multispace0,
     combinator::{eof, map, map_res, opt, verify},
     sequence::{preceded, tuple},
-    IResult,
 };
 use url::Url;
 
 use crate::dependency::{
-    parser::{parse_composite, parse_expression_list, DependencyParser, PartialExpressionParser},
+    parser::{
+        parse_composite, parse_expression_list, to_dependency_parse_error, DependencyParser,
+        PResult, PartialExpressionParser,
+    },
     uri::{UriAtomDependency, UriDependency},
     CompositeDependency, Dependency,
 };
@@ -25,7 +27,7 @@ pub struct UriDependencyParser;
 impl PartialExpressionParser for UriDependencyParser {
     type Output = UriDependency;
 
-    fn parse_expression(input: &str) -> IResult<&str, Self::Output> {
+    fn parse_expression(input: &str) -> PResult<Self::Output> {
         let (input, _) = multispace0(input)?;
         alt((
             // Prefer matches with composite dependencies since URIs/filenames
@@ -42,7 +44,7 @@ impl PartialExpressionParser for UriDependencyParser {
 }
 
 impl UriDependencyParser {
-    fn uri(input: &str) -> IResult<&str, (Url, Option<&str>)> {
+    fn uri(input: &str) -> PResult<(Url, Option<&str>)> {
         let (input, url) = map_res(take_till1(char::is_whitespace), Url::parse)(input)?;
         let (input, filename) = opt(preceded(
             tuple((multispace0, tag("->"), multispace0)),
@@ -51,12 +53,12 @@ impl UriDependencyParser {
         Ok((input, (url, filename)))
     }
 
-    fn filename(input: &str) -> IResult<&str, &str> {
+    fn filename(input: &str) -> PResult<&str> {
         // Avoid matching with a closing parenthesis.
         verify(take_till1(char::is_whitespace), |s: &str| s != ")")(input)
     }
 
-    fn full(input: &str) -> IResult<&str, UriDependency> {
+    fn full(input: &str) -> PResult<UriDependency> {
         let (input, children) = parse_expression_list::<Self>(input)?;
         let (input, _) = multispace0(input)?;
         let (input, _) = eof(input)?;
@@ -72,7 +74,8 @@ impl DependencyParser for UriDependencyParser {
     type Err = Error;
 
     fn parse(input: &str) -> Result<Self::Output> {
-        let (_, deps) = UriDependencyParser::full(input).map_err(|err| err.to_owned())?;
+        let (_, deps) = UriDependencyParser::full(input)
+            .map_err(|err| to_dependency_parse_error(input, err))?;
         Ok(deps)
     }
 }
@@ -0,0 +1,257 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! A pluggable, content-addressed shared cache for [`super::metadata::CachedEBuildEvaluator`],
+//! modeled on sccache: a digest computed from every input that affects evaluation is used as the
+//! lookup key into a [`CacheStorage`] backend, so developers and CI bots evaluating the same
+//! ebuild never have to re-run bash for it more than once.
+
+use std::{
+    fmt, fs, io,
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest as _, Sha256};
+use walkdir::WalkDir;
+
+use crate::repository::Sha256Digest;
+
+/// A pluggable backend for the shared ebuild evaluation cache.
+///
+/// Implementations must be safe to share across threads: [`super::metadata::CachedEBuildEvaluator`]
+/// may call `get`/`put` concurrently for different ebuilds.
+pub trait CacheStorage: fmt::Debug + Send + Sync {
+    /// Returns the cached bytes for `digest`, or `None` on a cache miss. A backend should prefer
+    /// returning `Ok(None)` over an `Err` for anything that looks like corruption, so that a
+    /// single bad entry costs extra evaluation work instead of poisoning the result.
+    fn get(&self, digest: &Sha256Digest) -> Result<Option<Vec<u8>>>;
+
+    /// Stores `data` under `digest`. Callers treat a `put` failure as non-fatal: it should only
+    /// cost a future cache miss, never be surfaced as an evaluation failure.
+    fn put(&self, digest: &Sha256Digest, data: &[u8]) -> Result<()>;
+}
+
+/// A [`CacheStorage`] backed by a local directory, keyed by hex digest file names.
+#[derive(Debug)]
+pub struct LocalDiskCache {
+    root: PathBuf,
+}
+
+impl LocalDiskCache {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn entry_path(&self, digest: &Sha256Digest) -> PathBuf {
+        self.root.join(format!("{:x}", digest))
+    }
+}
+
+impl CacheStorage for LocalDiskCache {
+    fn get(&self, digest: &Sha256Digest) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.entry_path(digest)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context("Failed to read from local disk cache"),
+        }
+    }
+
+    fn put(&self, digest: &Sha256Digest, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.root)
+            .with_context(|| format!("Failed to create {}", self.root.display()))?;
+
+        // Write to a sibling temporary file and rename into place, so a concurrent `get` never
+        // observes a partially written entry.
+        let final_path = self.entry_path(digest);
+        let temp_path = self
+            .root
+            .join(format!("{:x}.tmp.{}", digest, std::process::id()));
+        fs::write(&temp_path, data)
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        fs::rename(&temp_path, &final_path)
+            .with_context(|| format!("Failed to commit {}", final_path.display()))?;
+        Ok(())
+    }
+}
+
+/// A [`CacheStorage`] backed by an HTTP(S) object store, e.g. an S3 bucket exposed behind
+/// presigned GET/PUT URLs, or a GCS bucket's XML API.
+///
+/// Shells out to `curl` rather than pulling in an HTTP client crate, the same tradeoff other
+/// object-store integrations in this repo make (see the `gsutil` invocation in
+/// `extract_package_from_manifest`).
+#[derive(Debug)]
+pub struct HttpObjectStoreCache {
+    /// Base URL such that `<base_url>/<hex digest>` is a GET-able/PUT-able object.
+    base_url: String,
+}
+
+impl HttpObjectStoreCache {
+    pub fn new(base_url: String) -> Self {
+        Self { base_url }
+    }
+
+    fn entry_url(&self, digest: &Sha256Digest) -> String {
+        format!("{}/{:x}", self.base_url.trim_end_matches('/'), digest)
+    }
+}
+
+impl CacheStorage for HttpObjectStoreCache {
+    fn get(&self, digest: &Sha256Digest) -> Result<Option<Vec<u8>>> {
+        let output = Command::new("curl")
+            .args(["--fail", "--silent", "--show-error", "--location"])
+            .arg(self.entry_url(digest))
+            .output()
+            .context("Failed to spawn curl")?;
+        if !output.status.success() {
+            // With --fail, curl exits non-zero on an HTTP error response (e.g. 404 for a cache
+            // miss), so treat any failure as a miss rather than aborting evaluation over it.
+            return Ok(None);
+        }
+        Ok(Some(output.stdout))
+    }
+
+    fn put(&self, digest: &Sha256Digest, data: &[u8]) -> Result<()> {
+        let mut temp_file = tempfile::NamedTempFile::new()?;
+        temp_file.write_all(data)?;
+
+        let url = self.entry_url(digest);
+        let status = Command::new("curl")
+            .args([
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--request",
+                "PUT",
+                "--upload-file",
+            ])
+            .arg(temp_file.path())
+            .arg(&url)
+            .status()
+            .context("Failed to spawn curl")?;
+        if !status.success() {
+            bail!("curl PUT to {} failed", url);
+        }
+        Ok(())
+    }
+}
+
+/// Hashes every regular file under `dir`, in path-sorted order, into a single digest.
+///
+/// Used to fingerprint inputs -- an eclass directory, the tools directory -- where what matters
+/// is "did anything in this tree change" rather than any individual file's content on its own. A
+/// missing directory fingerprints the same as an empty one.
+pub(super) fn fingerprint_dir(dir: &Path) -> Result<Sha256Digest> {
+    let mut paths: Vec<PathBuf> = match fs::metadata(dir) {
+        Ok(_) => WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to stat {}", dir.display())),
+    };
+    paths.sort();
+
+    let mut hasher = Sha256::new();
+    for path in paths {
+        let content =
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        // Use the path relative to `dir` so the digest is stable across machines that check the
+        // tree out to different absolute locations.
+        hasher.update(
+            path.strip_prefix(dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .as_bytes(),
+        );
+        hasher.update(&content);
+    }
+    Ok(hasher.finalize())
+}
+
+/// Computes the shared-cache key for evaluating `ebuild_path`, from every input that affects the
+/// result of [`super::metadata::EBuildEvaluator::evaluate_metadata`]: the ebuild's own path and
+/// content, the effective configuration it's evaluated against (`config_digest`, from
+/// [`super::super::config::bundle::ConfigBundle::digest`]), plus the eclass and tools-directory
+/// fingerprints computed once per process by
+/// [`super::metadata::CachedEBuildEvaluator::with_shared_cache`].
+///
+/// The path is part of the key (not just the content) so that two packages with byte-identical
+/// ebuilds -- common for stub/virtual packages -- never collide and serve each other's cached
+/// result.
+///
+/// Which eclasses a given ebuild actually inherits is only known after running it -- `INHERITED`
+/// is an output of evaluation, not an input alchemist can read up front -- so `eclass_fingerprint`
+/// conservatively covers every eclass file reachable from the repository set instead of just the
+/// inherited subset. This trades a coarser cache key (an edit to an unrelated eclass invalidates
+/// every ebuild's entry) for one that's computable before evaluation starts.
+pub(super) fn compute_digest(
+    ebuild_path: &Path,
+    config_digest: &Sha256Digest,
+    eclass_fingerprint: &Sha256Digest,
+    tools_fingerprint: &Sha256Digest,
+) -> Result<Sha256Digest> {
+    let ebuild_content = fs::read(ebuild_path)
+        .with_context(|| format!("Failed to read {}", ebuild_path.display()))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(ebuild_path.to_string_lossy().as_bytes());
+    hasher.update(&ebuild_content);
+    hasher.update(config_digest);
+    hasher.update(eclass_fingerprint);
+    hasher.update(tools_fingerprint);
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_disk_cache_roundtrip() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let cache = LocalDiskCache::new(temp_dir.path().join("cache"));
+
+        let digest = Sha256::digest(b"hello");
+
+        assert_eq!(cache.get(&digest)?, None);
+
+        cache.put(&digest, b"world")?;
+        assert_eq!(cache.get(&digest)?, Some(b"world".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint_dir_missing_is_empty() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let missing = temp_dir.path().join("does-not-exist");
+
+        assert_eq!(fingerprint_dir(&missing)?, Sha256::new().finalize());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fingerprint_dir_changes_with_content() -> Result<()> {
+        let temp_dir = tempfile::TempDir::new()?;
+        let dir = temp_dir.path().join("eclass");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("foo.eclass"), "one")?;
+
+        let before = fingerprint_dir(&dir)?;
+
+        std::fs::write(dir.join("foo.eclass"), "two")?;
+        let after = fingerprint_dir(&dir)?;
+
+        assert_ne!(before, after);
+
+        Ok(())
+    }
+}
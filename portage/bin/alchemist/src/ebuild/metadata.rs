@@ -2,11 +2,15 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use crate::repository::{Repository, UnorderedRepositorySet};
+use crate::repository::{
+    Repository, RepositorySetOperations, Sha256Digest, UnorderedRepositorySet,
+};
 
 use anyhow::{anyhow, bail, Context, Result};
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::sync::Arc;
@@ -21,7 +25,9 @@ use version::Version;
 
 use crate::{
     bash::vars::{parse_set_output, BashVars},
+    config::bundle::ConfigBundle,
     data::Vars,
+    ebuild::cache::{self, CacheStorage},
 };
 
 fn run_ebuild<'a>(
@@ -133,18 +139,36 @@ impl EBuildEvaluator {
 /// This information is available as long as an ebuild file exists with a correct file name format.
 /// All package-representing types containing [`EBuildBasicData`] directly or indirectly should
 /// implement [`Deref`] to provide easy access to [`EBuildBasicData`] fields.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct EBuildBasicData {
     pub repo_name: String,
     pub ebuild_path: PathBuf,
     pub package_name: String,
     pub short_package_name: String,
     pub category_name: String,
+    #[serde(with = "version_serde")]
     pub version: Version,
 }
 
+/// (De)serializes a [`Version`] as its canonical string form, since it is defined in an external
+/// crate we cannot derive [`Serialize`]/[`Deserialize`] on directly.
+mod version_serde {
+    use super::Version;
+    use serde::{de::Error as _, Deserialize, Deserializer, Serializer};
+    use std::str::FromStr;
+
+    pub fn serialize<S: Serializer>(version: &Version, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&version.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Version, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Version::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
 /// Describes metadata of an ebuild.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct EBuildMetadata {
     pub basic_data: EBuildBasicData,
     pub vars: BashVars,
@@ -159,7 +183,7 @@ impl Deref for EBuildMetadata {
 }
 
 /// Describes an error on evaluating an ebuild.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct EBuildEvaluationError {
     pub basic_data: EBuildBasicData,
     pub error: String,
@@ -180,7 +204,7 @@ impl Deref for EBuildEvaluationError {
 ///
 /// While this enum looks very similar to [`Result`], we don't make it a type alias of [`Result`]
 /// to implement a few convenient methods.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum MaybeEBuildMetadata {
     Ok(Arc<EBuildMetadata>),
     Err(Arc<EBuildEvaluationError>),
@@ -273,12 +297,39 @@ impl TryFrom<&Path> for EBuildPathInfo {
     }
 }
 
+/// Inputs to the shared, content-addressed evaluation cache that are constant for the lifetime
+/// of a [`CachedEBuildEvaluator`], computed once in [`CachedEBuildEvaluator::with_shared_cache`]
+/// so [`CachedEBuildEvaluator::evaluate_metadata`] doesn't have to re-walk the eclass and tools
+/// directories on every call.
+struct SharedCache {
+    storage: Arc<dyn CacheStorage>,
+    eclass_fingerprint: Sha256Digest,
+    tools_fingerprint: Sha256Digest,
+}
+
+impl std::fmt::Debug for SharedCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SharedCache")
+            .field("storage", &self.storage)
+            .field(
+                "eclass_fingerprint",
+                &format!("{:x}", self.eclass_fingerprint),
+            )
+            .field(
+                "tools_fingerprint",
+                &format!("{:x}", self.tools_fingerprint),
+            )
+            .finish()
+    }
+}
+
 /// Wraps EBuildEvaluator to cache results.
 #[derive(Debug)]
 pub struct CachedEBuildEvaluator {
     repos: UnorderedRepositorySet,
     evaluator: EBuildEvaluator,
     cache: Mutex<HashMap<PathBuf, Arc<OnceCell<MaybeEBuildMetadata>>>>,
+    shared_cache: Option<SharedCache>,
 }
 
 impl CachedEBuildEvaluator {
@@ -289,10 +340,53 @@ impl CachedEBuildEvaluator {
             repos,
             evaluator,
             cache: Default::default(),
+            shared_cache: None,
+        }
+    }
+
+    /// Enables an sccache-style shared evaluation cache backed by `storage`.
+    ///
+    /// Every ebuild's cache key folds in a fingerprint of every eclass file reachable from the
+    /// repository set and of the tools directory, computed once here, so that editing either
+    /// invalidates entries without this evaluator having to re-fingerprint them on every lookup.
+    pub fn with_shared_cache(mut self, storage: Arc<dyn CacheStorage>) -> Result<Self> {
+        let mut eclass_dirs: Vec<&Path> = self
+            .repos
+            .get_unordered_repos()
+            .flat_map(|repo| repo.eclass_dirs())
+            .collect();
+        eclass_dirs.sort();
+        eclass_dirs.dedup();
+
+        let mut hasher = Sha256::new();
+        for dir in eclass_dirs {
+            hasher.update(cache::fingerprint_dir(dir)?);
         }
+        let eclass_fingerprint = hasher.finalize();
+
+        let tools_fingerprint = cache::fingerprint_dir(&self.evaluator.tools_dir)?;
+
+        self.shared_cache = Some(SharedCache {
+            storage,
+            eclass_fingerprint,
+            tools_fingerprint,
+        });
+        Ok(self)
     }
 
-    pub fn evaluate_metadata(&self, ebuild_path: &Path) -> Result<MaybeEBuildMetadata> {
+    /// Evaluates `ebuild_path`, using the in-process cache (and the shared cache, if configured)
+    /// to avoid re-running bash for an ebuild this process has already evaluated.
+    ///
+    /// `config` is only consulted to key the shared cache (see [`ConfigBundle::digest`]); ebuild
+    /// metadata evaluation itself is config-independent, per PMS's requirement that ebuild
+    /// metadata be defined independently of profiles, so a single process only ever evaluates a
+    /// given `ebuild_path` under one `config` and the in-process cache below can stay keyed on
+    /// the path alone.
+    pub fn evaluate_metadata(
+        &self,
+        ebuild_path: &Path,
+        config: &ConfigBundle,
+    ) -> Result<MaybeEBuildMetadata> {
         let once_cell = {
             let mut cache_guard = self.cache.lock().unwrap();
             cache_guard
@@ -300,16 +394,55 @@ impl CachedEBuildEvaluator {
                 .or_default()
                 .clone()
         };
-        let details = once_cell.get_or_try_init(|| {
-            let repo = self.repos.get_repo_by_path(ebuild_path)?;
-            self.evaluator.evaluate_metadata(ebuild_path, repo)
-        })?;
+        let details =
+            once_cell.get_or_try_init(|| self.evaluate_metadata_uncached(ebuild_path, config))?;
         Ok(details.clone())
     }
+
+    /// Evaluates `ebuild_path`, consulting the shared cache (if any) before falling back to
+    /// running bash. Any problem reading or deserializing a shared-cache entry is treated as a
+    /// miss rather than propagated, so a corrupt or poisoned entry costs an extra evaluation
+    /// instead of returning a wrong package.
+    fn evaluate_metadata_uncached(
+        &self,
+        ebuild_path: &Path,
+        config: &ConfigBundle,
+    ) -> Result<MaybeEBuildMetadata> {
+        let Some(shared_cache) = &self.shared_cache else {
+            let repo = self.repos.get_repo_by_path(ebuild_path)?;
+            return self.evaluator.evaluate_metadata(ebuild_path, repo);
+        };
+
+        let config_digest = config.digest()?;
+        let digest = cache::compute_digest(
+            ebuild_path,
+            &config_digest,
+            &shared_cache.eclass_fingerprint,
+            &shared_cache.tools_fingerprint,
+        )?;
+
+        if let Ok(Some(bytes)) = shared_cache.storage.get(&digest) {
+            if let Ok(metadata) = serde_json::from_slice::<MaybeEBuildMetadata>(&bytes) {
+                return Ok(metadata);
+            }
+        }
+
+        let repo = self.repos.get_repo_by_path(ebuild_path)?;
+        let metadata = self.evaluator.evaluate_metadata(ebuild_path, repo)?;
+
+        if let Ok(bytes) = serde_json::to_vec(&metadata) {
+            // Best-effort: a failure to populate the shared cache should not fail evaluation.
+            let _ = shared_cache.storage.put(&digest, &bytes);
+        }
+
+        Ok(metadata)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
+
     use tempfile::TempDir;
 
     use super::*;
@@ -486,4 +619,92 @@ assert_var PVR "1.2.3-r99"
 
         Ok(())
     }
+
+    /// Ensures [`CachedEBuildEvaluator`] returns a shared cache hit verbatim instead of
+    /// re-evaluating the ebuild.
+    #[test]
+    fn test_cached_evaluator_shared_cache_hit() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_dir = temp_dir.path();
+
+        let ebuild_path = temp_dir.join("sys-apps/hello/hello-1.2.3.ebuild");
+        std::fs::create_dir_all(ebuild_path.parent().unwrap())?;
+        std::fs::write(&ebuild_path, "EAPI=7\nSLOT=0\nKEYWORDS=\"*\"\n")?;
+
+        let repo = Repository::new_for_testing("test", temp_dir);
+        let repos: UnorderedRepositorySet = [repo].into_iter().collect();
+        let storage = Arc::new(LocalDiskCache::new(temp_dir.join("shared-cache")));
+
+        let evaluator = CachedEBuildEvaluator::new(repos, &temp_dir.join("tools"))
+            .with_shared_cache(storage.clone())?;
+
+        let Some(shared_cache) = &evaluator.shared_cache else {
+            panic!("with_shared_cache did not set up a shared cache");
+        };
+        let config = ConfigBundle::new_for_testing("riscv");
+        let digest = cache::compute_digest(
+            &ebuild_path,
+            &config.digest()?,
+            &shared_cache.eclass_fingerprint,
+            &shared_cache.tools_fingerprint,
+        )?;
+
+        // Seed the shared cache with a result that live evaluation of this ebuild could never
+        // produce (a die error, even though the ebuild itself has no `die` call), so a hit is
+        // distinguishable from a miss that happened to fall back to live evaluation.
+        let basic_data = EBuildBasicData {
+            repo_name: "test".into(),
+            ebuild_path: ebuild_path.clone(),
+            package_name: "sys-apps/hello".into(),
+            short_package_name: "hello".into(),
+            category_name: "sys-apps".into(),
+            version: Version::from_str("1.2.3")?,
+        };
+        let seeded = MaybeEBuildMetadata::Err(Arc::new(EBuildEvaluationError {
+            basic_data,
+            error: "seeded from shared cache".into(),
+        }));
+        storage.put(&digest, &serde_json::to_vec(&seeded)?)?;
+
+        let metadata = evaluator.evaluate_metadata(&ebuild_path, &config)?;
+        assert_eq!(metadata, seeded);
+
+        Ok(())
+    }
+
+    /// Ensures a corrupt shared-cache entry is treated as a miss rather than returned as-is.
+    #[test]
+    fn test_cached_evaluator_shared_cache_corrupt_entry_is_a_miss() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_dir = temp_dir.path();
+
+        let ebuild_path = temp_dir.join("sys-apps/hello/hello-1.2.3.ebuild");
+        std::fs::create_dir_all(ebuild_path.parent().unwrap())?;
+        std::fs::write(&ebuild_path, "EAPI=7\nSLOT=0\nKEYWORDS=\"*\"\n")?;
+
+        let repo = Repository::new_for_testing("test", temp_dir);
+        let repos: UnorderedRepositorySet = [repo].into_iter().collect();
+        let storage = Arc::new(LocalDiskCache::new(temp_dir.join("shared-cache")));
+
+        let evaluator = CachedEBuildEvaluator::new(repos, &temp_dir.join("tools"))
+            .with_shared_cache(storage)?;
+
+        let Some(shared_cache) = &evaluator.shared_cache else {
+            panic!("with_shared_cache did not set up a shared cache");
+        };
+        let config = ConfigBundle::new_for_testing("riscv");
+        let digest = cache::compute_digest(
+            &ebuild_path,
+            &config.digest()?,
+            &shared_cache.eclass_fingerprint,
+            &shared_cache.tools_fingerprint,
+        )?;
+        shared_cache.storage.put(&digest, b"not valid json")?;
+
+        // A corrupt entry must fall back to live evaluation, never a wrong (or panicking) result.
+        let metadata = evaluator.evaluate_metadata(&ebuild_path, &config)?;
+        assert!(matches!(metadata, MaybeEBuildMetadata::Ok(_)));
+
+        Ok(())
+    }
 }
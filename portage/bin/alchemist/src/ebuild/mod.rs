@@ -2,16 +2,22 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+mod cache;
 pub mod metadata;
 
+pub use cache::{CacheStorage, HttpObjectStoreCache, LocalDiskCache};
+
 use anyhow::{bail, Context, Result};
 use itertools::Itertools;
 use once_cell::sync::OnceCell;
+use regex::Regex;
 use serde::Deserialize;
 use version::Version;
+use walkdir::WalkDir;
 
 use std::{
     collections::{HashMap, HashSet},
+    ffi::OsStr,
     io::ErrorKind,
     path::{Path, PathBuf},
     str::FromStr,
@@ -101,6 +107,90 @@ impl<'de> Deserialize<'de> for BashExpr {
     }
 }
 
+/// A per-field merge directive for list-valued `[bazel]` metadata, modeled on Cargo's
+/// `workspace.package` field inheritance: a field is either a plain list, which keeps today's
+/// union-with-whatever-eclasses-contributed behavior, or a table describing precisely how to
+/// combine it with what was inherited.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum ListField<T> {
+    /// `extra_sources = ["a", "b"]`: equivalent to `{ add = ["a", "b"] }`.
+    Add(Vec<T>),
+    /// `extra_sources = { inherit = false, add = [...], remove = [...] }`.
+    Directive {
+        /// When `false`, discards everything inherited from less specific configs before
+        /// `remove` and `add` below are applied. `clear = true` is accepted as a synonym, so a
+        /// field that only wants to reset (e.g. `interface_library_allowlist = { clear = true
+        /// }`) doesn't need a redundant empty `add`.
+        inherit: bool,
+        /// Entries to drop from what was inherited, applied after `inherit` and before `add`.
+        remove: Vec<T>,
+        /// Entries to union in, applied last.
+        add: Vec<T>,
+    },
+}
+
+impl<'de, T> Deserialize<'de> for ListField<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct RawDirective<T> {
+            inherit: Option<bool>,
+            clear: Option<bool>,
+            #[serde(default = "Vec::new")]
+            remove: Vec<T>,
+            #[serde(default = "Vec::new")]
+            add: Vec<T>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr<T> {
+            List(Vec<T>),
+            Directive(RawDirective<T>),
+        }
+
+        Ok(match Repr::<T>::deserialize(deserializer)? {
+            Repr::List(items) => ListField::Add(items),
+            Repr::Directive(raw) => ListField::Directive {
+                inherit: raw.inherit.unwrap_or(true) && !raw.clear.unwrap_or(false),
+                remove: raw.remove,
+                add: raw.add,
+            },
+        })
+    }
+}
+
+impl<T> ListField<T>
+where
+    T: Eq + std::hash::Hash,
+{
+    /// Applies this directive to `target`, which holds everything accumulated from less specific
+    /// configs so far.
+    fn apply(self, target: &mut HashSet<T>) {
+        match self {
+            ListField::Add(items) => target.extend(items),
+            ListField::Directive {
+                inherit,
+                remove,
+                add,
+            } => {
+                if !inherit {
+                    target.clear();
+                }
+                for item in &remove {
+                    target.remove(item);
+                }
+                target.extend(add);
+            }
+        }
+    }
+}
+
 /// Defines the merged Bazel-specific metadata found in all relevant TOML files.
 ///
 /// Metadata of a package may consist of multiple TOML files: one for the ebuild file and those for
@@ -127,8 +217,20 @@ pub struct BazelSpecificMetadata {
     /// element must be a label of a Bazel target defined with `extra_sources` rule from
     /// `//bazel/portage/build_defs:extra_sources.bzl`. The rule defines a set of files to be used
     /// as extra sources.
+    ///
+    /// Besides a plain list, a TOML file may instead write `extra_sources = { inherit = false,
+    /// add = [...], remove = [...] }` to subtract a label wrongly contributed by a broad eclass,
+    /// or `{ clear = true }` to discard everything inherited outright. See [`ListField`].
     pub extra_sources: HashSet<String>,
 
+    /// USE-conditional variants of [`Self::extra_sources`].
+    ///
+    /// Each entry is unioned into the result of [`Self::eval_extra_sources`] when its
+    /// [`BashExpr`] evaluates to true against the package's USE map. This lets ebuilds and
+    /// eclasses pull in board- or feature-specific build scripts without forking the package,
+    /// e.g. `[[bazel.extra_sources_if]]` with `condition = "use foo && use !bar"`.
+    conditional_extra_sources: Vec<(BashExpr, HashSet<String>)>,
+
     /// The package supports dynamically linking against interface only shared objects.
     ///
     /// Enabling this will result in all build-time dependencies of the package having their
@@ -139,12 +241,18 @@ pub struct BazelSpecificMetadata {
     /// You must set this to `false` if your package performs any kind of static linking,
     /// otherwise the required files won't be present.
     ///
-    /// Format: You can specify either `true`, `false`, or a shell expression. The shell
-    /// expression is used to test USE flags. i.e., `use static` or `use !foo && use bar`.
+    /// Format: You can specify either `true`, `false`, or a [`BashExpr`] such as `use static` or
+    /// `use !foo && use bar`.
     ///
     /// This value can also be declared on an `eclass` and it will propagate to all packages that
-    /// inherit from it. If multiple declarations are found they are all ANDed together.
-    supports_interface_libraries: Vec<BashExpr>,
+    /// inherit from it. If multiple declarations are found they are all ANDed together, so an
+    /// eclass can disable interface libraries for every package that inherits from it and no
+    /// individual ebuild can silently re-enable them.
+    ///
+    /// Every declaration is kept, each tagged with the path of the config file it came from, so
+    /// that when the AND chain evaluates to `false`, [`Self::explain_interface_libraries`] can
+    /// point back at the specific eclass or ebuild responsible.
+    supports_interface_libraries: Vec<(PathBuf, BashExpr)>,
 
     /// The static libraries that we allow into the interface library layers.
     ///
@@ -153,24 +261,216 @@ pub struct BazelSpecificMetadata {
     /// everything would be dynamically linked, but some packages are hybrids.
     ///
     /// The path is relative to the sysroot.
+    ///
+    /// Accepts the same `{ inherit, add, remove }` / `{ clear = true }` directive forms as
+    /// [`Self::extra_sources`].
     pub interface_library_allowlist: HashSet<PathBuf>,
+
+    /// USE-conditional variants of [`Self::interface_library_allowlist`].
+    ///
+    /// Each entry is unioned into the result of [`Self::eval_interface_library_allowlist`] when
+    /// its [`BashExpr`] evaluates to true against the package's USE map, via
+    /// `[[bazel.interface_library_allowlist_if]]`.
+    conditional_interface_library_allowlist: Vec<(BashExpr, HashSet<PathBuf>)>,
+
+    /// Whether to infer [`Self::extra_sources`] entries from the package's on-disk layout.
+    ///
+    /// Defaults to `true`; the last config file in the merge chain to set `auto_sources` wins, so
+    /// a package can write `auto_sources = false` to disable discovery entirely (e.g. because it
+    /// keeps unrelated files alongside the ebuild that aren't real build inputs).
+    auto_sources: Option<bool>,
+
+    /// Glob patterns, matched against each discovered file's path relative to the ebuild's own
+    /// directory (e.g. `files/*.patch` or `*.gn`), to exclude from auto-discovery. Accumulates
+    /// across all merged config files rather than overwriting.
+    auto_sources_exclude: Vec<String>,
+
+    /// The subset of [`Self::extra_sources`] that [`Self::auto_discover_sources`] inferred from
+    /// the package's on-disk layout, rather than an explicit `extra_sources` declaration. Kept
+    /// separate so tooling can explain why a given label showed up.
+    pub auto_discovered_sources: HashSet<String>,
+
+    /// Opts this package out of having the implicit system package set (see
+    /// `virtual/target-sdk-implicit-system`, or whatever atom the caller configures) treated as
+    /// already provided by its SDK.
+    ///
+    /// Defaults to `false`; the last config file in the merge chain to set
+    /// `inhibit_implicit_system` wins, mirroring [`Self::auto_sources`]. Bootstrap-critical
+    /// packages set this so they're generated assuming the implicit system isn't installed yet,
+    /// which is what building it from scratch (e.g. the stage3 bootstrap SDK) requires.
+    inhibit_implicit_system: Option<bool>,
 }
 
 impl BazelSpecificMetadata {
-    /// Evaluates the `supports_interface_libraries` expressions.
+    /// Evaluates the `supports_interface_libraries` expression, defaulting to `true` when no
+    /// config file declares one.
     pub fn eval_supports_interface_libraries(&self, use_map: &UseMap) -> Result<bool> {
-        for expr in &self.supports_interface_libraries {
+        Ok(self.explain_interface_libraries(use_map)?.is_none())
+    }
+
+    /// Returns the config file and expression that forced [`Self::eval_supports_interface_libraries`]
+    /// to evaluate to `false`, or `None` if it evaluates to `true`.
+    ///
+    /// Every entry in [`Self::supports_interface_libraries`] is ANDed together; this returns the
+    /// first one (in merge order: eclasses before the ebuild's own config) that evaluates to
+    /// `false`, since that's the declaration responsible for disabling interface libraries.
+    pub fn explain_interface_libraries(
+        &self,
+        use_map: &UseMap,
+    ) -> Result<Option<(PathBuf, BashExpr)>> {
+        for (config_path, expr) in &self.supports_interface_libraries {
             if !expr.eval(use_map).with_context(|| {
                 format!("Failed evaluating {:?} with use map: {:?}", expr, use_map)
             })? {
-                return Ok(false);
+                return Ok(Some((config_path.clone(), expr.clone())));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Evaluates [`Self::extra_sources`] together with every conditional entry whose USE
+    /// expression is satisfied by `use_map`.
+    pub fn eval_extra_sources(&self, use_map: &UseMap) -> Result<HashSet<String>> {
+        let mut sources = self.extra_sources.clone();
+        for (expr, labels) in &self.conditional_extra_sources {
+            if expr.eval(use_map).with_context(|| {
+                format!("Failed evaluating {:?} with use map: {:?}", expr, use_map)
+            })? {
+                sources.extend(labels.iter().cloned());
+            }
+        }
+        Ok(sources)
+    }
+
+    /// Evaluates [`Self::interface_library_allowlist`] together with every conditional entry
+    /// whose USE expression is satisfied by `use_map`.
+    pub fn eval_interface_library_allowlist(&self, use_map: &UseMap) -> Result<HashSet<PathBuf>> {
+        let mut allowlist = self.interface_library_allowlist.clone();
+        for (expr, paths) in &self.conditional_interface_library_allowlist {
+            if expr.eval(use_map).with_context(|| {
+                format!("Failed evaluating {:?} with use map: {:?}", expr, use_map)
+            })? {
+                allowlist.extend(paths.iter().cloned());
             }
         }
+        Ok(allowlist)
+    }
 
-        Ok(true)
+    /// Returns whether this package opted out of treating the implicit system package set as
+    /// already provided, via `inhibit_implicit_system = true`. Defaults to `false`.
+    pub fn inhibits_implicit_system(&self) -> bool {
+        self.inhibit_implicit_system.unwrap_or(false)
+    }
+
+    /// Infers [`Self::extra_sources`] entries from the package's on-disk layout, mirroring
+    /// Cargo's convention-based target discovery: every file under a sibling `files/` directory
+    /// (the Gentoo `FILESDIR` convention) plus any `*.patch` file directly in the ebuild's own
+    /// directory is turned into a label and unioned into [`Self::extra_sources`], unless
+    /// `auto_sources = false` or the file's path (relative to `package_dir`) matches a glob in
+    /// [`Self::auto_sources_exclude`].
+    ///
+    /// Discovered labels address the file through the `//internal/overlays/...` tree that mirrors
+    /// each overlay 1:1, the same addressing `BazelSpecificMetadata::load` already relies on for
+    /// eclass labels.
+    fn auto_discover_sources(
+        &mut self,
+        repo_name: &str,
+        overlay_dir: &Path,
+        package_dir: &Path,
+    ) -> Result<()> {
+        if !self.auto_sources.unwrap_or(true) {
+            return Ok(());
+        }
+
+        let exclude_patterns = self
+            .auto_sources_exclude
+            .iter()
+            .map(|pattern| compile_glob(pattern))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut discovered: Vec<PathBuf> = Vec::new();
+
+        let files_dir = package_dir.join(FILESDIR_NAME);
+        if files_dir.is_dir() {
+            for entry in WalkDir::new(&files_dir).sort_by_file_name() {
+                let entry = entry.with_context(|| {
+                    format!("Failed to scan {} for auto_sources", files_dir.display())
+                })?;
+                if entry.file_type().is_file() {
+                    discovered.push(entry.path().strip_prefix(package_dir)?.to_path_buf());
+                }
+            }
+        }
+
+        let mut patch_paths: Vec<PathBuf> = std::fs::read_dir(package_dir)
+            .with_context(|| format!("Failed to scan {}", package_dir.display()))?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?
+            .into_iter()
+            .filter(|path| path.is_file() && path.extension() == Some(OsStr::new("patch")))
+            .map(|path| {
+                path.strip_prefix(package_dir)
+                    .expect("path is a child of package_dir")
+                    .to_path_buf()
+            })
+            .collect();
+        patch_paths.sort();
+        discovered.append(&mut patch_paths);
+
+        let package_rel_dir = package_dir
+            .strip_prefix(overlay_dir)
+            .context("ebuild path has an overlay directory")?;
+
+        for rel_path in discovered {
+            let rel_path_str = rel_path.to_string_lossy();
+            if exclude_patterns.iter().any(|re| re.is_match(&rel_path_str)) {
+                continue;
+            }
+
+            let label_dir = match rel_path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => package_rel_dir.join(parent),
+                _ => package_rel_dir.to_path_buf(),
+            };
+            let file_name = rel_path
+                .file_name()
+                .expect("discovered path has a file name")
+                .to_string_lossy();
+            let label = format!(
+                "//internal/overlays/{}/{}:{}",
+                repo_name,
+                label_dir.display(),
+                file_name
+            );
+
+            self.auto_discovered_sources.insert(label.clone());
+            self.extra_sources.insert(label);
+        }
+
+        Ok(())
     }
 }
 
+/// Directory name, relative to an ebuild's own directory, that holds package-local auxiliary
+/// files under the Gentoo `FILESDIR` convention.
+const FILESDIR_NAME: &str = "files";
+
+/// Compiles a shell-style glob pattern (`*` for any run of characters, `?` for exactly one) into
+/// a [`Regex`] anchored to match the whole string, for matching against [`BazelSpecificMetadata`]
+/// `auto_sources_exclude` entries.
+fn compile_glob(pattern: &str) -> Result<Regex> {
+    let mut regex_str = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex_str.push_str(".*"),
+            '?' => regex_str.push('.'),
+            c => regex_str.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex_str.push('$');
+    Regex::new(&regex_str).with_context(|| format!("Invalid auto_sources_exclude glob: {pattern}"))
+}
+
 /// Defines the Bazel table found in a single TOML file.
 ///
 /// This is the actual format that users will specify in the TOML file. This
@@ -178,11 +478,54 @@ impl BazelSpecificMetadata {
 /// holding the merged results.
 #[derive(Clone, Debug, Default, Eq, Deserialize, PartialEq)]
 struct SingleBazelSpecificMetadata {
-    extra_sources: Option<Vec<String>>,
+    extra_sources: Option<ListField<String>>,
     supports_interface_libraries: Option<BashExpr>,
-    interface_library_allowlist: Option<Vec<PathBuf>>,
+    interface_library_allowlist: Option<ListField<PathBuf>>,
+    extra_sources_if: Option<Vec<ConditionalExtraSources>>,
+    interface_library_allowlist_if: Option<Vec<ConditionalInterfaceLibraryAllowlist>>,
+    auto_sources: Option<bool>,
+    #[serde(default = "Vec::new")]
+    auto_sources_exclude: Vec<String>,
+    inhibit_implicit_system: Option<bool>,
+
+    /// When `true`, discards everything accumulated from less specific config files (overlay
+    /// defaults, category defaults, and eclasses processed so far) before this file's own
+    /// settings are applied. Lets a package opt out of an inherited default entirely instead of
+    /// only being able to tighten it by unioning/ANDing more onto it.
+    reset: Option<bool>,
+}
+
+/// One entry of `[[bazel.extra_sources_if]]`: a set of extra source labels to add when
+/// `condition` evaluates to true.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+struct ConditionalExtraSources {
+    condition: BashExpr,
+    labels: Vec<String>,
+}
+
+/// One entry of `[[bazel.interface_library_allowlist_if]]`: a set of allowlisted paths to add
+/// when `condition` evaluates to true.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+struct ConditionalInterfaceLibraryAllowlist {
+    condition: BashExpr,
+    paths: Vec<PathBuf>,
 }
 
+/// Keys recognized in the `[bazel]` table, used to detect typos such as
+/// `extra_source` or `support_interface_libraries` that would otherwise
+/// silently parse to nothing.
+const BAZEL_METADATA_KEYS: &[&str] = &[
+    "extra_sources",
+    "supports_interface_libraries",
+    "interface_library_allowlist",
+    "extra_sources_if",
+    "interface_library_allowlist_if",
+    "auto_sources",
+    "auto_sources_exclude",
+    "inhibit_implicit_system",
+    "reset",
+];
+
 /// Defines the TOML metadata file format.
 #[derive(Clone, Debug, Default, Eq, Deserialize, PartialEq)]
 struct TomlMetadata {
@@ -190,20 +533,55 @@ struct TomlMetadata {
 }
 
 impl BazelSpecificMetadata {
-    pub fn load(ebuild_basic_data: &EBuildBasicData, eclass_paths: &[&Path]) -> Result<Self> {
-        // Compute config paths.
-        let ebuild_config_path = ebuild_basic_data
+    /// Loads and merges the Bazel-specific metadata from the overlay's and category's default
+    /// config files, the ebuild's TOML file, and those of its eclasses.
+    ///
+    /// Configs are merged in increasing order of specificity, each one able to add onto, subtract
+    /// from (via a field's `remove`), or (via `reset = true` or a field's `inherit = false` /
+    /// `clear = true`) wipe out everything merged so far:
+    ///
+    /// 1. `metadata/bazel-defaults.toml` at the overlay root, for repository-wide defaults.
+    /// 2. `<category>/metadata.toml`, for category-wide defaults.
+    /// 3. Each inherited eclass's `<eclass>.toml`, in inherit order.
+    /// 4. The ebuild's own `<package>.toml`.
+    ///
+    /// Besides the merged metadata, returns human-readable warnings for
+    /// unrecognized keys found in the `[bazel]` tables, e.g. misspellings.
+    /// Unlike parse errors, unknown keys never abort the load: the offending
+    /// config is still parsed with those keys ignored.
+    pub fn load(
+        ebuild_basic_data: &EBuildBasicData,
+        eclass_paths: &[&Path],
+    ) -> Result<(Self, Vec<String>)> {
+        // Compute config paths, from least to most specific.
+        let package_dir = ebuild_basic_data
             .ebuild_path
             .parent()
-            .expect("non-empty ebuild file path")
-            .join(format!("{}.toml", ebuild_basic_data.short_package_name));
+            .expect("non-empty ebuild file path");
+        let category_dir = package_dir
+            .parent()
+            .expect("ebuild path has a category directory");
+        let overlay_dir = category_dir
+            .parent()
+            .expect("ebuild path has an overlay directory");
+
+        let overlay_defaults_path = overlay_dir.join("metadata").join("bazel-defaults.toml");
+        let category_defaults_path = category_dir.join("metadata.toml");
+        let ebuild_config_path =
+            package_dir.join(format!("{}.toml", ebuild_basic_data.short_package_name));
         let eclass_config_paths = eclass_paths
             .iter()
             .map(|eclass_path| eclass_path.with_extension("toml"));
-        let config_paths = eclass_config_paths.chain(std::iter::once(ebuild_config_path));
+
+        let config_paths = [overlay_defaults_path, category_defaults_path]
+            .into_iter()
+            .chain(eclass_config_paths)
+            .chain(std::iter::once(ebuild_config_path));
 
         // Load configs.
         let mut merged_metadata: BazelSpecificMetadata = Default::default();
+        let mut warnings: Vec<String> = Vec::new();
+        let mut seen_warnings: HashSet<String> = HashSet::new();
         for config_path in config_paths {
             let toml_content = match std::fs::read_to_string(&config_path) {
                 Ok(toml_content) => toml_content,
@@ -213,24 +591,68 @@ impl BazelSpecificMetadata {
                 }
             };
 
-            let metadata: TomlMetadata = toml::from_str(&toml_content)
+            let raw_value: toml::Value = toml::from_str(&toml_content)
                 .with_context(|| format!("Failed to parse {}", config_path.display()))?;
-            merged_metadata.merge(metadata);
+            if let Some(bazel_table) = raw_value.get("bazel").and_then(toml::Value::as_table) {
+                for key in bazel_table.keys() {
+                    if BAZEL_METADATA_KEYS.contains(&key.as_str()) {
+                        continue;
+                    }
+                    let warning = format!(
+                        "{}: unknown key `{}` in [bazel] table",
+                        config_path.display(),
+                        key
+                    );
+                    if seen_warnings.insert(warning.clone()) {
+                        warnings.push(warning);
+                    }
+                }
+            }
+
+            let metadata: TomlMetadata = raw_value
+                .try_into()
+                .with_context(|| format!("Failed to parse {}", config_path.display()))?;
+            merged_metadata.merge(&config_path, metadata);
         }
 
-        Ok(merged_metadata)
+        merged_metadata.auto_discover_sources(
+            &ebuild_basic_data.repo_name,
+            overlay_dir,
+            package_dir,
+        )?;
+
+        Ok((merged_metadata, warnings))
     }
 
-    fn merge(&mut self, other: TomlMetadata) {
+    fn merge(&mut self, config_path: &Path, other: TomlMetadata) {
         if let Some(other) = other.bazel {
+            if other.reset == Some(true) {
+                *self = Default::default();
+            }
             if let Some(extra_sources) = other.extra_sources {
-                self.extra_sources.extend(extra_sources);
+                extra_sources.apply(&mut self.extra_sources);
+            }
+            if let Some(expr) = other.supports_interface_libraries {
+                self.supports_interface_libraries
+                    .push((config_path.to_path_buf(), expr));
             }
-            self.supports_interface_libraries
-                .extend(other.supports_interface_libraries);
             if let Some(interface_library_allowlist) = other.interface_library_allowlist {
-                self.interface_library_allowlist
-                    .extend(interface_library_allowlist);
+                interface_library_allowlist.apply(&mut self.interface_library_allowlist);
+            }
+            for entry in other.extra_sources_if.into_iter().flatten() {
+                self.conditional_extra_sources
+                    .push((entry.condition, entry.labels.into_iter().collect()));
+            }
+            for entry in other.interface_library_allowlist_if.into_iter().flatten() {
+                self.conditional_interface_library_allowlist
+                    .push((entry.condition, entry.paths.into_iter().collect()));
+            }
+            if let Some(auto_sources) = other.auto_sources {
+                self.auto_sources = Some(auto_sources);
+            }
+            self.auto_sources_exclude.extend(other.auto_sources_exclude);
+            if let Some(inhibit_implicit_system) = other.inhibit_implicit_system {
+                self.inhibit_implicit_system = Some(inhibit_implicit_system);
             }
         }
     }
@@ -247,6 +669,11 @@ pub struct PackageDetails {
     pub inherit_paths: Vec<PathBuf>,
     pub direct_build_target: Option<String>,
     pub bazel_metadata: BazelSpecificMetadata,
+    /// Warnings collected while loading the package, e.g. unrecognized keys
+    /// in `[bazel]` metadata TOML files. These are surfaced even when
+    /// [`Self::readiness`] is [`PackageReadiness::Ok`] so tooling can print
+    /// them without failing the load.
+    pub warnings: Vec<String>,
 }
 
 impl PackageDetails {
@@ -374,7 +801,7 @@ impl PackageLoader {
 
     /// Loads a package information from a specified ebuild path.
     pub fn load_package(&self, ebuild_path: &Path) -> Result<MaybePackageDetails> {
-        let metadata = self.evaluator.evaluate_metadata(ebuild_path)?;
+        let metadata = self.evaluator.evaluate_metadata(ebuild_path, &self.config)?;
 
         // Don't abort on package parse failures.
         match self.parse_package(metadata.clone()) {
@@ -488,7 +915,7 @@ impl PackageLoader {
                 }
             });
 
-        let bazel_metadata = BazelSpecificMetadata::load(
+        let (bazel_metadata, warnings) = BazelSpecificMetadata::load(
             metadata.as_basic_data(),
             &inherit_paths.iter().map(|p| p.as_path()).collect_vec(),
         )?;
@@ -503,6 +930,7 @@ impl PackageLoader {
             inherit_paths,
             direct_build_target,
             bazel_metadata,
+            warnings,
         })
     }
 }
@@ -606,6 +1034,7 @@ KEYWORDS="*"
         assert_eq!(details.inherited, HashSet::new());
         assert_eq!(details.inherit_paths, Vec::<PathBuf>::new());
         assert_eq!(details.direct_build_target, None);
+        assert_eq!(details.warnings, Vec::<String>::new());
     }
 
     #[test]
@@ -804,8 +1233,17 @@ REQUIRED_USE="|| ( foo !bar )"
                     "//scripts:sources".into(),
                     "@chromite//:sources".into(),
                 ]),
-                supports_interface_libraries: vec![BashExpr::from_str("true")?],
+                conditional_extra_sources: vec![],
+                supports_interface_libraries: vec![(
+                    ebuild_dir.join("hello.toml"),
+                    BashExpr::from_str("true")?
+                )],
                 interface_library_allowlist: HashSet::from([]),
+                conditional_interface_library_allowlist: vec![],
+                auto_sources: None,
+                auto_sources_exclude: vec![],
+                auto_discovered_sources: HashSet::from([]),
+                inhibit_implicit_system: None,
             }
         );
         Ok(())
@@ -839,19 +1277,46 @@ KEYWORDS="*"
         matches!(maybe_details, MaybePackageDetails::Err(_));
     }
 
+    /// Returns the merged metadata and warnings, plus the directories the TOML fixtures were
+    /// written to, so callers can build the paths expected in `supports_interface_libraries`
+    /// provenance (`ebuild_dir.join("hello.toml")`, `eclass_dir.join("<key>.toml")`, ...).
     fn write_toml(
         package_toml: &str,
         eclass_toml: &[(&str, &str)],
-    ) -> Result<BazelSpecificMetadata> {
+    ) -> Result<(BazelSpecificMetadata, Vec<String>, PathBuf, PathBuf)> {
+        write_toml_with_defaults(None, None, package_toml, eclass_toml)
+    }
+
+    /// Like [`write_toml`], but also writes an overlay-level `metadata/bazel-defaults.toml` and/or
+    /// a category-level `sys-apps/metadata.toml` when given.
+    fn write_toml_with_defaults(
+        overlay_defaults_toml: Option<&str>,
+        category_defaults_toml: Option<&str>,
+        package_toml: &str,
+        eclass_toml: &[(&str, &str)],
+    ) -> Result<(BazelSpecificMetadata, Vec<String>, PathBuf, PathBuf)> {
         let temp_dir = TempDir::new()?;
         let temp_dir = temp_dir.path();
 
-        let ebuild_dir = temp_dir.join("sys-apps/hello");
+        let category_dir = temp_dir.join("sys-apps");
+        let ebuild_dir = category_dir.join("hello");
         std::fs::create_dir_all(&ebuild_dir)?;
 
         let ebuild_path = ebuild_dir.join("hello-1.0.ebuild");
         std::fs::write(ebuild_dir.join("hello.toml"), package_toml)?;
 
+        if let Some(overlay_defaults_toml) = overlay_defaults_toml {
+            let metadata_dir = temp_dir.join("metadata");
+            std::fs::create_dir_all(&metadata_dir)?;
+            std::fs::write(
+                metadata_dir.join("bazel-defaults.toml"),
+                overlay_defaults_toml,
+            )?;
+        }
+        if let Some(category_defaults_toml) = category_defaults_toml {
+            std::fs::write(category_dir.join("metadata.toml"), category_defaults_toml)?;
+        }
+
         let eclass_dir = temp_dir.join("eclass");
         std::fs::create_dir_all(&eclass_dir)?;
         let mut eclass_paths = vec![];
@@ -862,7 +1327,7 @@ KEYWORDS="*"
             eclass_paths.push(eclass_path);
         }
 
-        BazelSpecificMetadata::load(
+        let (metadata, warnings) = BazelSpecificMetadata::load(
             &EBuildBasicData {
                 repo_name: "repo".to_string(),
                 ebuild_path,
@@ -872,18 +1337,26 @@ KEYWORDS="*"
                 version: Version::from_str("1.0")?,
             },
             &eclass_paths.iter().map(|p| p.as_path()).collect_vec(),
-        )
+        )?;
+
+        Ok((metadata, warnings, ebuild_dir, eclass_dir))
     }
 
     #[test]
     fn test_empty_toml_parsing() -> Result<()> {
         let metadata = BazelSpecificMetadata {
             extra_sources: HashSet::from([]),
+            conditional_extra_sources: vec![],
             supports_interface_libraries: vec![],
             interface_library_allowlist: HashSet::from([]),
+            conditional_interface_library_allowlist: vec![],
+            auto_sources: None,
+            auto_sources_exclude: vec![],
+            auto_discovered_sources: HashSet::from([]),
+            inhibit_implicit_system: None,
         };
 
-        assert_eq!(write_toml("", &[])?, metadata);
+        assert_eq!(write_toml("", &[])?.0, metadata);
 
         assert!(metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
         Ok(())
@@ -891,40 +1364,56 @@ KEYWORDS="*"
 
     #[test]
     fn test_bool_toml_parsing() -> Result<()> {
-        let metadata = BazelSpecificMetadata {
-            extra_sources: HashSet::from([]),
-            supports_interface_libraries: vec![BashExpr::from_str("false")?],
-            interface_library_allowlist: HashSet::from([]),
-        };
-
-        assert_eq!(
-            write_toml(
-                r#"
+        let (metadata, _, ebuild_dir, _) = write_toml(
+            r#"
 [bazel]
 supports_interface_libraries = false
                 "#,
-                &[]
-            )?,
-            metadata
+            &[],
+        )?;
+        assert_eq!(
+            metadata,
+            BazelSpecificMetadata {
+                extra_sources: HashSet::from([]),
+                conditional_extra_sources: vec![],
+                supports_interface_libraries: vec![(
+                    ebuild_dir.join("hello.toml"),
+                    BashExpr::from_str("false")?
+                )],
+                interface_library_allowlist: HashSet::from([]),
+                conditional_interface_library_allowlist: vec![],
+                auto_sources: None,
+                auto_sources_exclude: vec![],
+                auto_discovered_sources: HashSet::from([]),
+                inhibit_implicit_system: None,
+            }
         );
 
         assert!(!metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
 
-        let metadata = BazelSpecificMetadata {
-            extra_sources: HashSet::from([]),
-            supports_interface_libraries: vec![BashExpr::from_str("true")?],
-            interface_library_allowlist: HashSet::from([]),
-        };
-
-        assert_eq!(
-            write_toml(
-                r#"
+        let (metadata, _, ebuild_dir, _) = write_toml(
+            r#"
 [bazel]
 supports_interface_libraries = true
                 "#,
-                &[]
-            )?,
-            metadata
+            &[],
+        )?;
+        assert_eq!(
+            metadata,
+            BazelSpecificMetadata {
+                extra_sources: HashSet::from([]),
+                conditional_extra_sources: vec![],
+                supports_interface_libraries: vec![(
+                    ebuild_dir.join("hello.toml"),
+                    BashExpr::from_str("true")?
+                )],
+                interface_library_allowlist: HashSet::from([]),
+                conditional_interface_library_allowlist: vec![],
+                auto_sources: None,
+                auto_sources_exclude: vec![],
+                auto_discovered_sources: HashSet::from([]),
+                inhibit_implicit_system: None,
+            }
         );
 
         assert!(metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
@@ -934,21 +1423,29 @@ supports_interface_libraries = true
 
     #[test]
     fn test_str_toml_parsing() -> Result<()> {
-        let metadata = BazelSpecificMetadata {
-            extra_sources: HashSet::from([]),
-            supports_interface_libraries: vec![BashExpr::from_str("use !static")?],
-            interface_library_allowlist: HashSet::from([]),
-        };
-
-        assert_eq!(
-            write_toml(
-                r#"
+        let (metadata, _, ebuild_dir, _) = write_toml(
+            r#"
 [bazel]
 supports_interface_libraries = "use !static"
                 "#,
-                &[]
-            )?,
-            metadata
+            &[],
+        )?;
+        assert_eq!(
+            metadata,
+            BazelSpecificMetadata {
+                extra_sources: HashSet::from([]),
+                conditional_extra_sources: vec![],
+                supports_interface_libraries: vec![(
+                    ebuild_dir.join("hello.toml"),
+                    BashExpr::from_str("use !static")?
+                )],
+                interface_library_allowlist: HashSet::from([]),
+                conditional_interface_library_allowlist: vec![],
+                auto_sources: None,
+                auto_sources_exclude: vec![],
+                auto_discovered_sources: HashSet::from([]),
+                inhibit_implicit_system: None,
+            }
         );
 
         assert!(!metadata
@@ -962,53 +1459,67 @@ supports_interface_libraries = "use !static"
 
     #[test]
     fn test_toml_overrides() -> Result<()> {
-        let metadata = BazelSpecificMetadata {
-            extra_sources: HashSet::from([]),
-            supports_interface_libraries: vec![BashExpr::from_str("true")?],
-            interface_library_allowlist: HashSet::from([]),
-        };
-
-        assert_eq!(
-            write_toml(
-                "",
-                &[(
-                    "foo",
-                    r#"
+        let (metadata, _, _, eclass_dir) = write_toml(
+            "",
+            &[(
+                "foo",
+                r#"
 [bazel]
 supports_interface_libraries = true
-"#
-                )]
-            )?,
-            metadata
+"#,
+            )],
+        )?;
+        assert_eq!(
+            metadata,
+            BazelSpecificMetadata {
+                extra_sources: HashSet::from([]),
+                conditional_extra_sources: vec![],
+                supports_interface_libraries: vec![(
+                    eclass_dir.join("foo.toml"),
+                    BashExpr::from_str("true")?
+                )],
+                interface_library_allowlist: HashSet::from([]),
+                conditional_interface_library_allowlist: vec![],
+                auto_sources: None,
+                auto_sources_exclude: vec![],
+                auto_discovered_sources: HashSet::from([]),
+                inhibit_implicit_system: None,
+            }
         );
 
         assert!(metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
 
-        let metadata = BazelSpecificMetadata {
-            extra_sources: HashSet::from([]),
-            supports_interface_libraries: vec![
-                BashExpr::from_str("true")?,
-                BashExpr::from_str("false")?,
-            ],
-            interface_library_allowlist: HashSet::from([]),
-        };
-
         // Verify packages can override the eclasses.
-        assert_eq!(
-            write_toml(
-                r#"
+        let (metadata, _, ebuild_dir, eclass_dir) = write_toml(
+            r#"
                 [bazel]
 supports_interface_libraries = false
                 "#,
-                &[(
-                    "foo",
-                    r#"
+            &[(
+                "foo",
+                r#"
 [bazel]
 supports_interface_libraries = true
-"#
-                )]
-            )?,
-            metadata
+"#,
+            )],
+        )?;
+        assert_eq!(
+            metadata,
+            BazelSpecificMetadata {
+                extra_sources: HashSet::from([]),
+                conditional_extra_sources: vec![],
+                // Eclass configs are merged before the ebuild's own config.
+                supports_interface_libraries: vec![
+                    (eclass_dir.join("foo.toml"), BashExpr::from_str("true")?),
+                    (ebuild_dir.join("hello.toml"), BashExpr::from_str("false")?),
+                ],
+                interface_library_allowlist: HashSet::from([]),
+                conditional_interface_library_allowlist: vec![],
+                auto_sources: None,
+                auto_sources_exclude: vec![],
+                auto_discovered_sources: HashSet::from([]),
+                inhibit_implicit_system: None,
+            }
         );
 
         assert!(!metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
@@ -1016,16 +1527,156 @@ supports_interface_libraries = true
         Ok(())
     }
 
+    #[test]
+    fn test_supports_interface_libraries_anded() -> Result<()> {
+        // A disabling eclass is ANDed with the ebuild's own re-enable: the eclass's `false`
+        // wins regardless, since the ebuild can't unilaterally override it.
+        let (metadata, _, _, _) = write_toml(
+            r#"
+[bazel]
+supports_interface_libraries = true
+"#,
+            &[(
+                "foo",
+                r#"
+[bazel]
+supports_interface_libraries = false
+"#,
+            )],
+        )?;
+
+        assert!(!metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
+
+        // explain_interface_libraries names whichever declaration is responsible for the
+        // `false`, here the ebuild since it's the only declaration.
+        let (metadata, _, ebuild_dir, _) = write_toml(
+            r#"
+[bazel]
+supports_interface_libraries = "use !static"
+"#,
+            &[],
+        )?;
+
+        let (explain_path, _) = metadata
+            .explain_interface_libraries(&HashMap::from([("static".to_owned(), true)]))?
+            .expect("expression should evaluate to false");
+        assert_eq!(explain_path, ebuild_dir.join("hello.toml"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_supports_interface_libraries_boolean_grammar() -> Result<()> {
+        // The full BashExpr grammar -- &&, ||, parens, and any-of/all-of -- is available here
+        // too, not just bare bools and a single `use` token.
+        let (metadata, _, _, _) = write_toml(
+            r#"
+[bazel]
+supports_interface_libraries = "any-of ( use cross all-of ( use !static use arm ) )"
+"#,
+            &[],
+        )?;
+
+        assert!(metadata.eval_supports_interface_libraries(&HashMap::from([(
+            "cross".to_owned(),
+            true
+        )]))?);
+        assert!(!metadata.eval_supports_interface_libraries(&HashMap::from([
+            ("static".to_owned(), true),
+            ("arm".to_owned(), true),
+        ]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_directive_remove() -> Result<()> {
+        // A leaf ebuild can subtract a source wrongly contributed by a broad eclass.
+        let (metadata, _, _, _) = write_toml(
+            r#"
+[bazel]
+extra_sources = { remove = ["//scripts:unwanted"] }
+"#,
+            &[(
+                "foo",
+                r#"
+[bazel]
+extra_sources = ["//scripts:wanted", "//scripts:unwanted"]
+"#,
+            )],
+        )?;
+
+        assert_eq!(
+            metadata.extra_sources,
+            HashSet::from(["//scripts:wanted".to_string()])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_directive_inherit_false() -> Result<()> {
+        // `inherit = false` discards everything inherited before `add` is applied.
+        let (metadata, _, _, _) = write_toml(
+            r#"
+[bazel]
+extra_sources = { inherit = false, add = ["//scripts:only-this"] }
+"#,
+            &[(
+                "foo",
+                r#"
+[bazel]
+extra_sources = ["//scripts:from-eclass"]
+"#,
+            )],
+        )?;
+
+        assert_eq!(
+            metadata.extra_sources,
+            HashSet::from(["//scripts:only-this".to_string()])
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_directive_clear() -> Result<()> {
+        // `clear = true` is a synonym for `inherit = false` with no `add`.
+        let (metadata, _, _, _) = write_toml(
+            r#"
+[bazel]
+interface_library_allowlist = { clear = true }
+"#,
+            &[(
+                "foo",
+                r#"
+[bazel]
+interface_library_allowlist = ["/usr/lib/foo.a"]
+"#,
+            )],
+        )?;
+
+        assert_eq!(metadata.interface_library_allowlist, HashSet::new());
+
+        Ok(())
+    }
+
     #[test]
     fn test_toml_interface_library_allowlist() -> Result<()> {
         let metadata = BazelSpecificMetadata {
             extra_sources: HashSet::from([]),
+            conditional_extra_sources: vec![],
             supports_interface_libraries: vec![],
             interface_library_allowlist: HashSet::from([
                 PathBuf::from("/usr/lib/baz.a"),
                 PathBuf::from("/usr/lib/foo.a"),
                 PathBuf::from("/usr/lib/bar.a"),
             ]),
+            conditional_interface_library_allowlist: vec![],
+            auto_sources: None,
+            auto_sources_exclude: vec![],
+            auto_discovered_sources: HashSet::from([]),
+            inhibit_implicit_system: None,
         };
 
         assert_eq!(
@@ -1046,10 +1697,305 @@ interface_library_allowlist = [
 ]
 "#
                 )]
-            )?,
+            )?
+            .0,
             metadata
         );
 
         Ok(())
     }
+
+    #[test]
+    fn test_toml_conditional_extra_sources_and_allowlist() -> Result<()> {
+        let (metadata, _, _, _) = write_toml(
+            r#"
+[[bazel.extra_sources_if]]
+condition = "use foo"
+labels = ["//scripts:foo-sources"]
+
+[[bazel.interface_library_allowlist_if]]
+condition = "use !foo"
+paths = ["/usr/lib/bar.a"]
+"#,
+            &[(
+                "eclass",
+                r#"
+[[bazel.extra_sources_if]]
+condition = "use bar"
+labels = ["//scripts:bar-sources"]
+"#,
+            )],
+        )?;
+
+        assert_eq!(
+            metadata.eval_extra_sources(&HashMap::from([("foo".into(), true)]))?,
+            HashSet::from(["//scripts:foo-sources".to_string()])
+        );
+        assert_eq!(
+            metadata.eval_extra_sources(&HashMap::from([
+                ("foo".into(), false),
+                ("bar".into(), true)
+            ]))?,
+            HashSet::from(["//scripts:bar-sources".to_string()])
+        );
+        assert_eq!(
+            metadata.eval_interface_library_allowlist(&HashMap::from([("foo".into(), false)]))?,
+            HashSet::from([PathBuf::from("/usr/lib/bar.a")])
+        );
+        assert_eq!(
+            metadata.eval_interface_library_allowlist(&HashMap::from([("foo".into(), true)]))?,
+            HashSet::new()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_defaults_precedence() -> Result<()> {
+        // Overlay defaults set the baseline, and the category's list is unioned onto it: both are
+        // merged before the eclass and the ebuild's own config.
+        let (metadata, _, _, _) = write_toml_with_defaults(
+            Some(
+                r#"
+[bazel]
+supports_interface_libraries = true
+interface_library_allowlist = ["/usr/lib/overlay-default.a"]
+"#,
+            ),
+            Some(
+                r#"
+[bazel]
+interface_library_allowlist = ["/usr/lib/category-default.a"]
+"#,
+            ),
+            r#"
+[bazel]
+extra_sources = ["//scripts:hello-sources"]
+"#,
+            &[(
+                "foo",
+                r#"
+[bazel]
+extra_sources = ["//scripts:foo-sources"]
+"#,
+            )],
+        )?;
+
+        assert_eq!(
+            metadata.extra_sources,
+            HashSet::from([
+                "//scripts:hello-sources".to_string(),
+                "//scripts:foo-sources".to_string(),
+            ])
+        );
+        assert_eq!(
+            metadata.interface_library_allowlist,
+            HashSet::from([
+                PathBuf::from("/usr/lib/overlay-default.a"),
+                PathBuf::from("/usr/lib/category-default.a"),
+            ])
+        );
+        assert!(metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_reset_overrides_defaults() -> Result<()> {
+        // The ebuild's own `reset = true` clears everything inherited from the overlay defaults,
+        // category defaults, and eclasses, leaving only what it sets itself.
+        let (metadata, _, _, _) = write_toml_with_defaults(
+            Some(
+                r#"
+[bazel]
+supports_interface_libraries = true
+interface_library_allowlist = ["/usr/lib/overlay-default.a"]
+"#,
+            ),
+            None,
+            r#"
+[bazel]
+reset = true
+interface_library_allowlist = ["/usr/lib/hello.a"]
+"#,
+            &[(
+                "foo",
+                r#"
+[bazel]
+extra_sources = ["//scripts:foo-sources"]
+"#,
+            )],
+        )?;
+
+        assert_eq!(metadata.extra_sources, HashSet::new());
+        assert_eq!(
+            metadata.interface_library_allowlist,
+            HashSet::from([PathBuf::from("/usr/lib/hello.a")])
+        );
+        assert!(metadata.eval_supports_interface_libraries(&HashMap::from([]))?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_unknown_key_warnings() -> Result<()> {
+        let (_, warnings, _, _) = write_toml(
+            r#"
+[bazel]
+extra_source = ["//scripts:sources"]
+"#,
+            &[(
+                "foo",
+                r#"
+[bazel]
+support_interface_libraries = true
+"#,
+            )],
+        )?;
+
+        // Eclass configs are loaded before the ebuild's own config.
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0]
+            .ends_with("foo.toml: unknown key `support_interface_libraries` in [bazel] table"));
+        assert!(warnings[1].ends_with("hello.toml: unknown key `extra_source` in [bazel] table"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_toml_unknown_key_warnings_deduped() -> Result<()> {
+        let (_, warnings, _, _) = write_toml(
+            r#"
+[bazel]
+extra_source = ["//scripts:sources"]
+"#,
+            &[
+                (
+                    "foo",
+                    r#"
+[bazel]
+extra_source = ["//scripts:other"]
+"#,
+                ),
+                (
+                    "foo",
+                    r#"
+[bazel]
+extra_source = ["//scripts:other"]
+"#,
+                ),
+            ],
+        )?;
+
+        // "foo" is inherited twice here (e.g. diamond inheritance), so its unknown-key warning
+        // would otherwise be reported twice with identical text; it must be deduped down to one,
+        // leaving the ebuild's own distinct warning as the only other entry.
+        assert_eq!(warnings.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_discover_sources() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_dir = temp_dir.path();
+
+        let category_dir = temp_dir.join("sys-apps");
+        let ebuild_dir = category_dir.join("hello");
+        std::fs::create_dir_all(ebuild_dir.join("files/nested"))?;
+        std::fs::write(ebuild_dir.join("hello.toml"), "")?;
+        std::fs::write(ebuild_dir.join("files/hello.conf"), "")?;
+        std::fs::write(ebuild_dir.join("files/nested/hello.patch"), "")?;
+        std::fs::write(ebuild_dir.join("0001-fix-build.patch"), "")?;
+        // Not under `files/` and not a `.patch` file directly in the ebuild dir, so this should
+        // never be picked up by auto-discovery.
+        std::fs::write(ebuild_dir.join("hello-1.0.ebuild"), "")?;
+
+        let ebuild_path = ebuild_dir.join("hello-1.0.ebuild");
+        let (metadata, _) = BazelSpecificMetadata::load(
+            &EBuildBasicData {
+                repo_name: "repo".to_string(),
+                ebuild_path,
+                package_name: "sys-apps/hello".into(),
+                short_package_name: "hello".into(),
+                category_name: "sys-apps".into(),
+                version: Version::from_str("1.0")?,
+            },
+            &[],
+        )?;
+
+        assert_eq!(
+            metadata.extra_sources,
+            HashSet::from([
+                "//internal/overlays/repo/sys-apps/hello/files:hello.conf".to_string(),
+                "//internal/overlays/repo/sys-apps/hello/files/nested:hello.patch".to_string(),
+                "//internal/overlays/repo/sys-apps/hello:0001-fix-build.patch".to_string(),
+            ])
+        );
+        assert_eq!(metadata.extra_sources, metadata.auto_discovered_sources);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_discover_sources_exclude_and_opt_out() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let temp_dir = temp_dir.path();
+
+        let category_dir = temp_dir.join("sys-apps");
+        let ebuild_dir = category_dir.join("hello");
+        std::fs::create_dir_all(ebuild_dir.join("files"))?;
+        std::fs::write(
+            ebuild_dir.join("hello.toml"),
+            r#"
+[bazel]
+auto_sources_exclude = ["files/*.conf"]
+"#,
+        )?;
+        std::fs::write(ebuild_dir.join("files/hello.conf"), "")?;
+        std::fs::write(ebuild_dir.join("files/hello.data"), "")?;
+
+        let ebuild_path = ebuild_dir.join("hello-1.0.ebuild");
+        let (metadata, _) = BazelSpecificMetadata::load(
+            &EBuildBasicData {
+                repo_name: "repo".to_string(),
+                ebuild_path: ebuild_path.clone(),
+                package_name: "sys-apps/hello".into(),
+                short_package_name: "hello".into(),
+                category_name: "sys-apps".into(),
+                version: Version::from_str("1.0")?,
+            },
+            &[],
+        )?;
+
+        assert_eq!(
+            metadata.extra_sources,
+            HashSet::from(["//internal/overlays/repo/sys-apps/hello/files:hello.data".to_string()])
+        );
+
+        std::fs::write(
+            ebuild_dir.join("hello.toml"),
+            r#"
+[bazel]
+auto_sources = false
+"#,
+        )?;
+
+        let (metadata, _) = BazelSpecificMetadata::load(
+            &EBuildBasicData {
+                repo_name: "repo".to_string(),
+                ebuild_path,
+                package_name: "sys-apps/hello".into(),
+                short_package_name: "hello".into(),
+                category_name: "sys-apps".into(),
+                version: Version::from_str("1.0")?,
+            },
+            &[],
+        )?;
+
+        assert_eq!(metadata.extra_sources, HashSet::new());
+        assert_eq!(metadata.auto_discovered_sources, HashSet::new());
+
+        Ok(())
+    }
 }
@@ -12,7 +12,7 @@ use crate::{
     config::{bundle::ConfigBundle, ProvidedPackage},
     data::UseMap,
     dependency::{
-        package::{AsPackageRef, PackageAtom, PackageDependencyAtom},
+        package::{AsPackageRef, PackageAtom, PackageDependencyAtom, PackageVersionDependency},
         Predicate,
     },
     ebuild::{CachedPackageLoader, MaybePackageDetails, PackageDetails},
@@ -36,6 +36,118 @@ pub fn select_best_version<T: AsPackageRef, I: IntoIterator<Item = T>>(packages:
         .max_by(|a, b| a.as_package_ref().version.cmp(b.as_package_ref().version))
 }
 
+/// Which extreme of the feasible set [`select_version_satisfying`] should prefer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VersionSelectionMode {
+    /// Prefer the highest version satisfying every constraint: Portage's ordinary resolution
+    /// behavior, same as [`select_best_version`] among same-package candidates.
+    Maximal,
+    /// Prefer the lowest version satisfying every constraint, the way cargo's
+    /// `-Z minimal-versions` resolves dependencies: useful for checking that a package's stated
+    /// lower bounds (`>=` atoms) are actually sufficient, rather than happening to work because a
+    /// newer version was picked anyway.
+    Minimal,
+}
+
+/// Picks the version from `candidates` that satisfies every constraint in `constraints`,
+/// preferring the lowest or highest such version according to `mode`.
+///
+/// Returns `None` if no candidate satisfies every constraint; this is a normal, expected outcome
+/// (an empty feasible set), not an error.
+pub fn select_version_satisfying<'a>(
+    constraints: &[PackageVersionDependency],
+    candidates: impl IntoIterator<Item = &'a Version>,
+    mode: VersionSelectionMode,
+) -> Option<&'a Version> {
+    let feasible = candidates
+        .into_iter()
+        .filter(|&version| constraints.iter().all(|constraint| constraint.matches(version)));
+    match mode {
+        VersionSelectionMode::Maximal => feasible.max(),
+        VersionSelectionMode::Minimal => feasible.min(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    /// Parses a version-constrained atom string (e.g. `">=dev-lang/foo-2.0"`) into the
+    /// [`PackageVersionDependency`] it carries.
+    fn constraint(atom: &str) -> PackageVersionDependency {
+        PackageAtom::from_str(atom)
+            .unwrap()
+            .version()
+            .clone()
+            .unwrap_or_else(|| panic!("{atom} has no version constraint"))
+    }
+
+    fn versions(versions: &[&str]) -> Vec<Version> {
+        versions
+            .iter()
+            .map(|v| Version::from_str(v).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn maximal_picks_highest_feasible_version() {
+        let constraints = [constraint(">=dev-lang/foo-1.0")];
+        let candidates = versions(&["0.9", "1.0", "2.0"]);
+        assert_eq!(
+            select_version_satisfying(&constraints, &candidates, VersionSelectionMode::Maximal),
+            Some(&Version::from_str("2.0").unwrap())
+        );
+    }
+
+    #[test]
+    fn minimal_picks_lowest_feasible_version() {
+        let constraints = [constraint(">=dev-lang/foo-1.0")];
+        let candidates = versions(&["0.9", "1.0", "2.0"]);
+        assert_eq!(
+            select_version_satisfying(&constraints, &candidates, VersionSelectionMode::Minimal),
+            Some(&Version::from_str("1.0").unwrap())
+        );
+    }
+
+    #[test]
+    fn empty_feasible_set_returns_none() {
+        let constraints = [constraint(">=dev-lang/foo-5.0")];
+        let candidates = versions(&["0.9", "1.0", "2.0"]);
+        assert_eq!(
+            select_version_satisfying(&constraints, &candidates, VersionSelectionMode::Maximal),
+            None
+        );
+        assert_eq!(
+            select_version_satisfying(&constraints, &candidates, VersionSelectionMode::Minimal),
+            None
+        );
+    }
+
+    #[test]
+    fn no_candidates_returns_none() {
+        let constraints = [constraint(">=dev-lang/foo-1.0")];
+        assert_eq!(
+            select_version_satisfying(&constraints, &[], VersionSelectionMode::Maximal),
+            None
+        );
+    }
+
+    #[test]
+    fn intersects_multiple_constraints() {
+        let constraints = [
+            constraint(">=dev-lang/foo-1.0"),
+            constraint("<dev-lang/foo-3.0"),
+        ];
+        let candidates = versions(&["0.9", "1.0", "2.0", "3.0", "4.0"]);
+        assert_eq!(
+            select_version_satisfying(&constraints, &candidates, VersionSelectionMode::Maximal),
+            Some(&Version::from_str("2.0").unwrap())
+        );
+    }
+}
+
 /// Answers queries related to Portage packages.
 #[derive(Debug)]
 pub struct PackageResolver {
@@ -1,19 +1,81 @@
 // Copyright 2024 The ChromiumOS Authors
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use elf::{
-    abi::{SHN_ABS, STB_LOCAL, STT_NOTYPE, STT_OBJECT, VER_FLG_BASE},
+    abi::{
+        SHN_ABS, STB_GLOBAL, STB_LOCAL, STB_WEAK, STT_NOTYPE, STT_OBJECT, STV_DEFAULT,
+        STV_HIDDEN, STV_INTERNAL, STV_PROTECTED, VER_FLG_BASE,
+    },
     endian::AnyEndian,
     ElfStream,
 };
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
 use std::path::Path;
+use std::process::Command;
+use tempfile::Builder as TempFileBuilder;
 
-#[derive(Debug, Eq, PartialEq)]
-struct Symbol {
+/// ELF symbol binding (`STB_*`), which controls whether a later, weaker definition of the same
+/// name may override this one at link time.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum Binding {
+    /// `STB_GLOBAL`: the one definition rule applies; a duplicate is a link error.
+    Global,
+    /// `STB_WEAK`: a `Global` definition of the same name elsewhere takes priority over this one.
+    Weak,
+}
+
+impl Binding {
+    fn from_st_bind(bind: u8) -> Result<Self> {
+        match bind {
+            STB_GLOBAL => Ok(Binding::Global),
+            STB_WEAK => Ok(Binding::Weak),
+            other => bail!("Unexpected exported symbol binding {other}"),
+        }
+    }
+}
+
+/// ELF symbol visibility (`STV_*`, the low two bits of `st_other`), which controls whether the
+/// symbol can be interposed by another definition of the same name found earlier in the process's
+/// search order.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) enum Visibility {
+    /// `STV_DEFAULT`: normal, interposable visibility.
+    Default,
+    /// `STV_PROTECTED`: exported, but always resolved to this library's own definition, even by
+    /// references from within the same library — not interposable.
+    Protected,
+    /// `STV_HIDDEN`: not exported from the dynamic symbol table at all.
+    Hidden,
+    /// `STV_INTERNAL`: processor-specific, stricter than `Hidden`.
+    Internal,
+}
+
+impl Visibility {
+    fn from_st_other(st_other: u8) -> Result<Self> {
+        match st_other & 0x3 {
+            STV_DEFAULT => Ok(Visibility::Default),
+            STV_PROTECTED => Ok(Visibility::Protected),
+            STV_HIDDEN => Ok(Visibility::Hidden),
+            STV_INTERNAL => Ok(Visibility::Internal),
+            other => bail!("Unexpected exported symbol visibility {other}"),
+        }
+    }
+}
+
+/// Note that `hidden` is unrelated to [`Visibility::Hidden`]: it's a `Verdef` flag meaning "this
+/// version of the symbol isn't the default, unqualified one" (`sym@version` vs `sym@@version`),
+/// whereas `visibility` reflects `st_other` and applies regardless of versioning.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub(crate) struct Symbol {
     name: String,
     version: Option<String>,
     hidden: bool,
+    binding: Binding,
+    visibility: Visibility,
 }
 
 /// Checks if the symbol is external and available for linking
@@ -46,7 +108,7 @@ fn is_exported_symbol(symbol: &elf::symbol::Symbol) -> bool {
 }
 
 /// Returns a list of all the exported symbols in the ELF file.
-fn get_exported_symbols(path: &Path) -> Result<Vec<Symbol>> {
+pub(crate) fn get_exported_symbols(path: &Path) -> Result<Vec<Symbol>> {
     let file = std::fs::File::open(path).with_context(|| format!("open {path:?}"))?;
     let mut elf = ElfStream::<AnyEndian, _>::open_stream(file)
         .with_context(|| format!("{path:?} is not a valid ELF"))?;
@@ -74,6 +136,8 @@ fn get_exported_symbols(path: &Path) -> Result<Vec<Symbol>> {
                         format!("Failed to read symbol name at offset {}", symbol.st_name)
                     })?
                     .to_owned(),
+                Binding::from_st_bind(symbol.st_bind())?,
+                Visibility::from_st_other(symbol.st_other)?,
             ))
         })
         .collect::<Result<Vec<_>>>()?;
@@ -85,17 +149,19 @@ fn get_exported_symbols(path: &Path) -> Result<Vec<Symbol>> {
         eprintln!("{path:?} doesn't have a symbol version table");
         return Ok(exported_symbols
             .into_iter()
-            .map(|(_i, name)| Symbol {
-                name: name,
+            .map(|(_i, name, binding, visibility)| Symbol {
+                name,
                 version: None,
                 hidden: false,
+                binding,
+                visibility,
             })
             .collect());
     };
 
     let mut symbols = vec![];
 
-    for (i, symbol_name) in exported_symbols {
+    for (i, symbol_name, binding, visibility) in exported_symbols {
         let Some(version_definition) = symbol_version_table
             .get_definition(i)
             .with_context(|| format!("Failed parsing definition table"))?
@@ -105,6 +171,8 @@ fn get_exported_symbols(path: &Path) -> Result<Vec<Symbol>> {
                 name: symbol_name,
                 version: None,
                 hidden: false,
+                binding,
+                visibility,
             });
             continue;
         };
@@ -123,6 +191,8 @@ fn get_exported_symbols(path: &Path) -> Result<Vec<Symbol>> {
                     Some(name.to_owned())
                 },
                 hidden: version_definition.hidden,
+                binding,
+                visibility,
             });
         }
     }
@@ -130,6 +200,129 @@ fn get_exported_symbols(path: &Path) -> Result<Vec<Symbol>> {
     Ok(symbols)
 }
 
+/// A symbol a library imports from elsewhere, together with the specific version of it the
+/// library was linked against.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct RequiredSymbol {
+    name: String,
+    version: Option<String>,
+    from_soname: Option<String>,
+}
+
+/// Returns every symbol a library imports from elsewhere, i.e. every `.dynsym` entry that
+/// [`is_exported_symbol`] rejects for being undefined.
+///
+/// Each is joined against the `Verneed`/`Vernaux` (symbol requirement) table to recover the
+/// specific version the library was linked against and the SONAME it expects to provide it,
+/// mirroring how glibc's `do_lookup_versioned` pairs an undefined reference with its
+/// `r_found_version` before resolution. Together with [`get_exported_symbols`], this describes
+/// both sides of a library's ABI: what it provides, and what it consumes.
+pub(crate) fn get_required_symbols(path: &Path) -> Result<Vec<RequiredSymbol>> {
+    let file = std::fs::File::open(path).with_context(|| format!("open {path:?}"))?;
+    let mut elf = ElfStream::<AnyEndian, _>::open_stream(file)
+        .with_context(|| format!("{path:?} is not a valid ELF"))?;
+
+    let Some((dynamic_symbol_table, dynamic_symbol_string_table)) = elf
+        .dynamic_symbol_table()
+        .with_context(|| format!("Failed to parse dynamic symbol table from {path:?}"))?
+    else {
+        eprintln!("{path:?} doesn't have a dynamic symbol table");
+        return Ok(vec![]);
+    };
+
+    // We can't have a `dynamic_symbol_table` and a `symbol_version_table` instantiated
+    // at the same time because they both take a &mut, so we split up the computation.
+    let undefined_symbols = dynamic_symbol_table
+        .into_iter()
+        .enumerate()
+        .filter(|(_i, symbol)| symbol.is_undefined() && symbol.st_name != 0)
+        .map(|(i, symbol)| {
+            Ok((
+                i,
+                dynamic_symbol_string_table
+                    .get(symbol.st_name.try_into().unwrap())
+                    .with_context(|| {
+                        format!("Failed to read symbol name at offset {}", symbol.st_name)
+                    })?
+                    .to_owned(),
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let Some(symbol_version_table) = elf
+        .symbol_version_table()
+        .with_context(|| format!("Failed to parse symbol version table from {path:?}"))?
+    else {
+        eprintln!("{path:?} doesn't have a symbol version table");
+        return Ok(undefined_symbols
+            .into_iter()
+            .map(|(_i, name)| RequiredSymbol {
+                name,
+                version: None,
+                from_soname: None,
+            })
+            .collect());
+    };
+
+    let mut symbols = vec![];
+
+    for (i, symbol_name) in undefined_symbols {
+        let Some(requirement) = symbol_version_table
+            .get_requirement(i)
+            .with_context(|| format!("Failed parsing requirement table"))?
+        else {
+            symbols.push(RequiredSymbol {
+                name: symbol_name,
+                version: None,
+                from_soname: None,
+            });
+            continue;
+        };
+
+        let version = requirement
+            .names
+            .into_iter()
+            .next()
+            .transpose()
+            .with_context(|| {
+                format!("Failed while parsing required version for symbol {i}:{symbol_name}.")
+            })?
+            .map(|name| name.to_owned());
+
+        symbols.push(RequiredSymbol {
+            name: symbol_name,
+            version,
+            from_soname: Some(requirement.file.to_owned()),
+        });
+    }
+
+    Ok(symbols)
+}
+
+/// Renders `symbols` into a stable, newline-delimited manifest of `name\tversion\tfrom_soname`
+/// lines (the latter two empty when unknown), sorted for reproducibility, so a downstream Bazel
+/// rule can diff a library's required symbols against its providers without parsing ELF itself.
+pub(crate) fn render_required_symbols_manifest(symbols: &[RequiredSymbol]) -> String {
+    let mut lines: Vec<String> = symbols
+        .iter()
+        .map(|symbol| {
+            format!(
+                "{}\t{}\t{}",
+                symbol.name,
+                symbol.version.as_deref().unwrap_or(""),
+                symbol.from_soname.as_deref().unwrap_or(""),
+            )
+        })
+        .collect();
+    lines.sort();
+
+    let mut manifest = lines.join("\n");
+    if !manifest.is_empty() {
+        manifest.push('\n');
+    }
+    manifest
+}
+
 /// Checks if the ELF file contains any versioned symbols.
 pub fn has_versioned_symbols(path: &Path) -> Result<bool> {
     Ok(get_exported_symbols(path)?
@@ -137,6 +330,320 @@ pub fn has_versioned_symbols(path: &Path) -> Result<bool> {
         .any(|symbol| symbol.version.is_some()))
 }
 
+/// A stable hash over a library's exported-symbol set, invariant to everything except the ABI
+/// itself: reordering `.dynsym`, or changing implementation bytes without touching the symbol
+/// table, leaves this unchanged.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct AbiFingerprint(pub [u8; 32]);
+
+impl AbiFingerprint {
+    /// A short, BUILD-file-safe hex rendering, suitable for a `genrule` to stamp as a
+    /// cache-discriminating output.
+    pub(crate) fn short_hex(&self) -> String {
+        hex::encode(self.0)[..16].to_owned()
+    }
+}
+
+/// Computes a [`AbiFingerprint`] for the library at `path` from its exported symbol set.
+///
+/// The symbols are sorted by `(name, version, hidden, binding, visibility)` and fed into SHA-256
+/// one at a time as a length-prefixed `name \0 version \0 hidden_flag binding visibility` record.
+/// Sorting makes the fingerprint invariant to symbol ordering in `.dynsym`; the length prefix
+/// makes it immune to delimiter-collision between names and versions, since a `\0` inside either
+/// of those wouldn't otherwise distinguish "a longer name" from "a name followed by the start of
+/// a version". `binding`/`visibility` are included because they're part of the ABI a stub must
+/// preserve (see [`render_stub_attributes`]): a revision that only flips weak vs global binding,
+/// or default vs protected visibility, must not fingerprint identically to the original.
+pub(crate) fn abi_fingerprint(path: &Path) -> Result<AbiFingerprint> {
+    let mut symbols = get_exported_symbols(path)?;
+    symbols.sort();
+
+    let mut hasher = Sha256::new();
+    for symbol in &symbols {
+        let mut record = Vec::new();
+        record.extend_from_slice(symbol.name.as_bytes());
+        record.push(0);
+        record.extend_from_slice(symbol.version.as_deref().unwrap_or("").as_bytes());
+        record.push(0);
+        record.push(symbol.hidden as u8);
+        record.push(symbol.binding as u8);
+        record.push(symbol.visibility as u8);
+
+        hasher.update((record.len() as u64).to_le_bytes());
+        hasher.update(&record);
+    }
+
+    Ok(AbiFingerprint(hasher.finalize().into()))
+}
+
+/// Renders the `__attribute__((...))` prefix (or empty string) needed for a stub definition of
+/// `symbol` to preserve its original [`Binding`] and [`Visibility`], so substituting the stub in
+/// doesn't change link-time resolution for anything still linking against the real library.
+fn render_stub_attributes(symbol: &Symbol) -> String {
+    let mut attrs = vec![];
+    if symbol.binding == Binding::Weak {
+        attrs.push("weak");
+    }
+    if symbol.visibility == Visibility::Protected {
+        attrs.push("visibility(\"protected\")");
+    }
+
+    if attrs.is_empty() {
+        String::new()
+    } else {
+        format!("__attribute__(({})) ", attrs.join(", "))
+    }
+}
+
+/// Renders the C source for a stub object that re-exports every `symbols` entry without any real
+/// implementation.
+///
+/// Each symbol gets its own empty function under a synthetic internal name (to avoid collisions
+/// between multiple versions of the same symbol name), aliased to its real, versioned name via a
+/// `.symver` assembler directive. Unversioned symbols are defined directly under their real name
+/// instead, since they don't need a `.symver` alias. Either way, the stub definition carries
+/// whatever `__attribute__` is needed (see [`render_stub_attributes`]) to preserve the original
+/// symbol's weak/global binding and default/protected visibility.
+fn render_stub_source(symbols: &[Symbol]) -> String {
+    let mut source = String::new();
+    writeln!(
+        source,
+        "// Generated by create_interface_layer. Re-exports the real library's dynamic symbol \
+         table without any of its implementation."
+    )
+    .unwrap();
+
+    for (i, symbol) in symbols.iter().enumerate() {
+        let attributes = render_stub_attributes(symbol);
+        match &symbol.version {
+            None => {
+                writeln!(source, "{attributes}void {}(void) {{}}", symbol.name).unwrap();
+            }
+            Some(version) => {
+                let internal_name = format!("__interface_stub_{i}");
+                let at_sign = if symbol.hidden { "@" } else { "@@" };
+                writeln!(source, "{attributes}void {internal_name}(void) {{}}").unwrap();
+                writeln!(
+                    source,
+                    "__asm__(\".symver {internal_name},{}{at_sign}{version}\");",
+                    symbol.name
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    source
+}
+
+/// Renders a linker version script declaring every distinct version found in `symbols`, or
+/// `None` if none of them are versioned.
+///
+/// Every version is declared as its own node, in first-seen order, each inheriting from the
+/// previous one so the script forms one legal chain. The first node also carries `local: *;` so
+/// that the stub's own internal symbol names (see [`render_stub_source`]) don't leak into the
+/// dynamic symbol table alongside the versioned aliases.
+fn render_version_script(symbols: &[Symbol]) -> Option<String> {
+    let mut versions: Vec<&str> = vec![];
+    let mut names_by_version: std::collections::HashMap<&str, BTreeSet<&str>> = Default::default();
+
+    for symbol in symbols {
+        let Some(version) = &symbol.version else {
+            continue;
+        };
+        names_by_version
+            .entry(version)
+            .or_insert_with(|| {
+                versions.push(version);
+                BTreeSet::new()
+            })
+            .insert(&symbol.name);
+    }
+
+    if versions.is_empty() {
+        return None;
+    }
+
+    let mut script = String::new();
+    let mut parent: Option<&str> = None;
+    for version in versions {
+        writeln!(script, "{version} {{").unwrap();
+        writeln!(script, "  global:").unwrap();
+        for name in &names_by_version[version] {
+            writeln!(script, "    {name};").unwrap();
+        }
+        if parent.is_none() {
+            writeln!(script, "  local:").unwrap();
+            writeln!(script, "    *;").unwrap();
+        }
+        match parent {
+            Some(parent) => {
+                writeln!(script, "}} {parent};").unwrap();
+            }
+            None => {
+                writeln!(script, "}};").unwrap();
+            }
+        }
+        parent = Some(version);
+    }
+
+    Some(script)
+}
+
+/// Serializes `symbols` into a complete `ld`/`lld` `--version-script` (the grammar lld's
+/// `parseVersionScript` consumes), describing the *entire* dynamic symbol table rather than just
+/// the versioned subset. Unlike [`render_version_script`] — which only covers what's needed to
+/// relink a stub produced by [`render_stub_source`] — this always emits a full script, including
+/// an anonymous `{ global: ...; local: *; };` block for any unversioned symbols, so the result can
+/// be fed straight back into a relink to reproduce an identical `VERDEF` table from nothing but
+/// the symbol list.
+///
+/// Symbols are grouped by `version` (`None` becoming the anonymous block) and every group's names
+/// are sorted for reproducibility. Every symbol's real name is listed under its node's `global:`
+/// section regardless of `hidden`: which version is the default is determined by the `@`/`@@`
+/// separator baked into the originating `.symver` directive (see [`render_stub_source`]), not by
+/// version script placement. GNU ld rejects a script that lists the same symbol pattern as
+/// `local:` in one node and `global:` in another ("duplicate expression ... in version
+/// information"), which a literal default/hidden split would do whenever a name is shared across
+/// versions, as it always is for a superseded symbol. `local: *;` is kept on every node purely to
+/// stop anything un-listed, such as helper symbol names, from leaking into the dynamic symbol
+/// table.
+pub(crate) fn render_full_version_script(symbols: &[Symbol]) -> String {
+    let mut names_by_version: BTreeMap<Option<&str>, BTreeSet<&str>> = BTreeMap::new();
+    for symbol in symbols {
+        names_by_version
+            .entry(symbol.version.as_deref())
+            .or_default()
+            .insert(&symbol.name);
+    }
+
+    let mut script = String::new();
+    for (version, names) in &names_by_version {
+        match version {
+            Some(version) => writeln!(script, "{version} {{").unwrap(),
+            None => writeln!(script, "{{").unwrap(),
+        }
+        writeln!(script, "  global:").unwrap();
+        for name in names {
+            writeln!(script, "    {name};").unwrap();
+        }
+        writeln!(script, "  local:").unwrap();
+        writeln!(script, "    *;").unwrap();
+        writeln!(script, "}};").unwrap();
+    }
+
+    script
+}
+
+/// Synthesizes a minimal shared object that exposes the exact same dynamic symbol table ABI as
+/// `symbols` (names, versions, and which version is the default), but with every symbol's body
+/// replaced by a no-op stub.
+///
+/// This lets `BUILD` rules depend on the tiny interface library for ABI changes instead of on the
+/// fully-built shared object, so an implementation-only change doesn't force a rebuild of
+/// everything linked against it.
+pub(crate) fn write_interface_library(symbols: &[Symbol], out: &Path) -> Result<()> {
+    let mut source_file = TempFileBuilder::new()
+        .suffix(".c")
+        .tempfile()
+        .context("Failed to create a temporary file for the interface library stub source")?;
+    std::io::Write::write_all(&mut source_file, render_stub_source(symbols).as_bytes())
+        .context("Failed to write the interface library stub source")?;
+
+    let version_script_file = render_version_script(symbols)
+        .map(|script| -> Result<_> {
+            let mut file = TempFileBuilder::new()
+                .suffix(".map")
+                .tempfile()
+                .context("Failed to create a temporary file for the linker version script")?;
+            std::io::Write::write_all(&mut file, script.as_bytes())
+                .context("Failed to write the linker version script")?;
+            Ok(file)
+        })
+        .transpose()?;
+
+    let mut command = Command::new("cc");
+    command
+        .arg("-shared")
+        .arg("-fPIC")
+        .arg("-nostdlib")
+        .arg("-o")
+        .arg(out);
+    if let Some(version_script_file) = &version_script_file {
+        command.arg(format!(
+            "-Wl,--version-script={}",
+            version_script_file.path().display()
+        ));
+    }
+    command.arg(source_file.path());
+
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {command:?}"))?;
+    ensure!(status.success(), "{command:?} failed: {status:?}");
+
+    Ok(())
+}
+
+/// The result of comparing the dynamic symbol tables of two revisions of the same library.
+///
+/// Only forward compatibility is modeled: whether anything linked against `old` would still
+/// resolve against `new`. Symbols or versions gained by `new` are never a problem for existing
+/// consumers, so they're simply not reported.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct AbiDiff {
+    /// `(name, version)` pairs exported by the old revision that the new one doesn't export at
+    /// all.
+    pub removed: Vec<Symbol>,
+    /// `(name, version)` pairs exported by both revisions, but whose default-ness flipped from
+    /// visible (`sym@@version`) to hidden (`sym@version`). A consumer that resolved the unadorned
+    /// `sym` against this version previously would now be handed a different, newer default
+    /// instead.
+    pub newly_hidden: Vec<Symbol>,
+}
+
+impl AbiDiff {
+    /// Returns whether `new` is a drop-in replacement for `old`: nothing previously exported
+    /// disappeared or silently stopped being the default.
+    pub fn is_compatible(&self) -> bool {
+        self.removed.is_empty() && self.newly_hidden.is_empty()
+    }
+}
+
+/// Compares the ABI of `old` against `new` and reports every breaking change.
+///
+/// Each exported symbol is keyed by `(name, version)`, since versioned symbols exist precisely so
+/// that an old `sym@v1` and a new `sym@@v2` can coexist in the same library (see
+/// `simple_versioned_lib.so` and the `test_simple_versioned_lib` test below, which mirrors glibc's
+/// own versioned-symbol layout). That means dropping `(sym, v1)` is breaking even though `(sym,
+/// v2)` is still present under the same name: anything linked against the specific `v1` ABI can no
+/// longer be satisfied. Symbols or versions `new` adds are never reported, since they can't break
+/// an existing consumer.
+pub fn diff_abi(old: &Path, new: &Path) -> Result<AbiDiff> {
+    let old_symbols = get_exported_symbols(old)?;
+    let new_symbols = get_exported_symbols(new)?;
+
+    let new_by_key: BTreeMap<(&str, Option<&str>), &Symbol> = new_symbols
+        .iter()
+        .map(|symbol| ((symbol.name.as_str(), symbol.version.as_deref()), symbol))
+        .collect();
+
+    let mut diff = AbiDiff::default();
+
+    for old_symbol in &old_symbols {
+        match new_by_key.get(&(old_symbol.name.as_str(), old_symbol.version.as_deref())) {
+            None => diff.removed.push(old_symbol.clone()),
+            Some(new_symbol) => {
+                if !old_symbol.hidden && new_symbol.hidden {
+                    diff.newly_hidden.push(old_symbol.clone());
+                }
+            }
+        }
+    }
+
+    Ok(diff)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -166,7 +673,9 @@ mod tests {
             &[Symbol {
                 name: "hello_world".to_owned(),
                 version: None,
-                hidden: false
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
             },]
         );
         assert!(!has_versioned_symbols(&lib_path)?);
@@ -184,15 +693,410 @@ mod tests {
                     name: "hello_world".to_owned(),
                     version: Some("v2".to_owned()),
                     hidden: false,
+                    binding: Binding::Global,
+                    visibility: Visibility::Default,
                 },
                 Symbol {
                     name: "hello_world".to_owned(),
                     version: Some("v1".to_owned()),
                     hidden: true,
+                    binding: Binding::Global,
+                    visibility: Visibility::Default,
                 },
             ]
         );
         assert!(has_versioned_symbols(&lib_path)?);
         Ok(())
     }
+
+    #[test]
+    fn test_abi_fingerprint_is_invariant_to_symbol_order() {
+        let a = vec![
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v2".to_owned()),
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v1".to_owned()),
+                hidden: true,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+        ];
+        let b = vec![a[1].clone(), a[0].clone()];
+
+        let write_and_fingerprint = |symbols: &[Symbol]| -> Result<AbiFingerprint> {
+            let out = tempfile::Builder::new().suffix(".so").tempfile()?;
+            write_interface_library(symbols, out.path())?;
+            abi_fingerprint(out.path())
+        };
+
+        assert_eq!(
+            write_and_fingerprint(&a).unwrap().0,
+            write_and_fingerprint(&b).unwrap().0
+        );
+    }
+
+    #[test]
+    fn test_abi_fingerprint_changes_with_the_abi() -> Result<()> {
+        let unversioned = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+        let versioned = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: Some("v1".to_owned()),
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+
+        let out_a = tempfile::Builder::new().suffix(".so").tempfile()?;
+        write_interface_library(&unversioned, out_a.path())?;
+        let out_b = tempfile::Builder::new().suffix(".so").tempfile()?;
+        write_interface_library(&versioned, out_b.path())?;
+
+        assert_ne!(
+            abi_fingerprint(out_a.path())?.0,
+            abi_fingerprint(out_b.path())?.0
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_abi_fingerprint_changes_with_binding_and_visibility() -> Result<()> {
+        let global = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+        let weak = vec![Symbol {
+            binding: Binding::Weak,
+            ..global[0].clone()
+        }];
+        let protected = vec![Symbol {
+            visibility: Visibility::Protected,
+            ..global[0].clone()
+        }];
+
+        let write_and_fingerprint = |symbols: &[Symbol]| -> Result<AbiFingerprint> {
+            let out = tempfile::Builder::new().suffix(".so").tempfile()?;
+            write_interface_library(symbols, out.path())?;
+            abi_fingerprint(out.path())
+        };
+
+        let global_fp = write_and_fingerprint(&global)?.0;
+        assert_ne!(global_fp, write_and_fingerprint(&weak)?.0);
+        assert_ne!(global_fp, write_and_fingerprint(&protected)?.0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_required_symbols_simple_lib() -> Result<()> {
+        let lib_path = lookup_runfile("simple_lib.so")?;
+
+        // Built as a minimal stub with no external dependencies, so it shouldn't require
+        // anything.
+        assert!(get_required_symbols(&lib_path)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render_required_symbols_manifest() {
+        let symbols = vec![
+            RequiredSymbol {
+                name: "b_func".to_owned(),
+                version: None,
+                from_soname: None,
+            },
+            RequiredSymbol {
+                name: "a_func".to_owned(),
+                version: Some("GLIBC_2.2.5".to_owned()),
+                from_soname: Some("libc.so.6".to_owned()),
+            },
+        ];
+
+        assert_eq!(
+            render_required_symbols_manifest(&symbols),
+            "a_func\tGLIBC_2.2.5\tlibc.so.6\nb_func\t\t\n"
+        );
+    }
+
+    #[test]
+    fn test_render_full_version_script_unversioned_only() {
+        let symbols = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+
+        assert_eq!(
+            render_full_version_script(&symbols),
+            "{\n  global:\n    hello_world;\n  local:\n    *;\n};\n"
+        );
+    }
+
+    #[test]
+    fn test_render_full_version_script_mixed() {
+        // Mirrors simple_versioned_lib.so's dynamic symbol table plus an extra unversioned
+        // symbol, to exercise the anonymous block alongside named version nodes.
+        let symbols = vec![
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v2".to_owned()),
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v1".to_owned()),
+                hidden: true,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+            Symbol {
+                name: "unrelated".to_owned(),
+                version: None,
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+        ];
+
+        assert_eq!(
+            render_full_version_script(&symbols),
+            "{\n  global:\n    unrelated;\n  local:\n    *;\n};\n\
+             v1 {\n  global:\n    hello_world;\n  local:\n    *;\n};\n\
+             v2 {\n  global:\n    hello_world;\n  local:\n    *;\n};\n"
+        );
+    }
+
+    #[test]
+    fn test_write_interface_library_preserves_weak_binding() -> Result<()> {
+        let symbols = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Weak,
+            visibility: Visibility::Default,
+        }];
+
+        let out = tempfile::Builder::new().suffix(".so").tempfile()?;
+        write_interface_library(&symbols, out.path())?;
+
+        assert_eq!(&get_exported_symbols(out.path())?, &symbols[..]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_interface_library_unversioned() -> Result<()> {
+        let symbols = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+
+        let out = tempfile::Builder::new().suffix(".so").tempfile()?;
+        write_interface_library(&symbols, out.path())?;
+
+        assert_eq!(&get_exported_symbols(out.path())?, &symbols[..]);
+        assert!(!has_versioned_symbols(out.path())?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_interface_library_versioned() -> Result<()> {
+        // Mirrors the dynamic symbol table of simple_versioned_lib.so: a default and a hidden,
+        // superseded version of the same symbol name.
+        let symbols = vec![
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v2".to_owned()),
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v1".to_owned()),
+                hidden: true,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+        ];
+
+        let out = tempfile::Builder::new().suffix(".so").tempfile()?;
+        write_interface_library(&symbols, out.path())?;
+
+        let mut round_tripped = get_exported_symbols(out.path())?;
+        round_tripped.sort_by(|a, b| a.hidden.cmp(&b.hidden));
+        assert_eq!(round_tripped, symbols);
+        assert!(has_versioned_symbols(out.path())?);
+        Ok(())
+    }
+
+    fn write_lib(symbols: &[Symbol]) -> Result<tempfile::NamedTempFile> {
+        let out = tempfile::Builder::new().suffix(".so").tempfile()?;
+        write_interface_library(symbols, out.path())?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_diff_abi_identical_is_compatible() -> Result<()> {
+        let symbols = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+
+        let old = write_lib(&symbols)?;
+        let new = write_lib(&symbols)?;
+
+        let diff = diff_abi(old.path(), new.path())?;
+        assert!(diff.is_compatible());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_abi_detects_removed_symbol() -> Result<()> {
+        let old_symbols = vec![
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: None,
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+            Symbol {
+                name: "goodbye_world".to_owned(),
+                version: None,
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+        ];
+        let new_symbols = vec![old_symbols[0].clone()];
+
+        let old = write_lib(&old_symbols)?;
+        let new = write_lib(&new_symbols)?;
+
+        let diff = diff_abi(old.path(), new.path())?;
+        assert_eq!(&diff.removed, &[old_symbols[1].clone()]);
+        assert!(diff.newly_hidden.is_empty());
+        assert!(!diff.is_compatible());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_abi_dropping_old_version_is_breaking_even_with_newer_version_present() -> Result<()>
+    {
+        // Mirrors simple_versioned_lib.so: a visible v2 default and a hidden, superseded v1.
+        let old_symbols = vec![
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v2".to_owned()),
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v1".to_owned()),
+                hidden: true,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+        ];
+        // The new revision drops v1 entirely, even though hello_world@@v2 is still exported.
+        let new_symbols = vec![old_symbols[0].clone()];
+
+        let old = write_lib(&old_symbols)?;
+        let new = write_lib(&new_symbols)?;
+
+        let diff = diff_abi(old.path(), new.path())?;
+        assert_eq!(&diff.removed, &[old_symbols[1].clone()]);
+        assert!(!diff.is_compatible());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_abi_detects_newly_hidden_version() -> Result<()> {
+        // v1 used to be the default; the new revision keeps exporting it, but demotes it to
+        // hidden in favor of a new v2 default. Anything that resolved the unadorned `hello_world`
+        // against v1 would now get v2 instead.
+        let old_symbols = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: Some("v1".to_owned()),
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+        let new_symbols = vec![
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v2".to_owned()),
+                hidden: false,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+            Symbol {
+                name: "hello_world".to_owned(),
+                version: Some("v1".to_owned()),
+                hidden: true,
+                binding: Binding::Global,
+                visibility: Visibility::Default,
+            },
+        ];
+
+        let old = write_lib(&old_symbols)?;
+        let new = write_lib(&new_symbols)?;
+
+        let diff = diff_abi(old.path(), new.path())?;
+        assert!(diff.removed.is_empty());
+        assert_eq!(&diff.newly_hidden, &[old_symbols[0].clone()]);
+        assert!(!diff.is_compatible());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_abi_added_symbol_is_compatible() -> Result<()> {
+        let old_symbols = vec![Symbol {
+            name: "hello_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        }];
+        let mut new_symbols = old_symbols.clone();
+        new_symbols.push(Symbol {
+            name: "goodbye_world".to_owned(),
+            version: None,
+            hidden: false,
+            binding: Binding::Global,
+            visibility: Visibility::Default,
+        });
+
+        let old = write_lib(&old_symbols)?;
+        let new = write_lib(&new_symbols)?;
+
+        let diff = diff_abi(old.path(), new.path())?;
+        assert!(diff.is_compatible());
+        Ok(())
+    }
 }
@@ -19,12 +19,15 @@ use walkdir::WalkDir;
 
 use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::format;
-use std::fs::{read_link, FileType};
+use std::fs::read_link;
 use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::path::Path;
 use std::{os::unix::process::ExitStatusExt, path::PathBuf, process::ExitCode};
 
-use self::elf::has_versioned_symbols;
+use self::elf::{
+    abi_fingerprint, diff_abi, get_exported_symbols, get_required_symbols, has_versioned_symbols,
+    render_full_version_script, render_required_symbols_manifest, write_interface_library,
+};
 
 const INPUT: &str = "/.input";
 const WORK_LIST: &str = "/.work";
@@ -53,6 +56,14 @@ struct Cli {
     // This path is relative to the sysroot, and must start with a `/`.
     #[arg(long)]
     include: Vec<PathBuf>,
+
+    // A previous revision of the output, as produced by a prior invocation of this program.
+    //
+    // If set, every interface library's ABI is diffed (see `elf::diff_abi`) against the library
+    // at the same path in this tree, and the build fails if anything previously exported was
+    // removed or stopped being the default version.
+    #[arg(long)]
+    previous_output: Option<PathBuf>,
 }
 
 /// Returns true if `path` is inside the `sysroot` and in one of the `dirs`.
@@ -375,16 +386,13 @@ fn create_interface_libraries(
 fn partition_libraries<'a>(
     src_root: &Path,
     libraries: &'a BTreeSet<PathBuf>,
-) -> Result<(BTreeMap<PathBuf, FileType>, BTreeSet<&'a PathBuf>)> {
+) -> Result<(BTreeSet<&'a PathBuf>, BTreeSet<&'a PathBuf>)> {
     let libraries = libraries
         .into_par_iter()
         .map(|library| {
             let path = &src_root.join(library);
             Ok((
                 library,
-                path.metadata()
-                    .with_context(|| format!("stat {path:?}"))?
-                    .file_type(),
                 has_versioned_symbols(path).with_context(|| {
                     format!("Failed to parse {:?}, is it a valid ELF file?", library)
                 })?,
@@ -394,15 +402,113 @@ fn partition_libraries<'a>(
 
     Ok(libraries
         .into_par_iter()
-        .partition_map(|(library, file_type, versioned)| {
+        .partition_map(|(library, versioned)| {
             if versioned {
-                Either::Left((library.clone(), file_type))
+                Either::Left(library)
             } else {
                 Either::Right(library)
             }
         }))
 }
 
+/// Writes a minimal interface stub `.so` for each versioned library, instead of a full copy.
+///
+/// `llvm-ifs` (used by [`create_interface_libraries`] for everything else) can't yet generate
+/// interface libraries for versioned symbols (b/344001490), so this synthesizes the stub itself
+/// via [`write_interface_library`].
+fn create_versioned_interface_libraries(
+    src_root: &Path,
+    dest_root: &Path,
+    libraries: &BTreeSet<&PathBuf>,
+) -> Result<()> {
+    libraries.par_iter().try_for_each(|library| -> Result<()> {
+        let src = src_root.join(library);
+        let dest = dest_root.join(library);
+
+        let symbols = get_exported_symbols(&src)
+            .with_context(|| format!("Failed to parse {:?}, is it a valid ELF file?", library))?;
+        write_interface_library(&symbols, &dest)
+            .with_context(|| format!("Failed to write interface library for {:?}", library))?;
+        copy_metadata(&src, &dest)?;
+
+        Ok(())
+    })
+}
+
+/// Writes ABI metadata sidecar files next to each interface library, derived from the original
+/// (non-stub) library at `src_root`, so downstream Bazel rules can consume them without re-parsing
+/// ELF themselves:
+/// * `<library>.abi-fingerprint`: an [`abi_fingerprint`] short hex digest, for a `genrule` to stamp
+///   as a cache-discriminating output that only changes when the exported ABI does.
+/// * `<library>.version-script`: the library's [`render_full_version_script`], suitable for
+///   feeding back into a relink to reproduce an identical `VERDEF` table.
+/// * `<library>.required-symbols`: the library's [`get_required_symbols`] manifest, describing the
+///   consumed half of its ABI.
+fn write_abi_metadata(
+    src_root: &Path,
+    dest_root: &Path,
+    libraries: &BTreeSet<PathBuf>,
+) -> Result<()> {
+    libraries.par_iter().try_for_each(|library| -> Result<()> {
+        let src = src_root.join(library);
+
+        let fingerprint = abi_fingerprint(&src)
+            .with_context(|| format!("Failed to compute ABI fingerprint for {:?}", library))?;
+        std::fs::write(
+            dest_root.join(format!("{}.abi-fingerprint", library.display())),
+            fingerprint.short_hex(),
+        )
+        .with_context(|| format!("Failed to write ABI fingerprint for {:?}", library))?;
+
+        let symbols = get_exported_symbols(&src)
+            .with_context(|| format!("Failed to parse {:?}, is it a valid ELF file?", library))?;
+        std::fs::write(
+            dest_root.join(format!("{}.version-script", library.display())),
+            render_full_version_script(&symbols),
+        )
+        .with_context(|| format!("Failed to write version script for {:?}", library))?;
+
+        let required = get_required_symbols(&src)
+            .with_context(|| format!("Failed to read required symbols for {:?}", library))?;
+        std::fs::write(
+            dest_root.join(format!("{}.required-symbols", library.display())),
+            render_required_symbols_manifest(&required),
+        )
+        .with_context(|| format!("Failed to write required symbols for {:?}", library))?;
+
+        Ok(())
+    })
+}
+
+/// Verifies every interface library under `new_root` is still ABI-compatible with the library at
+/// the same path under `previous_root`, failing the build instead of silently shipping a
+/// regression in a previously published ABI. Libraries with no counterpart in `previous_root` are
+/// new and have no prior ABI to preserve.
+fn check_abi_compatibility(
+    previous_root: &Path,
+    new_root: &Path,
+    libraries: &BTreeSet<PathBuf>,
+) -> Result<()> {
+    for library in libraries {
+        let previous = previous_root.join(library);
+        if !previous.exists() {
+            continue;
+        }
+
+        let diff = diff_abi(&previous, &new_root.join(library))
+            .with_context(|| format!("Failed to diff ABI of {:?}", library))?;
+        ensure!(
+            diff.is_compatible(),
+            "{:?} broke ABI compatibility: removed={:?}, newly_hidden={:?}",
+            library,
+            diff.removed,
+            diff.newly_hidden,
+        );
+    }
+
+    Ok(())
+}
+
 fn finalize_directory_permissions(
     src_root: &Path,
     dest_root: &Path,
@@ -449,14 +555,10 @@ fn do_main() -> Result<()> {
 
     copy_files(input.path(), &args.output, &work.files_to_copy)?;
 
-    // TODO(b/344001490): When llvm-ifs can generate interface libraries for versioned
-    // symbols, then we can delete this chunk of code.
     let (versioned_libraries, unversioned_libraries) =
         partition_libraries(input.path(), &work.interface_libraries)?;
-    if !versioned_libraries.is_empty() {
-        eprintln!("b/344001490: Can't generate interface libraries for the following because they contain versioned symbols:");
-        copy_files(input.path(), &args.output, &versioned_libraries)?;
-    }
+
+    create_versioned_interface_libraries(input.path(), &args.output, &versioned_libraries)?;
 
     create_interface_libraries(
         &args.common,
@@ -465,6 +567,16 @@ fn do_main() -> Result<()> {
         &unversioned_libraries,
     )?;
 
+    write_abi_metadata(input.path(), &args.output, &work.interface_libraries)?;
+
+    if let Some(previous_output) = &args.previous_output {
+        let mut previous = ContainerSettings::new();
+        previous.push_layer(previous_output)?;
+        let previous = previous.mount()?;
+
+        check_abi_compatibility(previous.path(), &args.output, &work.interface_libraries)?;
+    }
+
     finalize_directory_permissions(input.path(), &args.output, &work.directories_to_create)?;
 
     DurableTree::convert(&args.output)
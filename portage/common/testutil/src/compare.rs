@@ -0,0 +1,333 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! An in-process replacement for shelling out to `cp`/`diff` when comparing
+//! output against golden data. Unlike the external tools, this reports a
+//! structured result that callers can inspect programmatically, not just a
+//! human-readable diff on stdout.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+/// A 1-based line/column position within a text file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Why a pair of files with the same relative path didn't match.
+#[derive(Debug)]
+pub enum Mismatch {
+    /// Both files are valid UTF-8 but differ.
+    Text { at: Location, diff: String },
+    /// At least one file isn't valid UTF-8.
+    Binary { offset: u64 },
+}
+
+/// The structured result of comparing two directory (or file) trees.
+#[derive(Debug, Default)]
+pub struct TreeComparison {
+    /// Relative paths that exist only under the golden tree.
+    pub only_in_golden: Vec<PathBuf>,
+    /// Relative paths that exist only under the output tree.
+    pub only_in_output: Vec<PathBuf>,
+    /// Relative paths that exist in both trees but whose contents differ,
+    /// paired with why they differ.
+    pub mismatches: Vec<(PathBuf, Mismatch)>,
+}
+
+impl TreeComparison {
+    pub fn is_match(&self) -> bool {
+        self.only_in_golden.is_empty() && self.only_in_output.is_empty() && self.mismatches.is_empty()
+    }
+}
+
+/// Recursively lists the relative paths of all regular files under `root`,
+/// following symlinks (mirroring `cp --dereference`'s treatment of them).
+fn list_files(root: &Path) -> Result<BTreeSet<PathBuf>> {
+    let mut files = BTreeSet::new();
+    list_files_impl(root, Path::new(""), &mut files)?;
+    Ok(files)
+}
+
+fn list_files_impl(root: &Path, relative: &Path, files: &mut BTreeSet<PathBuf>) -> Result<()> {
+    let full_path = root.join(relative);
+    let metadata = fs::metadata(&full_path)
+        .with_context(|| format!("Failed to stat {}", full_path.display()))?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(&full_path)? {
+            let entry = entry?;
+            list_files_impl(root, &relative.join(entry.file_name()), files)?;
+        }
+    } else {
+        files.insert(relative.to_owned());
+    }
+    Ok(())
+}
+
+/// Compares `golden` against `output`, which may each be either a regular
+/// file or a directory (but must be the same kind).
+pub fn compare_paths(golden: &Path, output: &Path) -> Result<TreeComparison> {
+    let mut result = TreeComparison::default();
+
+    if golden.is_dir() || output.is_dir() {
+        let golden_files = list_files(golden)?;
+        let output_files = list_files(output)?;
+
+        for rel in golden_files.difference(&output_files) {
+            result.only_in_golden.push(rel.clone());
+        }
+        for rel in output_files.difference(&golden_files) {
+            result.only_in_output.push(rel.clone());
+        }
+        for rel in golden_files.intersection(&output_files) {
+            if let Some(mismatch) = compare_files(&golden.join(rel), &output.join(rel))? {
+                result.mismatches.push((rel.clone(), mismatch));
+            }
+        }
+    } else if let Some(mismatch) = compare_files(golden, output)? {
+        result.mismatches.push((PathBuf::new(), mismatch));
+    }
+
+    Ok(result)
+}
+
+/// Compares the contents of two regular files, returning `None` if they're
+/// identical.
+fn compare_files(golden: &Path, output: &Path) -> Result<Option<Mismatch>> {
+    let golden_bytes =
+        fs::read(golden).with_context(|| format!("Failed to read {}", golden.display()))?;
+    let output_bytes =
+        fs::read(output).with_context(|| format!("Failed to read {}", output.display()))?;
+
+    if golden_bytes == output_bytes {
+        return Ok(None);
+    }
+
+    match (
+        std::str::from_utf8(&golden_bytes),
+        std::str::from_utf8(&output_bytes),
+    ) {
+        (Ok(golden_text), Ok(output_text)) => {
+            let at = first_difference_location(golden_text, output_text);
+            let diff = unified_diff(golden_text, output_text);
+            Ok(Some(Mismatch::Text { at, diff }))
+        }
+        _ => {
+            let offset = golden_bytes
+                .iter()
+                .zip(output_bytes.iter())
+                .position(|(a, b)| a != b)
+                .unwrap_or_else(|| golden_bytes.len().min(output_bytes.len()))
+                as u64;
+            Ok(Some(Mismatch::Binary { offset }))
+        }
+    }
+}
+
+/// Returns the 1-based line/column of the first byte at which `a` and `b`
+/// diverge.
+fn first_difference_location(a: &str, b: &str) -> Location {
+    let mismatch_offset = a
+        .bytes()
+        .zip(b.bytes())
+        .position(|(x, y)| x != y)
+        .unwrap_or_else(|| a.len().min(b.len()));
+
+    // `mismatch_offset` is a byte offset into the raw byte streams, which isn't guaranteed to
+    // land on a char boundary when `a` and `b` diverge partway through a multi-byte character
+    // (e.g. two characters that share a leading byte). Round down to the nearest boundary before
+    // slicing, so this never panics.
+    let mut mismatch_offset = mismatch_offset;
+    while !a.is_char_boundary(mismatch_offset) {
+        mismatch_offset -= 1;
+    }
+
+    let prefix = &a[..mismatch_offset];
+    let line = prefix.matches('\n').count() + 1;
+    let column = mismatch_offset - prefix.rfind('\n').map(|i| i + 1).unwrap_or(0) + 1;
+    Location { line, column }
+}
+
+/// Produces a unified diff (as used by `diff -u`) of two texts, using the
+/// whole file as a single hunk.
+fn unified_diff(golden_text: &str, output_text: &str) -> String {
+    let golden_lines: Vec<&str> = golden_text.lines().collect();
+    let output_lines: Vec<&str> = output_text.lines().collect();
+    let ops = diff_lines(&golden_lines, &output_lines);
+
+    let mut out = String::new();
+    out.push_str("--- golden\n");
+    out.push_str("+++ output\n");
+    out.push_str(&format!(
+        "@@ -1,{} +1,{} @@\n",
+        golden_lines.len(),
+        output_lines.len()
+    ));
+    for op in ops {
+        match op {
+            DiffOp::Common(line) => out.push_str(&format!(" {line}\n")),
+            DiffOp::Removed(line) => out.push_str(&format!("-{line}\n")),
+            DiffOp::Added(line) => out.push_str(&format!("+{line}\n")),
+        }
+    }
+    out
+}
+
+enum DiffOp<'a> {
+    Common(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// A textbook LCS-based line diff. Quadratic in the number of lines, which is
+/// fine for the small fixtures golden tests compare.
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<DiffOp<'a>> {
+    let n = a.len();
+    let m = b.len();
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if a[i] == b[j] {
+            ops.push(DiffOp::Common(a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push(DiffOp::Removed(a[i]));
+            i += 1;
+        } else {
+            ops.push(DiffOp::Added(b[j]));
+            j += 1;
+        }
+    }
+    ops.extend(a[i..].iter().map(|line| DiffOp::Removed(line)));
+    ops.extend(b[j..].iter().map(|line| DiffOp::Added(line)));
+    ops
+}
+
+/// Recursively copies `src` to `dst`, following symlinks, mirroring
+/// `cp --recursive --dereference`.
+pub fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    let metadata = fs::metadata(src)
+        .with_context(|| format!("Failed to stat {}", src.display()))?;
+    if metadata.is_dir() {
+        fs::create_dir_all(dst)
+            .with_context(|| format!("Failed to create {}", dst.display()))?;
+        for entry in fs::read_dir(src)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &dst.join(entry.file_name()))?;
+        }
+    } else {
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(src, dst)
+            .with_context(|| format!("Failed to copy {} to {}", src.display(), dst.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn identical_directories_match() -> Result<()> {
+        let golden = TempDir::new()?;
+        let output = TempDir::new()?;
+        fs::write(golden.path().join("a.txt"), "aaa\n")?;
+        fs::write(output.path().join("a.txt"), "aaa\n")?;
+
+        let result = compare_paths(golden.path(), output.path())?;
+        assert!(result.is_match());
+        Ok(())
+    }
+
+    #[test]
+    fn reports_missing_and_extra_files() -> Result<()> {
+        let golden = TempDir::new()?;
+        let output = TempDir::new()?;
+        fs::write(golden.path().join("only_golden.txt"), "a\n")?;
+        fs::write(output.path().join("only_output.txt"), "a\n")?;
+
+        let result = compare_paths(golden.path(), output.path())?;
+        assert_eq!(result.only_in_golden, vec![PathBuf::from("only_golden.txt")]);
+        assert_eq!(result.only_in_output, vec![PathBuf::from("only_output.txt")]);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_text_mismatch_location() -> Result<()> {
+        let golden = TempDir::new()?;
+        let output = TempDir::new()?;
+        fs::write(golden.path().join("a.txt"), "one\ntwo\nthree\n")?;
+        fs::write(output.path().join("a.txt"), "one\nTWO\nthree\n")?;
+
+        let result = compare_paths(golden.path(), output.path())?;
+        assert_eq!(result.mismatches.len(), 1);
+        let (path, mismatch) = &result.mismatches[0];
+        assert_eq!(path, &PathBuf::from("a.txt"));
+        match mismatch {
+            Mismatch::Text { at, diff } => {
+                assert_eq!(*at, Location { line: 2, column: 1 });
+                assert!(diff.contains("-two"));
+                assert!(diff.contains("+TWO"));
+            }
+            Mismatch::Binary { .. } => panic!("expected a text mismatch"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reports_text_mismatch_location_with_multibyte_utf8() -> Result<()> {
+        let golden = TempDir::new()?;
+        let output = TempDir::new()?;
+        // "一" (U+4E00) and "丁" (U+4E01) share their first two encoded bytes, so the first
+        // differing byte falls in the middle of the character rather than on a char boundary.
+        fs::write(golden.path().join("a.txt"), "one\n一\nthree\n")?;
+        fs::write(output.path().join("a.txt"), "one\n丁\nthree\n")?;
+
+        let result = compare_paths(golden.path(), output.path())?;
+        assert_eq!(result.mismatches.len(), 1);
+        let (path, mismatch) = &result.mismatches[0];
+        assert_eq!(path, &PathBuf::from("a.txt"));
+        match mismatch {
+            Mismatch::Text { at, .. } => assert_eq!(*at, Location { line: 2, column: 1 }),
+            Mismatch::Binary { .. } => panic!("expected a text mismatch"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn reports_binary_offset() -> Result<()> {
+        let golden = TempDir::new()?;
+        let output = TempDir::new()?;
+        fs::write(golden.path().join("a.bin"), [0u8, 1, 2, 0xff])?;
+        fs::write(output.path().join("a.bin"), [0u8, 1, 9, 0xff])?;
+
+        let result = compare_paths(golden.path(), output.path())?;
+        assert_eq!(result.mismatches.len(), 1);
+        match &result.mismatches[0].1 {
+            Mismatch::Binary { offset } => assert_eq!(*offset, 2),
+            Mismatch::Text { .. } => panic!("expected a binary mismatch"),
+        }
+        Ok(())
+    }
+}
@@ -2,13 +2,13 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
-use std::{
-    path::{Path, PathBuf},
-    process::Command,
-};
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, ensure, Context, Result};
 
+use crate::compare::{compare_paths, copy_tree, Mismatch};
+use crate::normalize::NormalizationRules;
+
 /// The name of the environment variable controlling whether to regenerate
 /// golden data.
 const REGENERATE_VAR_NAME: &str = "ALCHEMY_REGENERATE_GOLDEN";
@@ -89,16 +89,7 @@ pub fn compare_with_golden_data(output: &Path, golden: &Path) -> Result<()> {
         } else {
             ensure!(!real_golden.try_exists()?, "Unknown file type");
         }
-        let status = Command::new("cp")
-            .args(["--recursive", "--dereference", "--"])
-            .arg(output)
-            .arg(real_golden)
-            .status()?;
-        ensure!(
-            status.success(),
-            "Failed to update golden data: {:?}",
-            status
-        );
+        copy_tree(output, real_golden).context("Failed to update golden data")?;
     } else {
         let bazel_target = std::env::var("TEST_TARGET").ok();
         if let Some(ref bazel_target) = bazel_target {
@@ -110,27 +101,88 @@ pub fn compare_with_golden_data(output: &Path, golden: &Path) -> Result<()> {
                 To regenerate them, run 'ALCHEMY_REGENERATE_GOLDEN=1 bazel run {bazel_target}'"
             );
         }
-        let status = Command::new("diff")
-            .args(["-Naru", "--"])
-            .arg(real_golden)
-            .arg(output)
-            .status()?;
-        if !status.success() {
+        let result = compare_paths(real_golden, output)?;
+        if !result.is_match() {
+            let mut message = String::from("Found mismatch with golden data:\n");
+            for path in &result.only_in_golden {
+                message.push_str(&format!("- only in golden: {}\n", path.display()));
+            }
+            for path in &result.only_in_output {
+                message.push_str(&format!("- only in output: {}\n", path.display()));
+            }
+            for (path, mismatch) in &result.mismatches {
+                match mismatch {
+                    Mismatch::Text { at, diff } => {
+                        message.push_str(&format!(
+                            "- {} differs at line {}, column {}:\n{}\n",
+                            path.display(),
+                            at.line,
+                            at.column,
+                            diff
+                        ));
+                    }
+                    Mismatch::Binary { offset } => {
+                        message.push_str(&format!(
+                            "- {}: binary files differ at offset {}\n",
+                            path.display(),
+                            offset
+                        ));
+                    }
+                }
+            }
             // Print a friendly instruction if we're running under Bazel.
             if let Some(bazel_target) = bazel_target {
-                bail!(
-                    "Found mismatch with golden data; \
-                    consider regenerating them with: ALCHEMY_REGENERATE_GOLDEN=1 bazel run {}",
+                message.push_str(&format!(
+                    "consider regenerating them with: ALCHEMY_REGENERATE_GOLDEN=1 bazel run {}",
                     bazel_target,
-                )
-            } else {
-                bail!("Found mismatch with golden data");
+                ));
             }
+            bail!(message);
         }
     }
     Ok(())
 }
 
+/// Copies `src` to `dst`, applying `rules` to every line of every regular
+/// file. Non-UTF-8 files are copied verbatim.
+fn normalize_tree_into(src: &Path, dst: &Path, rules: &NormalizationRules) -> Result<()> {
+    if src.is_dir() {
+        std::fs::create_dir_all(dst)?;
+        for entry in std::fs::read_dir(src)? {
+            let entry = entry?;
+            normalize_tree_into(&entry.path(), &dst.join(entry.file_name()), rules)?;
+        }
+    } else {
+        match std::fs::read_to_string(src) {
+            Ok(text) => std::fs::write(dst, rules.normalize_text(&text))?,
+            // Not valid UTF-8; copy the file as-is rather than mangling it.
+            Err(_) => {
+                std::fs::copy(src, dst)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Same as [`compare_with_golden_data`], but first scrubs non-deterministic
+/// parts of `output` (timestamps, sandbox paths, content hashes, ...) using
+/// `rules`, loaded with [`NormalizationRules::load`].
+///
+/// Golden data is assumed to already be normalized: only `output` is run
+/// through `rules` before comparison, and it's the normalized copy that gets
+/// written back when `ALCHEMY_REGENERATE_GOLDEN` is set, so regenerated
+/// goldens stay stable across runs that only differ in the scrubbed fields.
+pub fn compare_with_golden_data_normalized(
+    output: &Path,
+    golden: &Path,
+    rules: &NormalizationRules,
+) -> Result<()> {
+    let normalized_dir = tempfile::tempdir()?;
+    let normalized_output = normalized_dir.path().join("output");
+    normalize_tree_into(output, &normalized_output, rules)?;
+    compare_with_golden_data(&normalized_output, golden)
+}
+
 #[cfg(test)]
 mod tests {
     use std::{
@@ -2,12 +2,16 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+mod compare;
 mod fakefs;
 mod golden;
 mod namespace;
+mod normalize;
 mod testdata;
 
+pub use compare::*;
 pub use fakefs::*;
 pub use golden::*;
 pub use namespace::*;
+pub use normalize::*;
 pub use testdata::*;
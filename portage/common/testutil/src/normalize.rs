@@ -0,0 +1,216 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Loads a small INI-style ruleset used to scrub non-deterministic output
+//! (timestamps, sandbox paths, content hashes, ...) before comparing it with
+//! golden data.
+//!
+//! A rule file looks like:
+//!
+//! ```ini
+//! [paths]
+//! /tmp/[^/]+ = /tmp/TMPDIR
+//! # A continuation line (starting with whitespace) is appended to the
+//! # previous rule's replacement.
+//! (?P<prefix>bazel-out/[^/]+)/bin/internal =
+//!   ${prefix}/bin/internal
+//!
+//! %include common.rules
+//! %unset /tmp/[^/]+
+//! ```
+//!
+//! Rules are grouped under `[section]` headers purely for readability and to
+//! scope `%unset`; every rule in every section is applied, in file order, to
+//! every line of both the actual output and (when regenerating) nothing else
+//! -- golden data is assumed to already be normalized.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+
+/// An ordered set of find/replace rules to apply line-by-line.
+#[derive(Default)]
+pub struct NormalizationRules {
+    // Keyed by "<section>\n<pattern>" so that `%unset` only affects rules
+    // declared in the same section, while still applying every rule in
+    // load order regardless of section.
+    rules: Vec<(String, Regex, String)>,
+}
+
+impl NormalizationRules {
+    /// Loads a ruleset from `path`, following `%include` directives relative
+    /// to the file that contains them.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut rules = Vec::new();
+        let mut visited = HashSet::new();
+        load_into(path, &mut rules, &mut visited)?;
+        Ok(Self { rules })
+    }
+
+    /// Applies every rule, in order, to a single line.
+    pub fn normalize_line(&self, line: &str) -> String {
+        let mut line = line.to_string();
+        for (_, pattern, replacement) in &self.rules {
+            line = pattern.replace_all(&line, replacement.as_str()).into_owned();
+        }
+        line
+    }
+
+    /// Applies [`Self::normalize_line`] to every line of `text`, preserving
+    /// the presence or absence of a trailing newline.
+    pub fn normalize_text(&self, text: &str) -> String {
+        let had_trailing_newline = text.ends_with('\n');
+        let mut out = text
+            .lines()
+            .map(|line| self.normalize_line(line))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if had_trailing_newline {
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn load_into(path: &Path, rules: &mut Vec<(String, Regex, String)>, visited: &mut HashSet<PathBuf>) -> Result<()> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("Failed to open normalization ruleset {}", path.display()))?;
+    if !visited.insert(canonical) {
+        bail!(
+            "%include cycle detected while loading normalization ruleset {}",
+            path.display()
+        );
+    }
+
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read normalization ruleset {}", path.display()))?;
+
+    let mut section = String::new();
+    let mut pending_key: Option<String> = None;
+
+    for raw_line in text.split('\n') {
+        if raw_line.is_empty() {
+            pending_key = None;
+            continue;
+        }
+
+        if raw_line.starts_with(char::is_whitespace) {
+            // Continuation of the previous rule's replacement.
+            if let Some(key) = &pending_key {
+                let rule = rules
+                    .iter_mut()
+                    .find(|(k, _, _)| k == key)
+                    .context("Continuation line with no preceding rule")?;
+                rule.2.push_str(raw_line.trim());
+            }
+            continue;
+        }
+
+        let line = raw_line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            pending_key = None;
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+            section = name.to_string();
+            pending_key = None;
+            continue;
+        }
+
+        if let Some(rest) = line.strip_prefix("%include ") {
+            let include_path = path
+                .parent()
+                .unwrap_or_else(|| Path::new("."))
+                .join(rest.trim());
+            load_into(&include_path, rules, visited)?;
+            pending_key = None;
+            continue;
+        }
+
+        if let Some(name) = line.strip_prefix("%unset ") {
+            let key = format!("{}\n{}", section, name.trim());
+            rules.retain(|(k, _, _)| k != &key);
+            pending_key = None;
+            continue;
+        }
+
+        let (pattern, replacement) = line
+            .split_once('=')
+            .with_context(|| format!("Invalid rule (expected `regex = replacement`): {line}"))?;
+        let pattern = pattern.trim();
+        let replacement = replacement.trim().to_string();
+        let key = format!("{}\n{}", section, pattern);
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("Invalid regex in normalization rule: {pattern}"))?;
+
+        if let Some(existing) = rules.iter_mut().find(|(k, _, _)| k == &key) {
+            *existing = (key.clone(), regex, replacement);
+        } else {
+            rules.push((key.clone(), regex, replacement));
+        }
+        pending_key = Some(key);
+    }
+
+    visited.remove(&path.canonicalize().unwrap());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn applies_rules_in_order() -> Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("rules.ini"),
+            "[paths]\n/tmp/[a-z0-9]+ = /tmp/TMPDIR\nhash-[0-9a-f]+ = hash-X\n",
+        )?;
+
+        let rules = NormalizationRules::load(&dir.path().join("rules.ini"))?;
+        assert_eq!(
+            rules.normalize_line("output at /tmp/abc123 has hash-deadbeef"),
+            "output at /tmp/TMPDIR has hash-X"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn continuation_line_extends_replacement() -> Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("rules.ini"),
+            "[paths]\n(?P<prefix>bazel-out/[^/]+)/bin/internal-\\d+ =\n  ${prefix}/bin/internal-X\n",
+        )?;
+
+        let rules = NormalizationRules::load(&dir.path().join("rules.ini"))?;
+        assert_eq!(
+            rules.normalize_line("bazel-out/k8-fastbuild/bin/internal-42/foo"),
+            "bazel-out/k8-fastbuild/bin/internal-X/foo"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn include_and_unset() -> Result<()> {
+        let dir = TempDir::new()?;
+        std::fs::write(
+            dir.path().join("base.ini"),
+            "[paths]\n/tmp/[a-z0-9]+ = /tmp/TMPDIR\n",
+        )?;
+        std::fs::write(
+            dir.path().join("override.ini"),
+            "%include base.ini\n[paths]\n%unset /tmp/[a-z0-9]+\n",
+        )?;
+
+        let rules = NormalizationRules::load(&dir.path().join("override.ini"))?;
+        assert_eq!(rules.normalize_line("/tmp/abc123"), "/tmp/abc123");
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2026 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Benchmarks `diagnose_cache_hits` over a synthetic exec log shaped like a real ChromiumOS
+//! build: a long chain of actions, each consuming the previous action's outputs plus a couple of
+//! leaf source files, so most scans have to walk the full transitive input-set graph.
+
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use process_artifacts::commands::diagnose_cache_hits::{
+    diagnose_cache_hits, diagnose_cache_hits_parallel,
+};
+use process_artifacts::processors::execlog::ExecLogProcessor;
+use process_artifacts::proto::spawn::{
+    exec_log_entry::{self, File, InputSet, Output, Spawn},
+    ExecLogEntry,
+};
+
+type EntryType = exec_log_entry::Type;
+type OutputType = exec_log_entry::output::Type;
+
+/// Builds a chain of `count` spawns, each with its own leaf input file and one "linking" input
+/// set that also pulls in the previous spawn's output, so the cache-miss frontier is non-trivial
+/// to compute. Every third spawn is a cache hit.
+fn synthetic_exec_log(count: i32) -> Vec<ExecLogEntry> {
+    let mut entries = Vec::new();
+    let mut previous_output_set: Option<i32> = None;
+
+    for i in 0..count {
+        let file_id = i * 4 + 1;
+        let output_id = i * 4 + 2;
+        let input_set_id = i * 4 + 3;
+        let spawn_id = i * 4 + 4;
+
+        entries.push(ExecLogEntry {
+            id: file_id,
+            r#type: Some(EntryType::File(File {
+                path: format!("src/file_{i}.txt"),
+                ..Default::default()
+            })),
+        });
+
+        let mut transitive_set_ids = Vec::new();
+        if let Some(previous) = previous_output_set {
+            transitive_set_ids.push(previous);
+        }
+        entries.push(ExecLogEntry {
+            id: input_set_id,
+            r#type: Some(EntryType::InputSet(InputSet {
+                file_ids: vec![file_id],
+                transitive_set_ids,
+                ..Default::default()
+            })),
+        });
+
+        entries.push(ExecLogEntry {
+            id: spawn_id,
+            r#type: Some(EntryType::Spawn(Spawn {
+                target_label: format!("//pkg:target_{i}"),
+                mnemonic: "Genrule".to_string(),
+                cache_hit: i % 3 == 0,
+                input_set_id,
+                outputs: vec![Output {
+                    r#type: Some(OutputType::FileId(output_id)),
+                }],
+                ..Default::default()
+            })),
+        });
+
+        entries.push(ExecLogEntry {
+            id: output_id,
+            r#type: Some(EntryType::InputSet(InputSet {
+                file_ids: vec![output_id],
+                ..Default::default()
+            })),
+        });
+
+        previous_output_set = Some(output_id);
+    }
+
+    entries
+}
+
+fn bench_diagnose_cache_hits(c: &mut Criterion) {
+    let entries = synthetic_exec_log(20_000);
+    let processor = ExecLogProcessor::from(&entries);
+    let output_path = std::env::temp_dir().join("diagnose_cache_hits_bench.out");
+
+    let mut group = c.benchmark_group("diagnose_cache_hits");
+    group.bench_function("sequential", |b| {
+        b.iter(|| diagnose_cache_hits(black_box(&output_path), black_box(&processor)).unwrap())
+    });
+    for jobs in [1, 2, 4, 8] {
+        group.bench_with_input(BenchmarkId::new("parallel", jobs), &jobs, |b, &jobs| {
+            b.iter(|| {
+                diagnose_cache_hits_parallel(black_box(&output_path), black_box(&processor), jobs)
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_diagnose_cache_hits);
+criterion_main!(benches);
@@ -14,6 +14,12 @@ fn main() -> Result<()> {
     if let Some(marker_path) = std::env::var_os("WELL_KNOWN_TYPES_MARKER") {
         includes.push(Path::new(&marker_path).parent().unwrap().join("src"));
     }
-    prost_build::compile_protos(&["proto/third_party/spawn.proto"], &includes)?;
+    prost_build::compile_protos(
+        &[
+            "proto/third_party/spawn.proto",
+            "proto/third_party/build_event_stream.proto",
+        ],
+        &includes,
+    )?;
     Ok(())
 }
@@ -0,0 +1,7 @@
+// Copyright 2026 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+pub mod archive_logs;
+pub mod diagnose_cache_hits;
+pub mod prebuilts;
@@ -10,16 +10,20 @@ use std::{
     sync::OnceLock,
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use archive_logs::archive_logs;
 use build_event_processor::BuildEventProcessor;
 use clap::Parser;
+use commands::diagnose_cache_hits::{diagnose_cache_hits, diagnose_cache_hits_parallel};
 use prebuilts::compute_prebuilts;
+use processors::execlog::{read_exec_log_compact, ExecLogProcessor};
 use proto::build_event_stream::BuildEvent;
 
 mod archive_logs;
 mod build_event_processor;
+mod commands;
 mod prebuilts;
+mod processors;
 mod proto;
 
 /// Loads a newline-deliminated JSON file containing Build Event Protocol data.
@@ -71,6 +75,19 @@ struct Args {
     /// flags pointing to the CAS for the packages specified in the BEP file..
     #[arg(long)]
     prebuilts: Option<PathBuf>,
+
+    /// If set, runs cache-hit diagnosis against the compact execution log referenced by the BEP
+    /// (i.e. the build was run with --execution_log_compact_file) and writes the report to this
+    /// path. The report format is selected by this path's extension: `.json` and `.dot` emit the
+    /// cache-miss dependency graph, anything else emits the plain-text report.
+    #[arg(long)]
+    diagnose_cache_hits: Option<PathBuf>,
+
+    /// Number of threads to scan the exec log with for `--diagnose-cache-hits`. If unset, scans
+    /// single-threaded; set this on a real ChromiumOS build, where the exec log can hold millions
+    /// of entries and the single-threaded scan dominates wall-clock time.
+    #[arg(long, value_name = "N")]
+    diagnose_cache_hits_jobs: Option<usize>,
 }
 
 fn main() -> Result<()> {
@@ -87,5 +104,25 @@ fn main() -> Result<()> {
         compute_prebuilts(output_path, &args.workspace, &processor)?;
     }
 
+    if let Some(output_path) = &args.diagnose_cache_hits {
+        let Some(exec_log_path) = processor.get_command_flag("execution_log_compact_file") else {
+            bail!(
+                "--diagnose-cache-hits requires the build to have run with \
+                 --execution_log_compact_file"
+            );
+        };
+        let exec_log_path = args.workspace.join(exec_log_path);
+
+        let exec_log_file = File::open(&exec_log_path)
+            .with_context(|| format!("Failed to open {}", exec_log_path.display()))?;
+        let entries = read_exec_log_compact(exec_log_file)?;
+        let exec_log_processor = ExecLogProcessor::from(&entries);
+
+        match args.diagnose_cache_hits_jobs {
+            Some(jobs) => diagnose_cache_hits_parallel(output_path, &exec_log_processor, jobs)?,
+            None => diagnose_cache_hits(output_path, &exec_log_processor)?,
+        }
+    }
+
     Ok(())
 }
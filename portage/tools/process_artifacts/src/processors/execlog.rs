@@ -3,12 +3,50 @@
 // found in the LICENSE file.
 
 use std::collections::{BTreeMap, BTreeSet};
+use std::io::Read;
 
 use crate::proto::spawn::{exec_log_entry, ExecLogEntry};
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
+use prost::Message;
+use rayon::prelude::*;
 
 type EntryType = exec_log_entry::Type;
 
+/// Reads [`ExecLogEntry`] records from Bazel's compact execution log format
+/// (`--execution_log_compact_file`): a varint-length-delimited stream of serialized
+/// `ExecLogEntry` protos, the same framing [`crate::proto::binary`] uses for the BEP binary
+/// format.
+pub fn read_exec_log_compact<R: Read>(mut reader: R) -> Result<Vec<ExecLogEntry>> {
+    let mut buf = Vec::new();
+    reader
+        .read_to_end(&mut buf)
+        .context("Failed to read compact execution log")?;
+
+    let mut remaining = buf.as_slice();
+    let mut entries = Vec::new();
+    while !remaining.is_empty() {
+        let before = remaining.len();
+        let len = match prost::encoding::decode_varint(&mut remaining) {
+            Ok(len) => len as usize,
+            // A truncated varint at EOF means an interrupted build; stop here instead of failing
+            // the whole file.
+            Err(_) => break,
+        };
+
+        if remaining.len() < len {
+            break;
+        }
+
+        let (record, rest) = remaining.split_at(len);
+        let entry = ExecLogEntry::decode(record)
+            .with_context(|| format!("Failed to decode ExecLogEntry at byte offset {before}"))?;
+        entries.push(entry);
+        remaining = rest;
+    }
+
+    Ok(entries)
+}
+
 struct ExecLogIndex<'e> {
     entries: Vec<&'e ExecLogEntry>,
     index: BTreeMap<i32, &'e EntryType>,
@@ -80,6 +118,46 @@ impl ExecLogProcessor<'_> {
         Ok(intersecting_input_sets)
     }
 
+    /// Same as [`Self::intersecting_input_sets`], but scans entries across a rayon thread pool
+    /// sized to `jobs`. Each chunk of entries keeps its own memoization cache (the whole point of
+    /// going parallel is to avoid one shared lock on it), and the per-chunk result sets are merged
+    /// into a single [`BTreeSet`] so the final, sorted output is identical no matter how the work
+    /// happened to be chunked or how many threads were used.
+    pub fn intersecting_input_sets_parallel(
+        &self,
+        files: impl IntoIterator<Item = i32>,
+        jobs: usize,
+    ) -> Result<Vec<i32>> {
+        let files: BTreeSet<i32> = files.into_iter().collect();
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build()
+            .context("Failed to build rayon thread pool")?;
+
+        let entries: Vec<&ExecLogEntry> = self.entries().collect();
+        let intersecting_input_sets: BTreeSet<i32> = pool.install(|| {
+            entries
+                .into_par_iter()
+                .try_fold(BTreeMap::new, |mut cache, entry| -> Result<BTreeMap<i32, bool>> {
+                    if let Some(EntryType::InputSet(_)) = &entry.r#type {
+                        self.intersects_memoized(entry.id, &files, &mut cache)?;
+                    }
+                    Ok(cache)
+                })
+                .try_reduce(BTreeMap::new, |mut a, b| {
+                    a.extend(b);
+                    Ok(a)
+                })
+        })?
+        .into_iter()
+        .filter(|(_, intersects)| *intersects)
+        .map(|(id, _)| id)
+        .collect();
+
+        Ok(intersecting_input_sets.into_iter().collect())
+    }
+
     fn intersects_memoized(
         &self,
         input_set: i32,
@@ -112,6 +190,130 @@ impl ExecLogProcessor<'_> {
         cache.insert(input_set, intersects);
         Ok(intersects)
     }
+
+    /// Returns the subset of `candidates` that are directly or transitively contained in
+    /// `input_set` — like [`Self::intersecting_input_sets`], but scoped to a single input set and
+    /// reporting *which* candidates matched instead of just whether any did. This lets a caller
+    /// that already knows an input set intersects a set of IDs figure out exactly which ones.
+    ///
+    /// Unlike [`Self::expand_input_set`], a missing `input_set` (e.g. a spawn's unset
+    /// `tool_set_id`, which defaults to 0 and may not correspond to any logged entry) is treated
+    /// as an empty set rather than an error, since callers probe IDs that aren't guaranteed to
+    /// appear in the log.
+    pub fn contained_ids(
+        &self,
+        input_set: i32,
+        candidates: &BTreeSet<i32>,
+    ) -> Result<BTreeSet<i32>> {
+        let mut found = BTreeSet::new();
+        let mut visited = BTreeSet::new();
+        self.contained_ids_into(input_set, candidates, &mut found, &mut visited)?;
+        Ok(found)
+    }
+
+    fn contained_ids_into(
+        &self,
+        input_set: i32,
+        candidates: &BTreeSet<i32>,
+        found: &mut BTreeSet<i32>,
+        visited: &mut BTreeSet<i32>,
+    ) -> Result<()> {
+        if !visited.insert(input_set) {
+            return Ok(());
+        }
+
+        let Some(EntryType::InputSet(set)) = self.index.get(input_set) else {
+            return Ok(());
+        };
+
+        for id in set
+            .file_ids
+            .iter()
+            .chain(set.directory_ids.iter())
+            .chain(set.unresolved_symlink_ids.iter())
+        {
+            if candidates.contains(id) {
+                found.insert(*id);
+            }
+        }
+        for transitive_set_id in &set.transitive_set_ids {
+            self.contained_ids_into(*transitive_set_id, candidates, found, visited)?;
+        }
+
+        Ok(())
+    }
+
+    /// Recursively expands `input_set` (and any `transitive_set_ids` it references) into a flat
+    /// map from each leaf input's path to a digest string, so two spawns' input sets can be
+    /// diffed path-by-path regardless of how the sets happen to be nested.
+    ///
+    /// Files and directories map to their content digest hash. Unresolved symlinks have no
+    /// content of their own, so they map to their target path instead: that's the only thing
+    /// about a symlink input that can actually change.
+    pub fn expand_input_set(&self, input_set: i32) -> Result<BTreeMap<String, String>> {
+        let mut digests = BTreeMap::new();
+        let mut visited = BTreeSet::new();
+        self.expand_input_set_into(input_set, &mut digests, &mut visited)?;
+        Ok(digests)
+    }
+
+    /// Like [`Self::expand_input_set`], but a missing `input_set` (e.g. a spawn's unset
+    /// `tool_set_id`, which defaults to 0 and may not correspond to any logged entry) is treated
+    /// as an empty set rather than an error, mirroring [`Self::contained_ids`].
+    pub fn expand_input_set_or_empty(&self, input_set: i32) -> Result<BTreeMap<String, String>> {
+        if !matches!(self.index.get(input_set), Some(EntryType::InputSet(_))) {
+            return Ok(BTreeMap::new());
+        }
+        self.expand_input_set(input_set)
+    }
+
+    fn expand_input_set_into(
+        &self,
+        input_set: i32,
+        digests: &mut BTreeMap<String, String>,
+        visited: &mut BTreeSet<i32>,
+    ) -> Result<()> {
+        // Guard against cycles between InputSets (and redundant re-expansion of diamond-shaped
+        // transitive sets).
+        if !visited.insert(input_set) {
+            return Ok(());
+        }
+
+        let Some(EntryType::InputSet(set)) = self.index.get(input_set) else {
+            bail!("Input set {input_set} not found");
+        };
+
+        for file_id in &set.file_ids {
+            let Some(EntryType::File(file)) = self.index.get(*file_id) else {
+                bail!("File {file_id} not found");
+            };
+            digests.insert(file.path.clone(), digest_to_string(&file.digest));
+        }
+        for directory_id in &set.directory_ids {
+            let Some(EntryType::Directory(directory)) = self.index.get(*directory_id) else {
+                bail!("Directory {directory_id} not found");
+            };
+            digests.insert(directory.path.clone(), digest_to_string(&directory.digest));
+        }
+        for symlink_id in &set.unresolved_symlink_ids {
+            let Some(EntryType::UnresolvedSymlink(symlink)) = self.index.get(*symlink_id) else {
+                bail!("Unresolved symlink {symlink_id} not found");
+            };
+            digests.insert(symlink.path.clone(), format!("-> {}", symlink.target_path));
+        }
+        for transitive_set_id in &set.transitive_set_ids {
+            self.expand_input_set_into(*transitive_set_id, digests, visited)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn digest_to_string(digest: &Option<exec_log_entry::Digest>) -> String {
+    match digest {
+        Some(digest) => digest.hash.clone(),
+        None => "<no digest>".to_string(),
+    }
 }
 
 #[cfg(test)]
@@ -120,6 +322,53 @@ mod tests {
 
     use super::*;
 
+    fn encode_record(entry: &ExecLogEntry) -> Vec<u8> {
+        let mut out = Vec::new();
+        prost::encoding::encode_varint(entry.encoded_len() as u64, &mut out);
+        entry.encode(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn reads_compact_exec_log_stream() -> Result<()> {
+        let entry = ExecLogEntry {
+            id: 1,
+            r#type: Some(EntryType::File(File {
+                path: "x".to_string(),
+                ..Default::default()
+            })),
+        };
+
+        let mut data = Vec::new();
+        data.extend(encode_record(&entry));
+        data.extend(encode_record(&entry));
+
+        let entries = read_exec_log_compact(data.as_slice())?;
+        assert_eq!(entries, vec![entry.clone(), entry]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tolerates_truncated_trailing_record() -> Result<()> {
+        let entry = ExecLogEntry {
+            id: 1,
+            r#type: Some(EntryType::File(File {
+                path: "x".to_string(),
+                ..Default::default()
+            })),
+        };
+
+        let mut data = encode_record(&entry);
+        data.extend(encode_record(&entry));
+        data.truncate(data.len() - 2);
+
+        let entries = read_exec_log_compact(data.as_slice())?;
+        assert_eq!(entries, vec![entry]);
+
+        Ok(())
+    }
+
     #[test]
     fn intersecting_input_sets() -> Result<()> {
         let entries = vec![
@@ -199,6 +448,58 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn contained_ids() -> Result<()> {
+        let entries = vec![
+            ExecLogEntry {
+                id: 1,
+                r#type: Some(EntryType::File(File {
+                    path: "x".to_string(),
+                    ..Default::default()
+                })),
+            },
+            ExecLogEntry {
+                id: 2,
+                r#type: Some(EntryType::File(File {
+                    path: "y".to_string(),
+                    ..Default::default()
+                })),
+            },
+            ExecLogEntry {
+                id: 11,
+                r#type: Some(EntryType::InputSet(InputSet {
+                    file_ids: vec![1],
+                    ..Default::default()
+                })),
+            },
+            ExecLogEntry {
+                id: 101,
+                r#type: Some(EntryType::InputSet(InputSet {
+                    transitive_set_ids: vec![11],
+                    ..Default::default()
+                })),
+            },
+        ];
+        let processor = ExecLogProcessor::from(&entries);
+
+        assert_eq!(
+            processor.contained_ids(101, &BTreeSet::from([1, 2]))?,
+            BTreeSet::from([1])
+        );
+        assert_eq!(
+            processor.contained_ids(11, &BTreeSet::from([2]))?,
+            BTreeSet::new()
+        );
+        // An input set ID that doesn't appear in the log (e.g. an unset tool_set_id) is treated
+        // as empty rather than an error.
+        assert_eq!(
+            processor.contained_ids(0, &BTreeSet::from([1]))?,
+            BTreeSet::new()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn intersecting_input_sets_deeply_nested() -> Result<()> {
         let mut entries = vec![
@@ -259,4 +560,59 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn intersecting_input_sets_parallel_matches_sequential() -> Result<()> {
+        let mut entries = vec![
+            ExecLogEntry {
+                id: 1,
+                r#type: Some(EntryType::File(File {
+                    path: "x".to_string(),
+                    ..Default::default()
+                })),
+            },
+            ExecLogEntry {
+                id: 2,
+                r#type: Some(EntryType::File(File {
+                    path: "y".to_string(),
+                    ..Default::default()
+                })),
+            },
+            ExecLogEntry {
+                id: 3,
+                r#type: Some(EntryType::InputSet(InputSet {
+                    file_ids: vec![1, 2],
+                    ..Default::default()
+                })),
+            },
+            ExecLogEntry {
+                id: 4,
+                r#type: Some(EntryType::InputSet(InputSet {
+                    file_ids: vec![2],
+                    transitive_set_ids: vec![3],
+                    ..Default::default()
+                })),
+            },
+        ];
+        for id in 5..1000 {
+            entries.push(ExecLogEntry {
+                id,
+                r#type: Some(EntryType::InputSet(InputSet {
+                    transitive_set_ids: vec![id - 2, id - 1],
+                    ..Default::default()
+                })),
+            });
+        }
+        let processor = ExecLogProcessor::from(&entries);
+
+        for jobs in [1, 2, 4, 8] {
+            assert_eq!(
+                processor.intersecting_input_sets_parallel([1], jobs)?,
+                processor.intersecting_input_sets([1])?,
+                "jobs={jobs}"
+            );
+        }
+
+        Ok(())
+    }
 }
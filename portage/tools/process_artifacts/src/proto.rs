@@ -8,6 +8,9 @@
 
 use serde::Deserialize;
 
+pub mod binary;
+pub mod spawn;
+
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct PhantomValue;
 
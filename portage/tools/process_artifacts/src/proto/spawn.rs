@@ -0,0 +1,13 @@
+// Copyright 2026 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Raw prost-generated types for Bazel's compact execution log format.
+//!
+//! Unlike [`super::build_event_stream`], these are used directly as generated (snake_case fields,
+//! `r#type` oneofs and all) rather than wrapped in hand-written, JSON-friendly structs, since
+//! nothing here is ever deserialized from JSON.
+//!
+//! See: https://github.com/bazelbuild/bazel/blob/HEAD/src/main/protobuf/spawn.proto
+
+include!(concat!(env!("OUT_DIR"), "/spawn.rs"));